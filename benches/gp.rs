@@ -13,7 +13,7 @@ fn create_random_data(shape: (usize, usize)) -> (DMatrix<f64>, DVector<f64>) {
     let sz = shape.0 * shape.1;
     // x data cannot have any duplicates, or the variance matrix will not be positive-definite
     (
-        DMatrix::<f64>::from_iterator(shape.0, shape.1, (0..sz).into_iter().map(|v| v as f64)),
+        DMatrix::<f64>::from_iterator(shape.0, shape.1, (0..sz).map(|v| v as f64)),
         DVector::<f64>::new_random(shape.1),
     )
 }
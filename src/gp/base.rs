@@ -1,33 +1,164 @@
+use std::f64::consts::PI;
+
 use nalgebra::{Cholesky, DMatrix, DVector, Dynamic};
+use rand::Rng;
 use rayon::prelude::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
 
 use crate::{
     // indexing::index_to_2d,
-    kernels::{Kernel, TriangleSide},
+    kernels::{Kernel, KernelGradient, TriangleSide},
     linalg::{
         errors::IncompatibleShapeError, par_solve_lower_triangular_unchecked, par_tr_matmul,
         par_tr_matmul_diag, util::par_add_diagonal_mut_unchecked,
     },
+    means::{MeanFunction, MeanGradient, Zero},
+    parameterized::Jacobian,
 };
 
-use super::errors::GPCompilationError;
+use super::{
+    errors::GPCompilationError,
+    jitter::{cholesky_with_jitter, JitterPolicy},
+    noise::Noise,
+    posterior,
+    prediction::Prediction,
+    sample::sample_mvn,
+};
 
 /// Standard Gaussian Process
 ///
 /// Definition:
 ///
-/// `f = K*T [K + sI]^-1 y`
+/// `f = m* + K*T [K + S]^-1 (y - m)`
 ///
-/// `cov = K** - K*T [K + sI]^-1 K*`
+/// `cov = K** - K*T [K + S]^-1 K*`
+///
+/// where `m` is the prior mean, which is zero unless set with `with_mean`,
+/// and `S` is the diagonal noise covariance, `sI` unless the noise is given per point.
 #[derive(Debug)]
-pub struct GP<K: Kernel> {
+pub struct GP<K: Kernel, M: MeanFunction = Zero> {
     kernel: K,
-    noise: f64,
+    noise: Noise,
+    mean: M,
+    jitter: Option<JitterPolicy>,
 }
 
 impl<K: Kernel> GP<K> {
     pub fn new(kernel: K, noise: f64) -> Self {
-        GP { kernel, noise }
+        GP {
+            kernel,
+            noise: Noise::Constant(noise),
+            mean: Zero,
+            jitter: None,
+        }
+    }
+
+    /// Create a GP with a known noise variance for each training point
+    ///
+    /// # Examples
+    /// ```rust
+    /// use gprs::{gp::GP, kernels::RBF};
+    /// use nalgebra::{DVector, DMatrix};
+    ///
+    /// let x = DMatrix::from_vec(1, 3, vec![0.0, 1.0, 2.0]);
+    /// let y = DVector::from_vec(vec![0.0, 1.0, 0.5]);
+    ///
+    /// // the last measurement is much less certain
+    /// let noise = DVector::from_vec(vec![0.01, 0.01, 1.0]);
+    ///
+    /// let compiled = GP::new_heteroscedastic(RBF::new(vec![1.0], 1.0), noise)
+    ///     .compile(x, &y)
+    ///     .unwrap();
+    /// ```
+    pub fn new_heteroscedastic(kernel: K, noise: DVector<f64>) -> Self {
+        GP {
+            kernel,
+            noise: Noise::PerPoint(noise),
+            mean: Zero,
+            jitter: None,
+        }
+    }
+}
+
+impl<K: Kernel, M: MeanFunction> GP<K, M> {
+    /// Replace the prior mean function
+    ///
+    /// # Examples
+    /// ```rust
+    /// use gprs::{gp::GP, kernels::RBF, means::Constant};
+    /// use nalgebra::{DVector, DMatrix};
+    ///
+    /// let x = DMatrix::from_vec(1, 2, vec![0.0, 1.0]);
+    /// let y = DVector::from_vec(vec![100.0, 101.0]);
+    ///
+    /// let compiled = GP::new(RBF::new(vec![0.1], 1.0), 0.1)
+    ///     .with_mean(Constant::new(100.0))
+    ///     .compile(x, &y)
+    ///     .unwrap();
+    ///
+    /// // far from the data, predictions revert to the prior mean
+    /// let far = DMatrix::from_vec(1, 1, vec![50.0]);
+    /// assert!((compiled.mean(&far).unwrap()[0] - 100.0).abs() < 1e-12);
+    /// ```
+    pub fn with_mean<N: MeanFunction>(self, mean: N) -> GP<K, N> {
+        GP {
+            kernel: self.kernel,
+            noise: self.noise,
+            mean,
+            jitter: self.jitter,
+        }
+    }
+
+    /// Replace the observation noise
+    pub fn with_noise<N: Into<Noise>>(self, noise: N) -> Self {
+        GP {
+            noise: noise.into(),
+            ..self
+        }
+    }
+
+    /// Retry compilation with jitter on the diagonal when the covariance matrix is not positive definite
+    ///
+    /// By default, compilation fails immediately.
+    pub fn with_jitter(self, policy: JitterPolicy) -> Self {
+        GP {
+            jitter: Some(policy),
+            ..self
+        }
+    }
+
+    /// Create a GP with new parameters and the same settings as this one
+    pub(crate) fn rebuild(&self, kernel: K, noise: Noise, mean: M) -> Self {
+        GP {
+            kernel,
+            noise,
+            mean,
+            jitter: self.jitter,
+        }
+    }
+
+    /// The covariance kernel
+    pub fn kernel(&self) -> &K {
+        &self.kernel
+    }
+
+    /// The noise variance added to the diagonal of the covariance matrix
+    pub fn noise(&self) -> &Noise {
+        &self.noise
+    }
+
+    /// The prior mean function
+    pub fn mean_function(&self) -> &M {
+        &self.mean
+    }
+
+    /// The jitter policy used when the covariance matrix is not positive definite
+    pub fn jitter_policy(&self) -> Option<&JitterPolicy> {
+        self.jitter.as_ref()
+    }
+
+    /// Split into the kernel, noise, mean function and jitter policy
+    pub(crate) fn into_parts(self) -> (K, Noise, M, Option<JitterPolicy>) {
+        (self.kernel, self.noise, self.mean, self.jitter)
     }
 
     /// Compile this GP for training or estimation. Consumes `self` and `x`.
@@ -62,7 +193,7 @@ impl<K: Kernel> GP<K> {
         self,
         x: DMatrix<f64>,
         y: &DVector<f64>,
-    ) -> Result<CompiledGP<K>, GPCompilationError> {
+    ) -> Result<CompiledGP<K, M>, GPCompilationError> {
         if x.shape().1 != y.len() {
             return Err(GPCompilationError::IncompatibleShapeError(
                 IncompatibleShapeError {
@@ -76,63 +207,509 @@ impl<K: Kernel> GP<K> {
             .call_triangular(&x, TriangleSide::LOWER)
             .map_err(GPCompilationError::IncompatibleShapeError)?;
 
-        // SAFETY: kxx is guaranteed to be square
-        unsafe {
-            par_add_diagonal_mut_unchecked(&mut kxx, &self.noise);
-        }
+        self.noise
+            .add_to_diagonal(&mut kxx)
+            .map_err(GPCompilationError::IncompatibleShapeError)?;
 
-        let cholesky = kxx
-            .cholesky()
-            .ok_or(GPCompilationError::NonPositiveDefiniteError)?;
-        let alpha = cholesky.solve(y);
+        let (cholesky, jitter) = cholesky_with_jitter(kxx, self.jitter.as_ref())?;
+        let residual = y
+            - self
+                .mean
+                .call(&x)
+                .map_err(GPCompilationError::IncompatibleShapeError)?;
+        let alpha = cholesky.solve(&residual);
 
         Ok(CompiledGP {
             cholesky,
             alpha,
             kernel: self.kernel,
+            noise: self.noise,
+            jitter,
+            policy: self.jitter,
+            mean: self.mean,
             x,
+            y: residual,
         })
     }
+
+    /// Draw functions from the prior at `x`, one sample per column
+    ///
+    /// Samples are centered on the prior mean function. The noise is not included, and the kernel
+    /// matrix is factorized with jitter from the GP's `JitterPolicy`, or the default policy,
+    /// scaled by the mean prior variance. The result has one row per column of `x`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use gprs::{gp::GP, kernels::{Periodic, RBF}};
+    /// use nalgebra::DMatrix;
+    /// use rand::{rngs::StdRng, SeedableRng};
+    ///
+    /// let gp = GP::new(RBF::new(vec![1.0], 1.0) * Periodic::new(2.0, 1.0, 1.0), 0.1);
+    ///
+    /// let x = DMatrix::from_fn(1, 50, |_, j| j as f64 * 0.1);
+    /// let mut rng = StdRng::seed_from_u64(0);
+    ///
+    /// let samples = gp.sample_prior(&x, 5, &mut rng).unwrap();
+    /// assert_eq!(samples.shape(), (50, 5));
+    /// ```
+    pub fn sample_prior<R: Rng + ?Sized>(
+        &self,
+        x: &DMatrix<f64>,
+        n_samples: usize,
+        rng: &mut R,
+    ) -> Result<DMatrix<f64>, GPCompilationError> {
+        Ok(self.sample_prior_with_jitter(x, n_samples, rng)?.0)
+    }
+
+    /// Draw functions from the prior at `x` like `sample_prior`, and also return the jitter that was added
+    /// to the diagonal of the kernel matrix
+    pub fn sample_prior_with_jitter<R: Rng + ?Sized>(
+        &self,
+        x: &DMatrix<f64>,
+        n_samples: usize,
+        rng: &mut R,
+    ) -> Result<(DMatrix<f64>, f64), GPCompilationError> {
+        let kxx = self
+            .kernel
+            .call_triangular(x, TriangleSide::LOWER)
+            .map_err(GPCompilationError::IncompatibleShapeError)?;
+        let mean = self
+            .mean
+            .call(x)
+            .map_err(GPCompilationError::IncompatibleShapeError)?;
+
+        sample_mvn(&mean, kxx, n_samples, self.jitter.as_ref(), rng)
+    }
 }
 
 pub type GPResult<T> = Result<T, IncompatibleShapeError>;
 
+/// Leave-one-out predictions at each training point, from a GP trained on the other points
+#[derive(Debug, Clone, PartialEq)]
+pub struct LooResult {
+    /// The predicted mean at each training point
+    pub mean: DVector<f64>,
+    /// The predicted variance of each noisy observation
+    pub var: DVector<f64>,
+    /// The log density of each observation under its prediction
+    pub log_density: DVector<f64>,
+}
+
+impl LooResult {
+    /// The LOO log predictive probability, summed over all observations
+    ///
+    /// Like the log marginal likelihood, higher is better.
+    pub fn score(&self) -> f64 {
+        self.log_density.sum()
+    }
+}
+
 #[derive(Debug)]
-pub struct CompiledGP<K: Kernel> {
-    /// The cholesky decomposition of (K + noise * I)
+pub struct CompiledGP<K: Kernel, M: MeanFunction = Zero> {
+    /// The cholesky decomposition of (K + S + jitter * I)
     cholesky: Cholesky<f64, Dynamic>,
     /// Factor to compute mean
     alpha: DVector<f64>,
     /// The original kernel
     kernel: K,
+    /// The noise variance
+    noise: Noise,
+    /// The jitter added to the diagonal to make the covariance matrix positive definite
+    jitter: f64,
+    /// The jitter policy of the GP, also used to draw samples
+    policy: Option<JitterPolicy>,
+    /// The prior mean function
+    mean: M,
     /// The input data set
     x: DMatrix<f64>,
+    /// The output data set, less the prior mean
+    y: DVector<f64>,
 }
 
-impl<K: Kernel> CompiledGP<K> {
+impl<K: Kernel, M: MeanFunction> CompiledGP<K, M> {
+    /// The covariance kernel
+    pub fn kernel(&self) -> &K {
+        &self.kernel
+    }
+
+    /// The noise variance added to the diagonal of the covariance matrix
+    pub fn noise(&self) -> &Noise {
+        &self.noise
+    }
+
+    /// The prior mean function
+    pub fn mean_function(&self) -> &M {
+        &self.mean
+    }
+
+    /// The jitter that was added to the diagonal of the covariance matrix during compilation
+    ///
+    /// This is zero unless a `JitterPolicy` was needed to make the matrix positive definite.
+    pub fn jitter(&self) -> f64 {
+        self.jitter
+    }
+
+    /// Add observations to the training data, without refactorizing the covariance matrix
+    ///
+    /// The cholesky factor is extended with a block update, which costs `O(n^2 m)` for `m` new points
+    /// instead of `O((n + m)^3)`. The same constant noise and jitter are added to the new points.
+    ///
+    /// Returns `MissingNoiseError` if the GP has per-point noise; use `add_noisy_observations` instead.
+    /// The GP is unchanged if an error is returned.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use gprs::{gp::GP, kernels::RBF};
+    /// use nalgebra::{DVector, DMatrix};
+    ///
+    /// let x = DMatrix::from_vec(1, 2, vec![0.0, 1.0]);
+    /// let y = DVector::from_vec(vec![0.0, 1.0]);
+    ///
+    /// let mut compiled = GP::new(RBF::new(vec![1.0], 1.0), 0.0).compile(x, &y).unwrap();
+    ///
+    /// let x_new = DMatrix::from_vec(1, 1, vec![2.0]);
+    /// let y_new = DVector::from_vec(vec![0.5]);
+    /// compiled.add_observations(&x_new, &y_new).unwrap();
+    ///
+    /// assert!((compiled.mean(&x_new).unwrap()[0] - 0.5).abs() < 1e-9);
+    /// ```
+    pub fn add_observations(
+        &mut self,
+        x_new: &DMatrix<f64>,
+        y_new: &DVector<f64>,
+    ) -> Result<(), GPCompilationError> {
+        let noise = match &self.noise {
+            Noise::Constant(noise) => Noise::Constant(*noise),
+            Noise::PerPoint(_) => return Err(GPCompilationError::MissingNoiseError),
+        };
+
+        self.extend(x_new, y_new, &noise)
+    }
+
+    /// Add observations with a known noise variance for each new point
+    ///
+    /// If the GP has a constant noise, it is converted to per-point noise.
+    /// See `add_observations`.
+    pub fn add_noisy_observations(
+        &mut self,
+        x_new: &DMatrix<f64>,
+        y_new: &DVector<f64>,
+        noise_new: &DVector<f64>,
+    ) -> Result<(), GPCompilationError> {
+        let n = self.y.len();
+        self.extend(x_new, y_new, &Noise::PerPoint(noise_new.clone_owned()))?;
+
+        let mut noise = match &self.noise {
+            Noise::Constant(noise) => DVector::from_element(n, *noise),
+            Noise::PerPoint(noise) => noise.clone_owned(),
+        };
+        noise.extend(noise_new.iter().copied());
+        self.noise = Noise::PerPoint(noise);
+
+        Ok(())
+    }
+
+    /// Extend the training data and cholesky factor with new points, with `noise` on the new diagonal
+    ///
+    /// With `L' = [L 0; B' C]`, `B = L^-1 K(x, x_new)` and `C C' = K(x_new, x_new) + S_new - B' B`.
+    fn extend(
+        &mut self,
+        x_new: &DMatrix<f64>,
+        y_new: &DVector<f64>,
+        noise: &Noise,
+    ) -> Result<(), GPCompilationError> {
+        let n = self.x.ncols();
+        let m = x_new.ncols();
+
+        if x_new.nrows() != self.x.nrows() || m != y_new.len() {
+            return Err(GPCompilationError::IncompatibleShapeError(
+                IncompatibleShapeError {
+                    shapes: vec![self.x.shape(), x_new.shape(), y_new.shape()],
+                },
+            ));
+        }
+
+        let residual = y_new
+            - self
+                .mean
+                .call(x_new)
+                .map_err(GPCompilationError::IncompatibleShapeError)?;
+
+        // the same blocks that `compile` would compute for the concatenated data
+        let k_x_xn = self
+            .kernel
+            .call_disjoint(&self.x, x_new)
+            .map_err(GPCompilationError::IncompatibleShapeError)?;
+        let mut schur = self
+            .kernel
+            .call_triangular(x_new, TriangleSide::LOWER)
+            .map_err(GPCompilationError::IncompatibleShapeError)?;
+
+        noise
+            .add_to_diagonal(&mut schur)
+            .map_err(GPCompilationError::IncompatibleShapeError)?;
+        // SAFETY: schur is square
+        unsafe {
+            par_add_diagonal_mut_unchecked(&mut schur, &self.jitter);
+        }
+
+        let b = par_solve_lower_triangular_unchecked(self.cholesky.l_dirty(), &k_x_xn);
+        let btb = par_tr_matmul(&b, &b).map_err(GPCompilationError::IncompatibleShapeError)?;
+
+        schur
+            .as_mut_slice()
+            .into_par_iter()
+            .zip(btb)
+            .for_each(|(s, v)| *s -= v);
+
+        let c = schur
+            .cholesky()
+            .ok_or(GPCompilationError::NonPositiveDefiniteError)?;
+
+        let mut l = self
+            .cholesky
+            .l_dirty()
+            .clone_owned()
+            .resize(n + m, n + m, 0.0);
+        l.slice_mut((n, 0), (m, n)).tr_copy_from(&b);
+        l.slice_mut((n, n), (m, m)).copy_from(c.l_dirty());
+
+        self.cholesky = Cholesky::pack_dirty(l);
+
+        let mut x = self.x.clone_owned().resize_horizontally(n + m, 0.0);
+        x.columns_mut(n, m).copy_from(x_new);
+        self.x = x;
+
+        self.y.extend(residual.iter().copied());
+        self.alpha = self.cholesky.solve(&self.y);
+
+        Ok(())
+    }
+
+    /// Remove observations from the training data, without refactorizing the covariance matrix
+    ///
+    /// Each removal downdates the cholesky factor in `O(n^2)`. Duplicate indices are ignored.
+    ///
+    /// # Panics
+    /// If any index is out of bounds
+    ///
+    /// # Examples
+    /// ```rust
+    /// use gprs::{gp::GP, kernels::RBF};
+    /// use nalgebra::{DVector, DMatrix};
+    ///
+    /// let x = DMatrix::from_vec(1, 3, vec![0.0, 1.0, 2.0]);
+    /// let y = DVector::from_vec(vec![0.0, 1.0, 0.5]);
+    ///
+    /// let mut compiled = GP::new(RBF::new(vec![1.0], 1.0), 0.1).compile(x, &y).unwrap();
+    ///
+    /// // slide the window forward
+    /// compiled.remove_observations(&[0]);
+    ///
+    /// assert_eq!(compiled.log_marginal_likelihood_gradient().unwrap().len(), 2);
+    /// ```
+    pub fn remove_observations(&mut self, indices: &[usize]) {
+        let n = self.y.len();
+        let mut indices = indices.to_vec();
+        indices.sort_unstable();
+        indices.dedup();
+
+        if let Some(last) = indices.last() {
+            assert!(*last < n, "observation index {} is out of bounds", last);
+        }
+
+        // remove from the end, so the remaining indices stay valid
+        for index in indices.iter().rev() {
+            self.cholesky = self.cholesky.remove_column(*index);
+        }
+
+        self.x = self.x.clone_owned().remove_columns_at(&indices);
+        self.y = self.y.clone_owned().remove_rows_at(&indices);
+
+        if let Noise::PerPoint(noise) = &self.noise {
+            self.noise = Noise::PerPoint(noise.clone_owned().remove_rows_at(&indices));
+        }
+
+        self.alpha = self.cholesky.solve(&self.y);
+    }
+
+    /// Replace the output of one observation
+    ///
+    /// The covariance matrix does not depend on the outputs, so only the mean weights are recomputed.
+    ///
+    /// # Panics
+    /// If `index` is out of bounds
+    pub fn set_observation(&mut self, index: usize, y: f64) -> GPResult<()> {
+        let mean = self.mean.call(&self.x.columns(index, 1).into_owned())?;
+
+        self.y[index] = y - mean[0];
+        self.alpha = self.cholesky.solve(&self.y);
+
+        Ok(())
+    }
+
+    /// Compute the log marginal likelihood of the training data
+    ///
+    /// `log p(y | x) = -0.5 y' [K + S]^-1 y - 0.5 log|K + S| - 0.5 n log(2 pi)`
+    ///
+    /// # Examples
+    /// ```rust
+    /// use gprs::{gp::GP, kernels::RBF};
+    /// use nalgebra::{DVector, DMatrix};
+    ///
+    /// let x = DMatrix::from_vec(1, 3, vec![0.0, 1.0, 2.0]);
+    /// let y = DVector::from_vec(vec![0.0, 1.0, 0.5]);
+    ///
+    /// let smooth = GP::new(RBF::new(vec![1.0], 1.0), 0.1).compile(x.clone(), &y).unwrap();
+    /// let rough = GP::new(RBF::new(vec![0.01], 1.0), 0.1).compile(x, &y).unwrap();
+    ///
+    /// assert!(smooth.log_marginal_likelihood() > rough.log_marginal_likelihood());
+    /// ```
+    pub fn log_marginal_likelihood(&self) -> f64 {
+        posterior::log_marginal_likelihood(&self.cholesky, &self.y, &self.alpha)
+    }
+
+    /// Compute the gradient of the log marginal likelihood w.r.t. the noise variance
+    ///
+    /// `dL/ds = 0.5 tr(a a' - [K + S]^-1)`
+    ///
+    /// With per-point noise, this is the gradient w.r.t. a constant added to every noise variance.
+    pub fn log_marginal_likelihood_noise_gradient(&self) -> f64 {
+        0.5 * self.likelihood_weights().trace()
+    }
+
+    /// Compute `a a' - [K + S]^-1`, the weights of `dK/dθ` in the likelihood gradient
+    fn likelihood_weights(&self) -> DMatrix<f64> {
+        let mut w = self.cholesky.inverse();
+        w.ger(1.0, &self.alpha, &self.alpha, -1.0);
+        w
+    }
+
+    /// Compute leave-one-out predictions for every training point in closed form
+    ///
+    /// With `P = [K + S]^-1`, removing point `i` predicts `y_i - a_i / P_ii` with variance `1 / P_ii`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use gprs::{gp::GP, kernels::RBF};
+    /// use nalgebra::{DVector, DMatrix};
+    ///
+    /// let x = DMatrix::from_fn(1, 10, |_, j| j as f64 * 0.5);
+    /// let y = DVector::from_fn(10, |i, _| (i as f64 * 0.5).sin());
+    ///
+    /// let smooth = GP::new(RBF::new(vec![1.0], 1.0), 0.01).compile(x.clone(), &y).unwrap();
+    /// let rough = GP::new(RBF::new(vec![0.05], 1.0), 0.01).compile(x, &y).unwrap();
+    ///
+    /// // a rough GP cannot predict a point from its neighbours
+    /// assert!(smooth.loo().unwrap().score() > rough.loo().unwrap().score());
+    /// ```
+    pub fn loo(&self) -> GPResult<LooResult> {
+        let precision = self.cholesky.inverse().diagonal();
+        let prior = self.mean.call(&self.x)?;
+
+        let var = precision.map(|p| 1.0 / p);
+        let error = self.alpha.component_div(&precision);
+        let mean = &prior + &self.y - &error;
+        let log_density = error.zip_map(&var, |e, v| -0.5 * (e * e / v + (2.0 * PI * v).ln()));
+
+        Ok(LooResult {
+            mean,
+            var,
+            log_density,
+        })
+    }
+
+    /// Compute the weights of `dK/dθ` and of the mean jacobian in the LOO score gradient
+    ///
+    /// With `u = a / diag(P)` and `c = (1 + a^2 / diag(P)) / (2 diag(P))`:
+    ///
+    /// `dS/dθ = tr(0.5 (P u a' + a u' P - 2 P diag(c) P) dK/dθ)`
+    ///
+    /// `dS/dθm = dm/dθm' P u`
+    fn loo_weights(&self) -> (DMatrix<f64>, DVector<f64>) {
+        let inverse = self.cholesky.inverse();
+        let precision = inverse.diagonal();
+
+        let u = self.alpha.component_div(&precision);
+        let c = self
+            .alpha
+            .zip_map(&precision, |a, p| 0.5 * (1.0 + a * a / p) / p);
+
+        let pu = &inverse * &u;
+
+        let mut scaled = inverse.clone();
+        scaled
+            .column_iter_mut()
+            .zip(c.iter())
+            .for_each(|(mut col, ci)| col *= *ci);
+
+        let mut w = -(scaled * &inverse);
+        w.ger(0.5, &pu, &self.alpha, 1.0);
+        w.ger(0.5, &self.alpha, &pu, 1.0);
+
+        (w, pu)
+    }
+
     /// Compute the mean and variance from input data
     pub fn call(&self, x: &DMatrix<f64>) -> GPResult<(DVector<f64>, DVector<f64>)> {
         let k_x_xp = self.kernel.call(&self.x, x)?;
 
-        let mean = self.mean_precomputed(&k_x_xp)?;
+        let mean = self.mean_precomputed(x, &k_x_xp)?;
         let var = self.var_precomputed(x, &k_x_xp)?;
 
         Ok((mean, var))
     }
 
+    /// Predict the latent function at `x`, without observation noise
+    ///
+    /// Unlike `call`, the result has helpers for intervals, quantiles and scoring held-out data.
+    pub fn predict(&self, x: &DMatrix<f64>) -> GPResult<Prediction> {
+        let (mean, var) = self.call(x)?;
+        Ok(Prediction { mean, var })
+    }
+
+    /// Predict noisy observations at `x`
+    ///
+    /// `noise` is the observation noise at `x`, which need not match the training noise.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use gprs::{gp::{Noise, GP}, kernels::RBF};
+    /// use nalgebra::{DVector, DMatrix};
+    ///
+    /// let x = DMatrix::from_vec(1, 4, vec![0.0, 1.0, 2.0, 3.0]);
+    /// let y = DVector::from_vec(vec![0.0, 1.0, 0.5, 0.2]);
+    ///
+    /// let compiled = GP::new(RBF::new(vec![1.0], 1.0), 0.1).compile(x, &y).unwrap();
+    ///
+    /// let x_test = DMatrix::from_vec(1, 2, vec![0.5, 2.5]);
+    /// let y_test = DVector::from_vec(vec![0.6, 0.3]);
+    ///
+    /// let latent = compiled.predict(&x_test).unwrap();
+    /// let noisy = compiled.predict_noisy(&x_test, &Noise::Constant(0.1)).unwrap();
+    ///
+    /// assert!((&noisy.var - &latent.var).iter().all(|v| (v - 0.1).abs() < 1e-12));
+    ///
+    /// // score held-out measurements against the noisy prediction
+    /// let lpd = noisy.log_predictive_density(&y_test).unwrap().sum();
+    /// let crps = noisy.crps(&y_test).unwrap().mean();
+    /// ```
+    pub fn predict_noisy(&self, x: &DMatrix<f64>, noise: &Noise) -> GPResult<Prediction> {
+        self.predict(x)?.with_noise(noise)
+    }
+
     /// Compute the mean from input data
     ///
-    /// `f = K*' [K + sI]^-1 y`
+    /// `f = m* + K*' [K + S]^-1 (y - m)`
     pub fn mean(&self, x: &DMatrix<f64>) -> GPResult<DVector<f64>> {
         // compute K*'
         let k_x_xp = self.kernel.call(&self.x, x)?;
-        self.mean_precomputed(&k_x_xp)
+        self.mean_precomputed(x, &k_x_xp)
     }
 
     /// Find the mean given a precomputed K*
-    fn mean_precomputed(&self, k_x_xp: &DMatrix<f64>) -> GPResult<DVector<f64>> {
-        let res = par_tr_matmul(k_x_xp, &self.alpha)?;
-        Ok(DVector::from_vec(res))
+    fn mean_precomputed(&self, x: &DMatrix<f64>, k_x_xp: &DMatrix<f64>) -> GPResult<DVector<f64>> {
+        posterior::mean(self.mean.call(x)?, k_x_xp, &self.alpha)
     }
 
     /// Compute just the diagonal variance
@@ -141,24 +718,38 @@ impl<K: Kernel> CompiledGP<K> {
         self.var_precomputed(x, &k_x_xp)
     }
 
+    /// Compute the variance of noisy observations, rather than of the latent function
+    ///
+    /// `noise` is the observation noise at `x`, which need not match the training noise.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use gprs::{gp::{Noise, GP}, kernels::RBF};
+    /// use nalgebra::{DVector, DMatrix};
+    ///
+    /// let x = DMatrix::from_vec(1, 2, vec![0.0, 1.0]);
+    /// let y = DVector::from_vec(vec![0.0, 1.0]);
+    ///
+    /// let compiled = GP::new(RBF::new(vec![1.0], 1.0), 0.1).compile(x.clone(), &y).unwrap();
+    ///
+    /// let latent = compiled.var(&x).unwrap();
+    /// let noisy = compiled.var_noisy(&x, &Noise::Constant(0.1)).unwrap();
+    /// assert!((noisy - latent).iter().all(|v| (v - 0.1).abs() < 1e-12));
+    /// ```
+    pub fn var_noisy(&self, x: &DMatrix<f64>, noise: &Noise) -> GPResult<DVector<f64>> {
+        let mut var = self.var(x)?;
+        noise.add_to(&mut var)?;
+        Ok(var)
+    }
+
     /// Find the variance given a precomputed K*
     fn var_precomputed(&self, x: &DMatrix<f64>, k_x_xp: &DMatrix<f64>) -> GPResult<DVector<f64>> {
-        let mut k_xp_xp = self.kernel.call_diagonal(x)?;
-        let fact = par_solve_lower_triangular_unchecked(self.cholesky.l_dirty(), k_x_xp);
-        let zipped = par_tr_matmul_diag(&fact, &fact)?;
-
-        k_xp_xp
-            .as_mut_slice()
-            .into_par_iter()
-            .zip(zipped)
-            .for_each(|(l, r)| *l -= r);
-
-        Ok(DVector::from_vec(k_xp_xp))
+        posterior::var(self.kernel.call_diagonal(x)?, k_x_xp, &self.cholesky)
     }
 
     /// Compute the full covariance matrix from input data
     ///
-    /// `V = K** - K*' [K + sI]^-1 K*`
+    /// `V = K** - K*' [K + S]^-1 K*`
     pub fn cov(&self, x: &DMatrix<f64>) -> GPResult<DMatrix<f64>> {
         // compute K*
         let k_x_xp = self.kernel.call(&self.x, x)?;
@@ -180,15 +771,255 @@ impl<K: Kernel> CompiledGP<K> {
 
         Ok(k_xp_xp)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use nalgebra::{DMatrix, DVector};
+    /// Draw functions from the posterior at `x`, one sample per column
+    ///
+    /// The posterior covariance is usually close to singular near the training points, so it is factorized
+    /// with jitter from the GP's `JitterPolicy`, or the default policy, scaled by the mean posterior variance.
+    /// The result has one row per column of `x`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use gprs::{gp::GP, kernels::RBF};
+    /// use nalgebra::{DVector, DMatrix};
+    /// use rand::{rngs::StdRng, SeedableRng};
+    ///
+    /// let x = DMatrix::from_vec(1, 2, vec![0.0, 1.0]);
+    /// let y = DVector::from_vec(vec![0.0, 1.0]);
+    ///
+    /// let compiled = GP::new(RBF::new(vec![1.0], 1.0), 0.1).compile(x, &y).unwrap();
+    ///
+    /// let x_test = DMatrix::from_fn(1, 5, |_, j| j as f64 * 0.25);
+    /// let mut rng = StdRng::seed_from_u64(0);
+    ///
+    /// let samples = compiled.sample(&x_test, 3, &mut rng).unwrap();
+    /// assert_eq!(samples.shape(), (5, 3));
+    /// ```
+    pub fn sample<R: Rng + ?Sized>(
+        &self,
+        x: &DMatrix<f64>,
+        n_samples: usize,
+        rng: &mut R,
+    ) -> Result<DMatrix<f64>, GPCompilationError> {
+        Ok(self.sample_with_jitter(x, n_samples, rng)?.0)
+    }
 
-    use crate::{gp::errors::GPCompilationError, kernels::RBF};
+    /// Draw functions from the posterior at `x` like `sample`, and also return the jitter that was added
+    /// to the diagonal of the posterior covariance
+    pub fn sample_with_jitter<R: Rng + ?Sized>(
+        &self,
+        x: &DMatrix<f64>,
+        n_samples: usize,
+        rng: &mut R,
+    ) -> Result<(DMatrix<f64>, f64), GPCompilationError> {
+        let (mean, cov) = self
+            .kernel
+            .call(&self.x, x)
+            .and_then(|k_x_xp| {
+                Ok((
+                    self.mean_precomputed(x, &k_x_xp)?,
+                    self.cov_precomputed(x, &k_x_xp)?,
+                ))
+            })
+            .map_err(GPCompilationError::IncompatibleShapeError)?;
 
-    use super::GP;
+        sample_mvn(&mean, cov, n_samples, self.policy.as_ref(), rng)
+    }
+}
+
+impl<K, M> CompiledGP<K, M>
+where
+    K: KernelGradient,
+    M: MeanFunction,
+{
+    /// Compute the derivative of the posterior variance at each column of `x` w.r.t. that column
+    ///
+    /// `dv/dx* = dk(x*, x*)/dx* - 2 (dK*/dx*)' [K + S]^-1 K*`
+    ///
+    /// The result has the same shape as `x`.
+    pub fn var_gradient(&self, x: &DMatrix<f64>) -> GPResult<DMatrix<f64>> {
+        let k_x_xp = self.kernel.call(&self.x, x)?;
+        let solved = self.cholesky.solve(&k_x_xp);
+
+        let mut grad = self.kernel.diagonal_gradient(x)?;
+        for (d, dk) in self.kernel.gradient(&self.x, x)?.iter().enumerate() {
+            let dots = par_tr_matmul_diag(dk, &solved)?;
+            grad.row_mut(d)
+                .iter_mut()
+                .zip(dots)
+                .for_each(|(g, dot)| *g -= 2.0 * dot);
+        }
+
+        Ok(grad)
+    }
+}
+
+impl<K, M> CompiledGP<K, M>
+where
+    K: KernelGradient,
+    M: MeanGradient,
+{
+    /// Compute the derivative of the posterior mean at each column of `x` w.r.t. that column
+    ///
+    /// `df/dx* = dm*/dx* + (dK*/dx*)' [K + S]^-1 (y - m)`
+    ///
+    /// The result has the same shape as `x`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use gprs::{gp::GP, kernels::RBF};
+    /// use nalgebra::{DVector, DMatrix};
+    ///
+    /// let x = DMatrix::from_fn(1, 20, |_, j| j as f64 * 0.3);
+    /// let y = DVector::from_fn(20, |i, _| (i as f64 * 0.3).sin());
+    ///
+    /// let compiled = GP::new(RBF::new(vec![1.0], 1.0), 1e-4).compile(x, &y).unwrap();
+    ///
+    /// // the slope of sin(x) at pi is -1
+    /// let x_test = DMatrix::from_vec(1, 1, vec![std::f64::consts::PI]);
+    /// let slope = compiled.mean_gradient(&x_test).unwrap();
+    /// assert!((slope[0] + 1.0).abs() < 1e-2);
+    /// ```
+    pub fn mean_gradient(&self, x: &DMatrix<f64>) -> GPResult<DMatrix<f64>> {
+        let mut grad = self.mean.gradient(x)?;
+
+        for (d, dk) in self.kernel.gradient(&self.x, x)?.iter().enumerate() {
+            let dots = par_tr_matmul(dk, &self.alpha)?;
+            grad.row_mut(d)
+                .iter_mut()
+                .zip(dots)
+                .for_each(|(g, dot)| *g += dot);
+        }
+
+        Ok(grad)
+    }
+}
+
+impl<K, M> CompiledGP<K, M>
+where
+    K: Kernel + for<'a> Jacobian<'a>,
+    M: MeanFunction,
+{
+    /// Compute the gradient of the log marginal likelihood w.r.t. each kernel parameter
+    ///
+    /// `dL/dθ = 0.5 tr((a a' - [K + S]^-1) dK/dθ)`
+    ///
+    /// where `a = [K + S]^-1 y`. The gradient is in the same order as the kernel's `get_params`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use gprs::{gp::GP, kernels::RBF};
+    /// use nalgebra::{DVector, DMatrix};
+    ///
+    /// let x = DMatrix::from_vec(1, 3, vec![0.0, 1.0, 2.0]);
+    /// let y = DVector::from_vec(vec![0.0, 1.0, 0.5]);
+    ///
+    /// let compiled = GP::new(RBF::new(vec![1.0], 1.0), 0.1).compile(x, &y).unwrap();
+    ///
+    /// // one entry for the amplitude, and one for the length scale
+    /// let grad = compiled.log_marginal_likelihood_gradient().unwrap();
+    /// assert_eq!(grad.len(), 2);
+    /// ```
+    pub fn log_marginal_likelihood_gradient(&self) -> GPResult<Vec<f64>> {
+        Ok(self.log_marginal_likelihood_gradients()?.0)
+    }
+
+    /// Compute the likelihood gradient w.r.t. the kernel parameters and the noise together.
+    ///
+    /// This shares the inverse covariance matrix between both.
+    pub(crate) fn log_marginal_likelihood_gradients(&self) -> GPResult<(Vec<f64>, f64)> {
+        let w = self.likelihood_weights();
+        let jac = self.kernel.jacobian(&self.x);
+
+        let w_flat = DVector::from_column_slice(w.as_slice());
+        let grad = par_tr_matmul(&jac, &w_flat)?;
+
+        Ok((grad.into_iter().map(|g| 0.5 * g).collect(), 0.5 * w.trace()))
+    }
+
+    /// Compute the gradient of the LOO score w.r.t. each kernel parameter
+    ///
+    /// The gradient is in the same order as the kernel's `get_params`.
+    pub fn loo_gradient(&self) -> GPResult<Vec<f64>> {
+        let (w, _) = self.loo_weights();
+        let jac = self.kernel.jacobian(&self.x);
+
+        par_tr_matmul(&jac, &DVector::from_column_slice(w.as_slice()))
+    }
+}
+
+impl<K, M> CompiledGP<K, M>
+where
+    K: Kernel,
+    M: MeanFunction + for<'a> Jacobian<'a>,
+{
+    /// Compute the gradient of the log marginal likelihood w.r.t. each mean function parameter
+    ///
+    /// `dL/dθ = dm/dθ' [K + S]^-1 (y - m)`
+    ///
+    /// The gradient is in the same order as the mean function's `get_params`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use gprs::{gp::GP, kernels::RBF, means::Constant};
+    /// use nalgebra::{DVector, DMatrix};
+    ///
+    /// let x = DMatrix::from_vec(1, 3, vec![0.0, 1.0, 2.0]);
+    /// let y = DVector::from_vec(vec![5.0, 6.0, 5.5]);
+    ///
+    /// let compiled = GP::new(RBF::new(vec![1.0], 1.0), 0.1)
+    ///     .with_mean(Constant::new(0.0))
+    ///     .compile(x, &y)
+    ///     .unwrap();
+    ///
+    /// // the data lies above the prior mean, so increasing it improves the likelihood
+    /// let grad = compiled.log_marginal_likelihood_mean_gradient().unwrap();
+    /// assert!(grad[0] > 0.0);
+    /// ```
+    pub fn log_marginal_likelihood_mean_gradient(&self) -> GPResult<Vec<f64>> {
+        let jac = self.mean.jacobian(&self.x);
+        par_tr_matmul(&jac, &self.alpha)
+    }
+
+    /// Compute the gradient of the LOO score w.r.t. each mean function parameter
+    pub fn loo_mean_gradient(&self) -> GPResult<Vec<f64>> {
+        let (_, pu) = self.loo_weights();
+        par_tr_matmul(&self.mean.jacobian(&self.x), &pu)
+    }
+}
+
+impl<K, M> CompiledGP<K, M>
+where
+    K: Kernel + for<'a> Jacobian<'a>,
+    M: MeanFunction + for<'a> Jacobian<'a>,
+{
+    /// Compute the LOO score gradient w.r.t. the kernel parameters, the noise and the mean function together.
+    ///
+    /// This shares the inverse covariance matrix between all of them.
+    pub(crate) fn loo_gradients(&self) -> GPResult<(Vec<f64>, f64, Vec<f64>)> {
+        let (w, pu) = self.loo_weights();
+
+        let kernel_jac = self.kernel.jacobian(&self.x);
+        let kernel_grad = par_tr_matmul(&kernel_jac, &DVector::from_column_slice(w.as_slice()))?;
+        let mean_grad = par_tr_matmul(&self.mean.jacobian(&self.x), &pu)?;
+
+        Ok((kernel_grad, w.trace(), mean_grad))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DMatrix, DVector};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{
+        gp::{errors::GPCompilationError, JitterPolicy, Noise},
+        kernels::{Kernel, WhiteNoise, RBF},
+        means::{Closure, Linear},
+        parameterized::{FromParams, Parameterized},
+    };
+
+    use super::GP;
 
     /// Predicting a noiseless GP on one of the input points returns the measured output
     #[test]
@@ -278,4 +1109,691 @@ mod tests {
 
         assert!(res.iter().all(|v| *v > 0.0))
     }
+
+    /// The log marginal likelihood of a single point is the log density of a 1-d normal
+    #[test]
+    fn test_log_marginal_likelihood_single() {
+        let kern = RBF::new(vec![1.0], 2.0);
+        let gp = GP::new(kern, 0.5);
+
+        let x = DMatrix::from_vec(1, 1, vec![0.3]);
+        let y = DVector::from_vec(vec![1.5]);
+
+        let compiled = gp.compile(x, &y).unwrap();
+
+        // variance is sigma^2 + noise
+        let var: f64 = 4.5;
+        let expected =
+            -0.5 * 1.5 * 1.5 / var - 0.5 * var.ln() - 0.5 * (2.0 * std::f64::consts::PI).ln();
+
+        assert!((compiled.log_marginal_likelihood() - expected).abs() < 1e-12);
+    }
+
+    /// The log marginal likelihood matches a direct evaluation of the multivariate normal density
+    #[test]
+    fn test_log_marginal_likelihood_direct() {
+        let kern = RBF::new(vec![0.7], 1.3);
+        let gp = GP::new(kern, 0.2);
+
+        let x = DMatrix::from_vec(1, 3, vec![0.0, 0.5, 2.0]);
+        let y = DVector::from_vec(vec![0.4, -0.2, 1.1]);
+
+        let compiled = gp.compile(x.clone(), &y).unwrap();
+
+        let kern = RBF::new(vec![0.7], 1.3);
+        let k = kern.call(&x, &x).unwrap() + DMatrix::identity(3, 3) * 0.2;
+        let k_inv = k.clone().try_inverse().unwrap();
+        let expected = -0.5 * (y.transpose() * k_inv * &y)[0]
+            - 0.5 * k.determinant().ln()
+            - 1.5 * (2.0 * std::f64::consts::PI).ln();
+
+        assert!((compiled.log_marginal_likelihood() - expected).abs() < 1e-10);
+    }
+
+    /// The log marginal likelihood gradient matches a finite-difference approximation
+    #[test]
+    fn test_log_marginal_likelihood_gradient() {
+        let x = DMatrix::from_vec(2, 4, vec![0.0, 0.1, 0.5, 1.0, 1.3, 0.2, 2.0, -0.5]);
+        let y = DVector::from_vec(vec![0.4, -0.2, 1.1, 0.3]);
+
+        let lml = |params: &[f64]| {
+            GP::new(RBF::from_params(params), 0.3)
+                .compile(x.clone(), &y)
+                .unwrap()
+                .log_marginal_likelihood()
+        };
+
+        let params = RBF::new(vec![0.7, 1.4], 1.3).get_params();
+        let grad = GP::new(RBF::from_params(&params), 0.3)
+            .compile(x.clone(), &y)
+            .unwrap()
+            .log_marginal_likelihood_gradient()
+            .unwrap();
+
+        let eps = 1e-6;
+        for (p, g) in grad.iter().enumerate() {
+            let mut up = params.clone();
+            up[p] += eps;
+            let mut down = params.clone();
+            down[p] -= eps;
+
+            let fd = (lml(&up) - lml(&down)) / (2.0 * eps);
+            assert!((g - fd).abs() < 1e-5, "{} != {}", g, fd);
+        }
+    }
+
+    /// The noise gradient matches a finite-difference approximation
+    #[test]
+    fn test_log_marginal_likelihood_noise_gradient() {
+        let x = DMatrix::from_vec(1, 3, vec![0.0, 0.5, 2.0]);
+        let y = DVector::from_vec(vec![0.4, -0.2, 1.1]);
+
+        let lml = |noise: f64| {
+            GP::new(RBF::new(vec![0.7], 1.3), noise)
+                .compile(x.clone(), &y)
+                .unwrap()
+                .log_marginal_likelihood()
+        };
+
+        let grad = GP::new(RBF::new(vec![0.7], 1.3), 0.2)
+            .compile(x.clone(), &y)
+            .unwrap()
+            .log_marginal_likelihood_noise_gradient();
+
+        let eps = 1e-6;
+        let fd = (lml(0.2 + eps) - lml(0.2 - eps)) / (2.0 * eps);
+        assert!((grad - fd).abs() < 1e-6);
+    }
+
+    /// A prior mean is subtracted from the outputs before fitting, and added back to predictions
+    #[test]
+    fn test_prior_mean() {
+        let x = DMatrix::from_vec(1, 3, vec![0.0, 0.5, 2.0]);
+        let y = DVector::from_vec(vec![10.4, 10.3, 12.1]);
+        let trend = Linear::new(vec![1.0], 10.0);
+
+        let with_mean = GP::new(RBF::new(vec![0.7], 1.3), 0.2)
+            .with_mean(trend.clone())
+            .compile(x.clone(), &y)
+            .unwrap();
+
+        let residual = DVector::from_vec(vec![0.4, -0.2, 0.1]);
+        let zero_mean = GP::new(RBF::new(vec![0.7], 1.3), 0.2)
+            .compile(x, &residual)
+            .unwrap();
+
+        assert!(
+            (with_mean.log_marginal_likelihood() - zero_mean.log_marginal_likelihood()).abs()
+                < 1e-12
+        );
+
+        let xp = DMatrix::from_vec(1, 3, vec![0.25, 1.0, 50.0]);
+        let expected = zero_mean.mean(&xp).unwrap() + DVector::from_vec(vec![10.25, 11.0, 60.0]);
+        let (mean, var) = with_mean.call(&xp).unwrap();
+
+        assert!((mean - expected).amax() < 1e-12);
+        assert_eq!(var, zero_mean.var(&xp).unwrap());
+    }
+
+    /// A closure mean is evaluated at each point
+    #[test]
+    fn test_closure_mean() {
+        let x = DMatrix::from_vec(1, 2, vec![0.0, 1.0]);
+        let y = DVector::from_vec(vec![0.0, 1.0]);
+
+        let compiled = GP::new(RBF::new(vec![0.1], 1.0), 0.0)
+            .with_mean(Closure::new(|point: &[f64]| point[0].sin()))
+            .compile(x, &y)
+            .unwrap();
+
+        let far = DMatrix::from_vec(1, 1, vec![10.0]);
+        assert!((compiled.mean(&far).unwrap()[0] - 10.0_f64.sin()).abs() < 1e-12);
+    }
+
+    /// A mean function with the wrong number of dimensions fails to compile
+    #[test]
+    fn test_prior_mean_mismatched() {
+        let x = DMatrix::from_vec(1, 2, vec![0.0, 1.0]);
+        let y = DVector::from_vec(vec![0.0, 1.0]);
+
+        let result = GP::new(RBF::new(vec![1.0], 1.0), 0.1)
+            .with_mean(Linear::new(vec![1.0, 1.0], 0.0))
+            .compile(x, &y);
+
+        assert!(matches!(
+            result,
+            Err(GPCompilationError::IncompatibleShapeError(_))
+        ));
+    }
+
+    /// The mean function gradient matches a finite-difference approximation
+    #[test]
+    fn test_log_marginal_likelihood_mean_gradient() {
+        let x = DMatrix::from_vec(2, 4, vec![0.0, 0.1, 0.5, 1.0, 1.3, 0.2, 2.0, -0.5]);
+        let y = DVector::from_vec(vec![0.4, -0.2, 1.1, 0.3]);
+
+        let lml = |params: &[f64]| {
+            GP::new(RBF::new(vec![0.7, 1.4], 1.3), 0.3)
+                .with_mean(Linear::from_params(params))
+                .compile(x.clone(), &y)
+                .unwrap()
+                .log_marginal_likelihood()
+        };
+
+        let params = vec![0.2, 0.5, -0.4];
+        let grad = GP::new(RBF::new(vec![0.7, 1.4], 1.3), 0.3)
+            .with_mean(Linear::from_params(&params))
+            .compile(x.clone(), &y)
+            .unwrap()
+            .log_marginal_likelihood_mean_gradient()
+            .unwrap();
+
+        assert_eq!(grad.len(), 3);
+
+        let eps = 1e-6;
+        for (p, g) in grad.iter().enumerate() {
+            let mut up = params.clone();
+            up[p] += eps;
+            let mut down = params.clone();
+            down[p] -= eps;
+
+            let fd = (lml(&up) - lml(&down)) / (2.0 * eps);
+            assert!((g - fd).abs() < 1e-5, "{} != {}", g, fd);
+        }
+    }
+
+    /// Equal per-point noise is the same as a constant noise
+    #[test]
+    fn test_heteroscedastic_constant() {
+        let x = DMatrix::from_vec(1, 3, vec![0.0, 0.5, 2.0]);
+        let y = DVector::from_vec(vec![0.4, -0.2, 1.1]);
+        let xp = DMatrix::from_vec(1, 2, vec![0.25, 1.0]);
+
+        let constant = GP::new(RBF::new(vec![0.7], 1.3), 0.2)
+            .compile(x.clone(), &y)
+            .unwrap();
+        let per_point =
+            GP::new_heteroscedastic(RBF::new(vec![0.7], 1.3), DVector::from_element(3, 0.2))
+                .compile(x, &y)
+                .unwrap();
+
+        assert_eq!(constant.call(&xp).unwrap(), per_point.call(&xp).unwrap());
+        assert_eq!(
+            constant.log_marginal_likelihood(),
+            per_point.log_marginal_likelihood()
+        );
+    }
+
+    /// A noisier observation has less influence on the mean, and leaves more variance
+    #[test]
+    fn test_heteroscedastic() {
+        let x = DMatrix::from_vec(1, 2, vec![0.0, 1.0]);
+        let y = DVector::from_vec(vec![1.0, 1.0]);
+        let noise = DVector::from_vec(vec![0.01, 1.0]);
+
+        let compiled = GP::new_heteroscedastic(RBF::new(vec![10.0], 1.0), noise)
+            .compile(x.clone(), &y)
+            .unwrap();
+
+        let (mean, var) = compiled.call(&x).unwrap();
+        assert!((mean[0] - 1.0).abs() < (mean[1] - 1.0).abs());
+        assert!(var[0] < var[1]);
+
+        let noisy = compiled
+            .var_noisy(&x, &Noise::PerPoint(DVector::from_vec(vec![0.01, 1.0])))
+            .unwrap();
+        assert!((noisy[0] - var[0] - 0.01).abs() < 1e-12);
+        assert!((noisy[1] - var[1] - 1.0).abs() < 1e-12);
+    }
+
+    /// Per-point noise must have one value per training point
+    #[test]
+    fn test_heteroscedastic_mismatched() {
+        let x = DMatrix::from_vec(1, 2, vec![0.0, 1.0]);
+        let y = DVector::from_vec(vec![0.0, 1.0]);
+
+        let result =
+            GP::new_heteroscedastic(RBF::new(vec![1.0], 1.0), DVector::from_element(3, 0.1))
+                .compile(x.clone(), &y);
+        assert!(matches!(
+            result,
+            Err(GPCompilationError::IncompatibleShapeError(_))
+        ));
+
+        let compiled = GP::new(RBF::new(vec![1.0], 1.0), 0.1)
+            .compile(x.clone(), &y)
+            .unwrap();
+        assert!(compiled
+            .var_noisy(&x, &Noise::PerPoint(DVector::from_element(3, 0.1)))
+            .is_err());
+    }
+
+    /// Near-duplicate inputs compile with a jitter policy, and the applied jitter is reported
+    #[test]
+    fn test_jitter() {
+        let x = DMatrix::from_vec(1, 3, vec![0.0, 1.0, 1.0 + 1e-9]);
+        let y = DVector::from_vec(vec![0.0, 1.0, 1.0]);
+
+        let result = GP::new(RBF::new(vec![1.0], 1.0), 0.0).compile(x.clone(), &y);
+        assert_eq!(
+            result.unwrap_err(),
+            GPCompilationError::NonPositiveDefiniteError
+        );
+
+        let compiled = GP::new(RBF::new(vec![1.0], 1.0), 0.0)
+            .with_jitter(JitterPolicy::default())
+            .compile(x.clone(), &y)
+            .unwrap();
+
+        assert!(compiled.jitter() > 0.0 && compiled.jitter() <= 1e-4);
+        assert!((compiled.mean(&x).unwrap() - &y).amax() < 1e-3);
+
+        let well_posed = GP::new(RBF::new(vec![1.0], 1.0), 0.1)
+            .with_jitter(JitterPolicy::default())
+            .compile(x, &y)
+            .unwrap();
+
+        assert_eq!(well_posed.jitter(), 0.0);
+    }
+
+    /// Adding observations gives the same GP as compiling with all of them
+    #[test]
+    fn test_add_observations() {
+        let x = DMatrix::from_vec(
+            2,
+            5,
+            vec![0.0, 0.1, 0.5, 1.0, 1.3, 0.2, 2.0, -0.5, 0.7, 0.7],
+        );
+        let y = DVector::from_vec(vec![0.4, -0.2, 1.1, 0.3, 0.8]);
+        let xp = DMatrix::from_vec(2, 2, vec![0.2, 0.2, 1.5, 0.0]);
+        let gp = || {
+            GP::new(RBF::new(vec![0.7, 1.4], 1.3), 0.1).with_mean(Linear::new(vec![0.5, 0.1], 0.2))
+        };
+
+        let full = gp().compile(x.clone(), &y).unwrap();
+
+        let mut incremental = gp()
+            .compile(x.columns(0, 2).into_owned(), &y.rows(0, 2).into_owned())
+            .unwrap();
+        incremental
+            .add_observations(&x.columns(2, 3).into_owned(), &y.rows(2, 3).into_owned())
+            .unwrap();
+
+        let (mean, var) = full.call(&xp).unwrap();
+        let (inc_mean, inc_var) = incremental.call(&xp).unwrap();
+
+        assert!((mean - inc_mean).amax() < 1e-12);
+        assert!((var - inc_var).amax() < 1e-12);
+        assert!(
+            (full.log_marginal_likelihood() - incremental.log_marginal_likelihood()).abs() < 1e-12
+        );
+    }
+
+    /// Adding observations at duplicated points gives each its own white noise, as compiling does
+    #[test]
+    fn test_add_observations_white_noise() {
+        let x = DMatrix::from_vec(1, 6, vec![0.0, 0.5, 1.0, 0.5, 1.0, 1.0]);
+        let y = DVector::from_vec(vec![0.4, -0.2, 1.1, -0.1, 0.9, 1.2]);
+        let xp = DMatrix::from_vec(1, 3, vec![0.25, 0.5, 1.75]);
+        let gp = || GP::new(RBF::new(vec![0.7], 1.3) + WhiteNoise::new(0.2), 0.0);
+
+        let full = gp().compile(x.clone(), &y).unwrap();
+
+        let mut incremental = gp()
+            .compile(x.columns(0, 3).into_owned(), &y.rows(0, 3).into_owned())
+            .unwrap();
+        incremental
+            .add_observations(&x.columns(3, 3).into_owned(), &y.rows(3, 3).into_owned())
+            .unwrap();
+
+        let (mean, var) = full.call(&xp).unwrap();
+        let (inc_mean, inc_var) = incremental.call(&xp).unwrap();
+
+        assert!((mean - inc_mean).amax() < 1e-12);
+        assert!((var - inc_var).amax() < 1e-12);
+        assert!(
+            (full.log_marginal_likelihood() - incremental.log_marginal_likelihood()).abs() < 1e-12
+        );
+    }
+
+    /// Adding noisy observations converts the noise to per-point
+    #[test]
+    fn test_add_noisy_observations() {
+        let x = DMatrix::from_vec(1, 3, vec![0.0, 0.5, 2.0]);
+        let y = DVector::from_vec(vec![0.4, -0.2, 1.1]);
+        let noise = DVector::from_vec(vec![0.1, 0.1, 0.5]);
+        let xp = DMatrix::from_vec(1, 2, vec![0.25, 1.0]);
+
+        let full = GP::new_heteroscedastic(RBF::new(vec![0.7], 1.3), noise.clone())
+            .compile(x.clone(), &y)
+            .unwrap();
+
+        let mut incremental = GP::new(RBF::new(vec![0.7], 1.3), 0.1)
+            .compile(x.columns(0, 2).into_owned(), &y.rows(0, 2).into_owned())
+            .unwrap();
+
+        // the noise must be given once the GP has per-point noise
+        let x_new = x.columns(2, 1).into_owned();
+        let y_new = y.rows(2, 1).into_owned();
+        incremental
+            .add_noisy_observations(&x_new, &y_new, &noise.rows(2, 1).into_owned())
+            .unwrap();
+
+        assert_eq!(incremental.noise(), &Noise::PerPoint(noise));
+        assert!((full.mean(&xp).unwrap() - incremental.mean(&xp).unwrap()).amax() < 1e-12);
+        assert_eq!(
+            incremental.add_observations(&x_new, &y_new).unwrap_err(),
+            GPCompilationError::MissingNoiseError
+        );
+    }
+
+    /// A failed update leaves the GP unchanged
+    #[test]
+    fn test_add_observations_error() {
+        let x = DMatrix::from_vec(1, 2, vec![0.0, 1.0]);
+        let y = DVector::from_vec(vec![0.0, 1.0]);
+
+        let mut compiled = GP::new(RBF::new(vec![1.0], 1.0), 0.0)
+            .compile(x.clone(), &y)
+            .unwrap();
+
+        // a duplicate point is singular without noise
+        let duplicate = DMatrix::from_vec(1, 1, vec![1.0]);
+        assert_eq!(
+            compiled
+                .add_observations(&duplicate, &DVector::from_vec(vec![1.0]))
+                .unwrap_err(),
+            GPCompilationError::NonPositiveDefiniteError
+        );
+
+        let mismatched = DMatrix::from_vec(2, 1, vec![1.0, 1.0]);
+        assert!(compiled
+            .add_observations(&mismatched, &DVector::from_vec(vec![1.0]))
+            .is_err());
+
+        assert_eq!(compiled.mean(&x).unwrap().len(), 2);
+        assert!((compiled.mean(&x).unwrap() - y).amax() < 1e-12);
+    }
+
+    /// Removing observations gives the same GP as compiling without them
+    #[test]
+    fn test_remove_observations() {
+        let x = DMatrix::from_vec(1, 5, vec![0.0, 0.5, 1.0, 1.5, 2.0]);
+        let y = DVector::from_vec(vec![0.4, -0.2, 1.1, 0.3, 0.8]);
+        let noise = DVector::from_vec(vec![0.1, 0.2, 0.3, 0.4, 0.5]);
+        let xp = DMatrix::from_vec(1, 2, vec![0.25, 1.75]);
+
+        let mut removed = GP::new_heteroscedastic(RBF::new(vec![0.7], 1.3), noise)
+            .with_mean(Linear::new(vec![0.5], 0.2))
+            .compile(x, &y)
+            .unwrap();
+        removed.remove_observations(&[3, 0, 3]);
+
+        let kept = GP::new_heteroscedastic(
+            RBF::new(vec![0.7], 1.3),
+            DVector::from_vec(vec![0.2, 0.3, 0.5]),
+        )
+        .with_mean(Linear::new(vec![0.5], 0.2))
+        .compile(
+            DMatrix::from_vec(1, 3, vec![0.5, 1.0, 2.0]),
+            &DVector::from_vec(vec![-0.2, 1.1, 0.8]),
+        )
+        .unwrap();
+
+        let (mean, var) = kept.call(&xp).unwrap();
+        let (rem_mean, rem_var) = removed.call(&xp).unwrap();
+
+        assert!((mean - rem_mean).amax() < 1e-12);
+        assert!((var - rem_var).amax() < 1e-12);
+        assert!((kept.log_marginal_likelihood() - removed.log_marginal_likelihood()).abs() < 1e-12);
+        assert_eq!(kept.noise(), removed.noise());
+    }
+
+    /// Removing an observation that does not exist panics
+    #[test]
+    #[should_panic]
+    fn test_remove_observations_out_of_bounds() {
+        let x = DMatrix::from_vec(1, 2, vec![0.0, 1.0]);
+        let y = DVector::from_vec(vec![0.0, 1.0]);
+
+        let mut compiled = GP::new(RBF::new(vec![1.0], 1.0), 0.1)
+            .compile(x, &y)
+            .unwrap();
+        compiled.remove_observations(&[2]);
+    }
+
+    /// Replacing an output gives the same GP as compiling with the new output
+    #[test]
+    fn test_set_observation() {
+        let x = DMatrix::from_vec(1, 3, vec![0.0, 0.5, 2.0]);
+        let y = DVector::from_vec(vec![0.4, -0.2, 1.1]);
+        let xp = DMatrix::from_vec(1, 2, vec![0.25, 1.0]);
+        let gp = || GP::new(RBF::new(vec![0.7], 1.3), 0.2).with_mean(Linear::new(vec![0.5], 0.2));
+
+        let mut replaced = gp().compile(x.clone(), &y).unwrap();
+        replaced.set_observation(1, 0.6).unwrap();
+
+        let expected = gp()
+            .compile(x, &DVector::from_vec(vec![0.4, 0.6, 1.1]))
+            .unwrap();
+
+        assert!((expected.mean(&xp).unwrap() - replaced.mean(&xp).unwrap()).amax() < 1e-12);
+        assert!(
+            (expected.log_marginal_likelihood() - replaced.log_marginal_likelihood()).abs() < 1e-12
+        );
+    }
+
+    /// Closed-form LOO predictions match compiling without each point
+    #[test]
+    fn test_loo() {
+        let x = DMatrix::from_vec(1, 4, vec![0.0, 0.5, 1.0, 2.0]);
+        let y = DVector::from_vec(vec![0.4, -0.2, 1.1, 0.3]);
+        let noise = DVector::from_vec(vec![0.1, 0.2, 0.3, 0.4]);
+        let gp = |noise: DVector<f64>| {
+            GP::new_heteroscedastic(RBF::new(vec![0.7], 1.3), noise)
+                .with_mean(Linear::new(vec![0.5], 0.2))
+        };
+
+        let loo = gp(noise.clone())
+            .compile(x.clone(), &y)
+            .unwrap()
+            .loo()
+            .unwrap();
+
+        for i in 0..4 {
+            let mut without = gp(noise.clone()).compile(x.clone(), &y).unwrap();
+            without.remove_observations(&[i]);
+
+            let xi = x.columns(i, 1).into_owned();
+            let mean = without.mean(&xi).unwrap()[0];
+            let var = without.var_noisy(&xi, &Noise::Constant(noise[i])).unwrap()[0];
+            let log_density =
+                -0.5 * (y[i] - mean).powi(2) / var - 0.5 * (2.0 * std::f64::consts::PI * var).ln();
+
+            assert!((loo.mean[i] - mean).abs() < 1e-10);
+            assert!((loo.var[i] - var).abs() < 1e-10);
+            assert!((loo.log_density[i] - log_density).abs() < 1e-10);
+        }
+
+        assert!((loo.score() - loo.log_density.sum()).abs() < 1e-12);
+    }
+
+    /// The LOO score gradients match finite-difference approximations
+    #[test]
+    fn test_loo_gradients() {
+        let x = DMatrix::from_vec(2, 4, vec![0.0, 0.1, 0.5, 1.0, 1.3, 0.2, 2.0, -0.5]);
+        let y = DVector::from_vec(vec![0.4, -0.2, 1.1, 0.3]);
+
+        let score = |kernel: &[f64], noise: f64, mean: &[f64]| {
+            GP::new(RBF::from_params(kernel), noise)
+                .with_mean(Linear::from_params(mean))
+                .compile(x.clone(), &y)
+                .unwrap()
+                .loo()
+                .unwrap()
+                .score()
+        };
+
+        let kernel = RBF::new(vec![0.7, 1.4], 1.3).get_params();
+        let noise = 0.3;
+        let mean = vec![0.2, 0.5, -0.4];
+
+        let compiled = GP::new(RBF::from_params(&kernel), noise)
+            .with_mean(Linear::from_params(&mean))
+            .compile(x.clone(), &y)
+            .unwrap();
+        let (kernel_grad, noise_grad, mean_grad) = compiled.loo_gradients().unwrap();
+
+        assert_eq!(kernel_grad, compiled.loo_gradient().unwrap());
+        assert_eq!(mean_grad, compiled.loo_mean_gradient().unwrap());
+
+        let eps = 1e-6;
+        for (p, g) in kernel_grad.iter().enumerate() {
+            let mut up = kernel.clone();
+            up[p] += eps;
+            let mut down = kernel.clone();
+            down[p] -= eps;
+
+            let fd = (score(&up, noise, &mean) - score(&down, noise, &mean)) / (2.0 * eps);
+            assert!((g - fd).abs() < 1e-5, "{} != {}", g, fd);
+        }
+
+        let fd =
+            (score(&kernel, noise + eps, &mean) - score(&kernel, noise - eps, &mean)) / (2.0 * eps);
+        assert!((noise_grad - fd).abs() < 1e-5, "{} != {}", noise_grad, fd);
+
+        for (p, g) in mean_grad.iter().enumerate() {
+            let mut up = mean.clone();
+            up[p] += eps;
+            let mut down = mean.clone();
+            down[p] -= eps;
+
+            let fd = (score(&kernel, noise, &up) - score(&kernel, noise, &down)) / (2.0 * eps);
+            assert!((g - fd).abs() < 1e-5, "{} != {}", g, fd);
+        }
+    }
+
+    /// Posterior samples interpolate noiseless data and are reproducible from a seed
+    #[test]
+    fn test_sample() {
+        let x = DMatrix::from_vec(1, 3, vec![0.0, 1.0, 2.0]);
+        let y = DVector::from_vec(vec![0.5, -0.3, 0.8]);
+
+        let compiled = GP::new(RBF::new(vec![1.0], 1.0), 0.0)
+            .with_jitter(JitterPolicy::default())
+            .compile(x.clone(), &y)
+            .unwrap();
+
+        let draw = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            compiled.sample(&x, 4, &mut rng).unwrap()
+        };
+
+        let samples = draw(3);
+        assert_eq!(samples.shape(), (3, 4));
+        assert_eq!(samples, draw(3));
+        assert_ne!(samples, draw(4));
+
+        for sample in samples.column_iter() {
+            assert!((sample - &y).amax() < 1e-3);
+        }
+    }
+
+    /// Prior samples are centered on the mean function with the kernel's variance
+    #[test]
+    fn test_sample_prior() {
+        let x = DMatrix::from_vec(1, 3, vec![0.0, 0.5, 3.0]);
+        let gp = GP::new(RBF::new(vec![1.0], 2.0), 0.1).with_mean(Linear::new(vec![1.0], 0.5));
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let (samples, jitter) = gp.sample_prior_with_jitter(&x, 20000, &mut rng).unwrap();
+        assert_eq!(samples.shape(), (3, 20000));
+        assert_eq!(jitter, 0.0);
+
+        let expected = DVector::from_vec(vec![0.5, 1.0, 3.5]);
+        let mean = samples.column_mean();
+        let var = samples.column_variance();
+
+        assert!((mean - expected).amax() < 0.05);
+        assert!(var.iter().all(|v| (v - 4.0).abs() < 0.2));
+    }
+
+    /// Sampling scales the jitter with the kernel amplitude, with or without a jitter policy
+    #[test]
+    fn test_sample_large_amplitude() {
+        let x = DMatrix::from_fn(1, 5, |_, j| j as f64);
+        let y = DVector::from_fn(5, |i, _| 1e6 * (i as f64).sin());
+        let x_test = DMatrix::from_fn(1, 100, |_, j| j as f64 * 0.05);
+        let kernel = RBF::new(vec![5.0], 1e6);
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let (_, jitter) = GP::new(kernel.clone(), 0.1)
+            .sample_prior_with_jitter(&x_test, 2, &mut rng)
+            .unwrap();
+        assert!(jitter > 1e-4);
+
+        let samples = GP::new(kernel.clone(), 0.1)
+            .with_jitter(JitterPolicy::default())
+            .sample_prior(&x_test, 2, &mut rng)
+            .unwrap();
+        assert_eq!(samples.shape(), (100, 2));
+
+        let compiled = GP::new(kernel.clone(), 0.1).compile(x.clone(), &y).unwrap();
+        let (_, jitter) = compiled.sample_with_jitter(&x_test, 2, &mut rng).unwrap();
+        assert!(jitter > 1e-4);
+
+        let compiled = GP::new(kernel, 0.1)
+            .with_jitter(JitterPolicy::default())
+            .compile(x, &y)
+            .unwrap();
+        let samples = compiled.sample(&x_test, 2, &mut rng).unwrap();
+        assert_eq!(samples.shape(), (100, 2));
+    }
+
+    /// The posterior mean and variance gradients match finite-difference approximations
+    #[test]
+    fn test_prediction_gradients() {
+        let x = DMatrix::from_vec(2, 4, vec![0.0, 0.1, 0.5, 1.0, 1.3, 0.2, 2.0, -0.5]);
+        let y = DVector::from_vec(vec![0.4, -0.2, 1.1, 0.3]);
+
+        let compiled = GP::new(RBF::new(vec![0.7, 1.4], 1.3), 0.1)
+            .with_mean(Linear::new(vec![0.5, -0.4], 0.2))
+            .compile(x, &y)
+            .unwrap();
+
+        let x_test = DMatrix::from_vec(2, 3, vec![0.2, 0.3, 1.1, -0.2, 3.0, 1.0]);
+        let mean_grad = compiled.mean_gradient(&x_test).unwrap();
+        let var_grad = compiled.var_gradient(&x_test).unwrap();
+        assert_eq!(mean_grad.shape(), (2, 3));
+        assert_eq!(var_grad.shape(), (2, 3));
+
+        let eps = 1e-6;
+        for j in 0..3 {
+            for d in 0..2 {
+                let mut up = x_test.columns(j, 1).into_owned();
+                up[d] += eps;
+                let mut down = x_test.columns(j, 1).into_owned();
+                down[d] -= eps;
+
+                let (mean_up, var_up) = compiled.call(&up).unwrap();
+                let (mean_down, var_down) = compiled.call(&down).unwrap();
+
+                let fd = (mean_up[0] - mean_down[0]) / (2.0 * eps);
+                assert!(
+                    (mean_grad[(d, j)] - fd).abs() < 1e-6,
+                    "{} != {}",
+                    mean_grad[(d, j)],
+                    fd
+                );
+
+                let fd = (var_up[0] - var_down[0]) / (2.0 * eps);
+                assert!(
+                    (var_grad[(d, j)] - fd).abs() < 1e-6,
+                    "{} != {}",
+                    var_grad[(d, j)],
+                    fd
+                );
+            }
+        }
+    }
 }
@@ -0,0 +1,453 @@
+use nalgebra::{Cholesky, DMatrix, DVector, Dynamic};
+
+use crate::{
+    kernels::{KernelHessian, TriangleSide},
+    linalg::errors::IncompatibleShapeError,
+    means::{MeanGradient, Zero},
+};
+
+use super::{
+    base::{GPResult, GP},
+    errors::GPCompilationError,
+    jitter::cholesky_with_jitter,
+    noise::Noise,
+    posterior,
+    prediction::Prediction,
+};
+
+/// Observed partial derivatives of the modelled function
+///
+/// Column `i` of `x` is a point where the derivative along dimension `dims[i]` was observed to be `values[i]`.
+///
+/// # Examples
+/// ```rust
+/// use gprs::gp::DerivativeObservations;
+/// use nalgebra::DMatrix;
+///
+/// // the full gradient at two 2-d points
+/// let x = DMatrix::from_vec(2, 2, vec![0.0, 0.0, 1.0, 2.0]);
+/// let gradients = DMatrix::from_vec(2, 2, vec![1.0, -1.0, 0.5, 0.2]);
+///
+/// let observations = DerivativeObservations::from_gradients(&x, &gradients).unwrap();
+/// assert_eq!(observations.dims, vec![0, 1, 0, 1]);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct DerivativeObservations {
+    /// The points where each derivative was observed, one per column
+    pub x: DMatrix<f64>,
+    /// The dimension each derivative is taken along
+    pub dims: Vec<usize>,
+    /// The observed derivatives
+    pub values: DVector<f64>,
+    /// The noise variance of the observed derivatives
+    pub noise: Noise,
+}
+
+impl DerivativeObservations {
+    /// Create noiseless derivative observations
+    pub fn new(x: DMatrix<f64>, dims: Vec<usize>, values: DVector<f64>) -> Self {
+        DerivativeObservations {
+            x,
+            dims,
+            values,
+            noise: Noise::Constant(0.0),
+        }
+    }
+
+    /// Create noiseless observations of every partial derivative at each column of `x`
+    ///
+    /// `gradients` has the same shape as `x`, with the gradient at each point in its column.
+    /// Returns an error if the shapes differ.
+    pub fn from_gradients(x: &DMatrix<f64>, gradients: &DMatrix<f64>) -> GPResult<Self> {
+        if gradients.shape() != x.shape() {
+            return Err(IncompatibleShapeError {
+                shapes: vec![x.shape(), gradients.shape()],
+            });
+        }
+
+        let (dims, n) = x.shape();
+        let columns: Vec<usize> = (0..n).flat_map(|j| std::iter::repeat_n(j, dims)).collect();
+
+        Ok(DerivativeObservations::new(
+            x.select_columns(&columns),
+            (0..n).flat_map(|_| 0..dims).collect(),
+            DVector::from_column_slice(gradients.as_slice()),
+        ))
+    }
+
+    /// Replace the observation noise
+    pub fn with_noise<N: Into<Noise>>(self, noise: N) -> Self {
+        DerivativeObservations {
+            noise: noise.into(),
+            ..self
+        }
+    }
+
+    /// Check that there is one dimension and value per point
+    fn check_len(&self) -> Result<(), IncompatibleShapeError> {
+        let n = self.x.ncols();
+
+        if self.dims.len() != n || self.values.len() != n {
+            return Err(IncompatibleShapeError {
+                shapes: vec![self.x.shape(), (self.dims.len(), 1), self.values.shape()],
+            });
+        }
+
+        self.noise.check_len(n)
+    }
+}
+
+/// Compute the covariance between each derivative observation and the function at each column of `x`
+///
+/// `cov(df(z_i)/dz_ia, f(x_j)) = dk(x_j, z_i)/dz_ia`, since the kernel is symmetric.
+/// The result has one row per derivative observation.
+fn derivative_cross_cov<K: KernelHessian>(
+    kernel: &K,
+    x: &DMatrix<f64>,
+    derivatives: &DerivativeObservations,
+) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+    let grad = kernel.gradient(x, &derivatives.x)?;
+
+    if let Some(d) = derivatives.dims.iter().find(|d| **d >= grad.len()) {
+        return Err(IncompatibleShapeError {
+            shapes: vec![derivatives.x.shape(), (*d + 1, 1)],
+        });
+    }
+
+    Ok(DMatrix::from_fn(
+        derivatives.x.ncols(),
+        x.ncols(),
+        |i, j| grad[derivatives.dims[i]][(j, i)],
+    ))
+}
+
+impl<K: KernelHessian, M: MeanGradient> GP<K, M> {
+    /// Compile this GP on function values and derivative observations together
+    ///
+    /// The covariance of the joint observations is
+    ///
+    /// `[K + S, dK/dz; dK/dz', d2K/dz dz' + S_d]`
+    ///
+    /// where `z` are the points of the derivative observations. The noise of the GP applies to `y`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use gprs::{gp::{DerivativeObservations, GP}, kernels::RBF};
+    /// use nalgebra::{DVector, DMatrix};
+    ///
+    /// // values of sin(x), and its derivatives at other points
+    /// let x = DMatrix::from_vec(1, 3, vec![0.0, 2.0, 4.0]);
+    /// let y = x.row(0).transpose().map(f64::sin);
+    ///
+    /// let z = DMatrix::from_vec(1, 2, vec![1.0, 3.0]);
+    /// let derivatives = DerivativeObservations::from_gradients(&z, &z.map(f64::cos)).unwrap();
+    ///
+    /// let gp = || GP::new(RBF::new(vec![1.0], 1.0), 1e-6);
+    /// let values_only = gp().compile(x.clone(), &y).unwrap();
+    /// let compiled = gp().compile_with_derivatives(x, &y, derivatives).unwrap();
+    ///
+    /// // the derivatives improve the prediction between the values
+    /// let x_test = DMatrix::from_vec(1, 1, vec![1.0]);
+    /// let error = |mean: f64| (mean - 1.0f64.sin()).abs();
+    /// let with_derivatives = compiled.mean(&x_test).unwrap()[0];
+    /// assert!(error(with_derivatives) < error(values_only.mean(&x_test).unwrap()[0]));
+    /// ```
+    pub fn compile_with_derivatives(
+        self,
+        x: DMatrix<f64>,
+        y: &DVector<f64>,
+        derivatives: DerivativeObservations,
+    ) -> Result<CompiledDerivativeGP<K, M>, GPCompilationError> {
+        if x.ncols() != y.len() {
+            return Err(GPCompilationError::IncompatibleShapeError(
+                IncompatibleShapeError {
+                    shapes: vec![x.shape(), y.shape()],
+                },
+            ));
+        }
+
+        let (kernel, noise, mean, jitter) = self.into_parts();
+        let n = x.ncols();
+        let m = derivatives.x.ncols();
+
+        let assemble = || -> Result<(DMatrix<f64>, DVector<f64>), IncompatibleShapeError> {
+            derivatives.check_len()?;
+
+            let mut kxx = kernel.call_triangular(&x, TriangleSide::LOWER)?;
+            noise.add_to_diagonal(&mut kxx)?;
+
+            let cross = derivative_cross_cov(&kernel, &x, &derivatives)?;
+
+            let mut kzz = kernel.hessian(
+                &derivatives.x,
+                &derivatives.dims,
+                &derivatives.x,
+                &derivatives.dims,
+            )?;
+            derivatives.noise.add_to_diagonal(&mut kzz)?;
+
+            // only the lower triangle is used by the cholesky decomposition
+            let mut joint = DMatrix::<f64>::zeros(n + m, n + m);
+            joint.slice_mut((0, 0), (n, n)).copy_from(&kxx);
+            joint.slice_mut((n, 0), (m, n)).copy_from(&cross);
+            joint.slice_mut((n, n), (m, m)).copy_from(&kzz);
+
+            // the GP models the residuals of the derivatives from the prior mean's derivatives
+            let mean_grad = mean.gradient(&derivatives.x)?;
+            let mut residual = y - mean.call(&x)?;
+            residual.extend(
+                (0..m).map(|i| derivatives.values[i] - mean_grad[(derivatives.dims[i], i)]),
+            );
+
+            Ok((joint, residual))
+        };
+
+        let (joint, residual) = assemble().map_err(GPCompilationError::IncompatibleShapeError)?;
+        let (cholesky, jitter) = cholesky_with_jitter(joint, jitter.as_ref())?;
+        let alpha = cholesky.solve(&residual);
+
+        Ok(CompiledDerivativeGP {
+            cholesky,
+            alpha,
+            kernel,
+            jitter,
+            mean,
+            x,
+            derivatives,
+            y: residual,
+        })
+    }
+}
+
+/// A GP conditioned on function values and derivative observations
+///
+/// Predictions are of the function values, as with `CompiledGP`.
+#[derive(Debug)]
+pub struct CompiledDerivativeGP<K: KernelHessian, M: MeanGradient = Zero> {
+    /// The cholesky decomposition of the joint covariance of the values and derivatives
+    cholesky: Cholesky<f64, Dynamic>,
+    /// Factor to compute mean
+    alpha: DVector<f64>,
+    /// The original kernel
+    kernel: K,
+    /// The jitter that was added to the diagonal to make the covariance positive definite
+    jitter: f64,
+    /// The prior mean function
+    mean: M,
+    /// The points where values were observed
+    x: DMatrix<f64>,
+    /// The derivative observations
+    derivatives: DerivativeObservations,
+    /// The residuals of the values, followed by the residuals of the derivatives
+    y: DVector<f64>,
+}
+
+impl<K: KernelHessian, M: MeanGradient> CompiledDerivativeGP<K, M> {
+    /// The covariance kernel
+    pub fn kernel(&self) -> &K {
+        &self.kernel
+    }
+
+    /// The prior mean function
+    pub fn mean_function(&self) -> &M {
+        &self.mean
+    }
+
+    /// The derivative observations the GP was conditioned on
+    pub fn derivatives(&self) -> &DerivativeObservations {
+        &self.derivatives
+    }
+
+    /// The jitter that was added to the diagonal of the covariance matrix
+    pub fn jitter(&self) -> f64 {
+        self.jitter
+    }
+
+    /// Compute the log marginal likelihood of the values and derivatives together
+    pub fn log_marginal_likelihood(&self) -> f64 {
+        posterior::log_marginal_likelihood(&self.cholesky, &self.y, &self.alpha)
+    }
+
+    /// Compute the covariance between every observation and the function at each column of `x`
+    fn cross_cov(&self, x: &DMatrix<f64>) -> GPResult<DMatrix<f64>> {
+        let values = self.kernel.call(&self.x, x)?;
+        let derivatives = derivative_cross_cov(&self.kernel, x, &self.derivatives)?;
+
+        let n = values.nrows();
+        let mut cross = DMatrix::zeros(n + derivatives.nrows(), x.ncols());
+        cross.rows_mut(0, n).copy_from(&values);
+        cross
+            .rows_mut(n, derivatives.nrows())
+            .copy_from(&derivatives);
+
+        Ok(cross)
+    }
+
+    /// Compute the mean and variance from input data
+    pub fn call(&self, x: &DMatrix<f64>) -> GPResult<(DVector<f64>, DVector<f64>)> {
+        let cross = self.cross_cov(x)?;
+        Ok((
+            self.mean_precomputed(x, &cross)?,
+            self.var_precomputed(x, &cross)?,
+        ))
+    }
+
+    /// Predict the latent function at `x`, without observation noise
+    pub fn predict(&self, x: &DMatrix<f64>) -> GPResult<Prediction> {
+        let (mean, var) = self.call(x)?;
+        Ok(Prediction { mean, var })
+    }
+
+    /// Compute the mean from input data
+    pub fn mean(&self, x: &DMatrix<f64>) -> GPResult<DVector<f64>> {
+        let cross = self.cross_cov(x)?;
+        self.mean_precomputed(x, &cross)
+    }
+
+    /// Compute just the diagonal variance
+    pub fn var(&self, x: &DMatrix<f64>) -> GPResult<DVector<f64>> {
+        let cross = self.cross_cov(x)?;
+        self.var_precomputed(x, &cross)
+    }
+
+    fn mean_precomputed(&self, x: &DMatrix<f64>, cross: &DMatrix<f64>) -> GPResult<DVector<f64>> {
+        posterior::mean(self.mean.call(x)?, cross, &self.alpha)
+    }
+
+    fn var_precomputed(&self, x: &DMatrix<f64>, cross: &DMatrix<f64>) -> GPResult<DVector<f64>> {
+        posterior::var(self.kernel.call_diagonal(x)?, cross, &self.cholesky)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DMatrix, DVector};
+
+    use crate::{
+        gp::{errors::GPCompilationError, GP},
+        kernels::RBF,
+        means::Linear,
+        parameterized::FromParams,
+    };
+
+    use super::DerivativeObservations;
+
+    fn values() -> (DMatrix<f64>, DVector<f64>) {
+        let x = DMatrix::from_vec(2, 3, vec![0.0, 0.0, 1.0, 0.5, -0.5, 1.5]);
+        let y = DVector::from_vec(vec![0.3, -0.4, 1.2]);
+        (x, y)
+    }
+
+    fn gp() -> GP<RBF, Linear> {
+        GP::new(RBF::new(vec![0.8, 1.3], 1.1), 1e-4).with_mean(Linear::new(vec![0.5, -0.2], 0.1))
+    }
+
+    /// Without derivative observations, the predictions match a standard GP
+    #[test]
+    fn test_no_derivatives() {
+        let (x, y) = values();
+        let empty = DerivativeObservations::new(DMatrix::zeros(2, 0), vec![], DVector::zeros(0));
+
+        let expected = gp().compile(x.clone(), &y).unwrap();
+        let compiled = gp().compile_with_derivatives(x.clone(), &y, empty).unwrap();
+
+        let x_test = DMatrix::from_vec(2, 2, vec![0.3, 0.2, 2.0, -1.0]);
+        let (mean, var) = compiled.call(&x_test).unwrap();
+        let (expected_mean, expected_var) = expected.call(&x_test).unwrap();
+
+        assert!((mean - expected_mean).amax() < 1e-10);
+        assert!((var - expected_var).amax() < 1e-10);
+        assert!(
+            (compiled.log_marginal_likelihood() - expected.log_marginal_likelihood()).abs() < 1e-10
+        );
+    }
+
+    /// The slope of the posterior mean matches the observed derivatives
+    #[test]
+    fn test_interpolates_derivatives() {
+        let (x, y) = values();
+        let z = DMatrix::from_vec(2, 2, vec![0.5, 0.5, -1.0, 1.0]);
+        let gradients = DMatrix::from_vec(2, 2, vec![1.5, -0.7, 0.2, 0.9]);
+        let derivatives = DerivativeObservations::from_gradients(&z, &gradients)
+            .unwrap()
+            .with_noise(1e-8);
+
+        let compiled = gp().compile_with_derivatives(x, &y, derivatives).unwrap();
+
+        let eps = 1e-5;
+        for j in 0..2 {
+            for d in 0..2 {
+                let mut up = z.columns(j, 1).into_owned();
+                up[d] += eps;
+                let mut down = z.columns(j, 1).into_owned();
+                down[d] -= eps;
+
+                let fd = (compiled.mean(&up).unwrap()[0] - compiled.mean(&down).unwrap()[0])
+                    / (2.0 * eps);
+                assert!(
+                    (fd - gradients[(d, j)]).abs() < 1e-3,
+                    "{} != {}",
+                    fd,
+                    gradients[(d, j)]
+                );
+            }
+        }
+
+        // the derivatives carry information, so the variance between the points shrinks
+        let (x, y) = values();
+        let without = gp().compile(x, &y).unwrap();
+        assert!(compiled.var(&z).unwrap()[0] < without.var(&z).unwrap()[0]);
+    }
+
+    /// A product of RBF kernels is an RBF kernel, and gives the same posterior
+    #[test]
+    fn test_product_kernel() {
+        let (x, y) = values();
+        let z = DMatrix::from_vec(2, 2, vec![0.5, 0.5, -1.0, 1.0]);
+        let gradients = DMatrix::from_vec(2, 2, vec![1.5, -0.7, 0.2, 0.9]);
+        let derivatives = DerivativeObservations::from_gradients(&z, &gradients).unwrap();
+
+        let product = RBF::from_params(&[1.5, -0.4, -0.1]) * RBF::from_params(&[0.8, -0.2, -0.3]);
+        let single = RBF::from_params(&[1.2, -0.6, -0.4]);
+
+        let product = GP::new(product, 1e-4)
+            .compile_with_derivatives(x.clone(), &y, derivatives.clone())
+            .unwrap();
+        let single = GP::new(single, 1e-4)
+            .compile_with_derivatives(x, &y, derivatives)
+            .unwrap();
+
+        let x_test = DMatrix::from_vec(2, 2, vec![0.3, 0.2, 2.0, -1.0]);
+        let (mean, var) = product.call(&x_test).unwrap();
+        let (expected_mean, expected_var) = single.call(&x_test).unwrap();
+
+        assert!((mean - expected_mean).amax() < 1e-10);
+        assert!((var - expected_var).amax() < 1e-10);
+    }
+
+    /// Derivatives along a dimension the kernel does not have are rejected
+    #[test]
+    fn test_invalid_dims() {
+        let (x, y) = values();
+        let derivatives = DerivativeObservations::new(
+            DMatrix::from_vec(2, 1, vec![0.0, 0.0]),
+            vec![2],
+            DVector::from_vec(vec![1.0]),
+        );
+
+        assert!(matches!(
+            gp().compile_with_derivatives(x, &y, derivatives),
+            Err(GPCompilationError::IncompatibleShapeError(_))
+        ));
+    }
+
+    /// Gradients must have the same shape as their points
+    #[test]
+    fn test_gradients_shape() {
+        let z = DMatrix::from_vec(2, 2, vec![0.5, 0.5, -1.0, 1.0]);
+        let gradients = DMatrix::from_vec(2, 1, vec![1.5, -0.7]);
+
+        assert!(DerivativeObservations::from_gradients(&z, &gradients).is_err());
+        assert!(DerivativeObservations::from_gradients(&z, &gradients.transpose()).is_err());
+    }
+}
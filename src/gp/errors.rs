@@ -8,4 +8,9 @@ pub enum GPCompilationError {
     NonPositiveDefiniteError,
     /// The input data shape is incompatible with itself or the kernel.
     IncompatibleShapeError(IncompatibleShapeError),
+    /// New observations were added without a noise variance, but the GP has per-point noise.
+    MissingNoiseError,
+    /// The fitting options are invalid, e.g. zero starts, a bound that is not positive and finite,
+    /// or a prior with its lower bound above its upper bound.
+    InvalidOptionsError,
 }
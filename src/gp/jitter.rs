@@ -0,0 +1,162 @@
+use nalgebra::{Cholesky, DMatrix, Dynamic};
+
+use crate::linalg::util::par_add_diagonal_mut_unchecked;
+
+use super::errors::GPCompilationError;
+
+/// Retry a failed cholesky decomposition with increasing jitter on the diagonal
+///
+/// The jitter starts at `initial` and is multiplied by `factor` after each failure,
+/// until it would exceed `max`.
+///
+/// # Examples
+/// ```rust
+/// use gprs::{gp::{JitterPolicy, GP}, kernels::RBF};
+/// use nalgebra::{DVector, DMatrix};
+///
+/// // duplicate inputs make the noiseless covariance matrix singular
+/// let x = DMatrix::from_vec(1, 2, vec![1.0, 1.0]);
+/// let y = DVector::from_vec(vec![0.0, 0.0]);
+///
+/// let compiled = GP::new(RBF::new(vec![1.0], 1.0), 0.0)
+///     .with_jitter(JitterPolicy::default())
+///     .compile(x, &y)
+///     .unwrap();
+///
+/// assert!(compiled.jitter() > 0.0);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JitterPolicy {
+    /// The first jitter to try
+    pub initial: f64,
+    /// The multiplier applied to the jitter after each failure
+    pub factor: f64,
+    /// The largest jitter to try
+    pub max: f64,
+}
+
+impl Default for JitterPolicy {
+    fn default() -> Self {
+        JitterPolicy {
+            initial: 1e-10,
+            factor: 10.0,
+            max: 1e-4,
+        }
+    }
+}
+
+impl JitterPolicy {
+    /// The jitter values to try, in order
+    fn attempts(&self) -> impl Iterator<Item = f64> + '_ {
+        std::iter::successors(Some(self.initial), |jitter| {
+            let next = jitter * self.factor;
+            // a factor <= 1 would never reach the cap
+            (next > *jitter).then_some(next)
+        })
+        .take_while(|jitter| *jitter <= self.max)
+    }
+}
+
+/// Factorize a square, symmetric matrix stored in its lower triangle
+///
+/// If the matrix is not positive definite, retry with the jitter from `policy` added to the diagonal.
+/// Returns the decomposition and the jitter that was added.
+pub(crate) fn cholesky_with_jitter(
+    mat: DMatrix<f64>,
+    policy: Option<&JitterPolicy>,
+) -> Result<(Cholesky<f64, Dynamic>, f64), GPCompilationError> {
+    let policy = match policy {
+        Some(policy) => policy,
+        None => {
+            return mat
+                .cholesky()
+                .map(|cholesky| (cholesky, 0.0))
+                .ok_or(GPCompilationError::NonPositiveDefiniteError)
+        }
+    };
+
+    if let Some(cholesky) = mat.clone().cholesky() {
+        return Ok((cholesky, 0.0));
+    }
+
+    for jitter in policy.attempts() {
+        let mut jittered = mat.clone();
+
+        // SAFETY: callers only factorize square matrices
+        unsafe {
+            par_add_diagonal_mut_unchecked(&mut jittered, &jitter);
+        }
+
+        if let Some(cholesky) = jittered.cholesky() {
+            return Ok((cholesky, jitter));
+        }
+    }
+
+    Err(GPCompilationError::NonPositiveDefiniteError)
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::DMatrix;
+
+    use crate::gp::errors::GPCompilationError;
+
+    use super::{cholesky_with_jitter, JitterPolicy};
+
+    /// The jitter grows geometrically up to the cap
+    #[test]
+    fn test_attempts() {
+        let policy = JitterPolicy {
+            initial: 1.0,
+            factor: 2.0,
+            max: 10.0,
+        };
+
+        assert_eq!(
+            policy.attempts().collect::<Vec<_>>(),
+            vec![1.0, 2.0, 4.0, 8.0]
+        );
+    }
+
+    /// A policy that cannot grow only tries once
+    #[test]
+    fn test_attempts_no_growth() {
+        let policy = JitterPolicy {
+            initial: 1.0,
+            factor: 1.0,
+            max: 10.0,
+        };
+
+        assert_eq!(policy.attempts().count(), 1);
+    }
+
+    /// No jitter is added to a matrix that is already positive definite
+    #[test]
+    fn test_no_jitter_needed() {
+        let mat = DMatrix::<f64>::identity(2, 2);
+        let (_, jitter) = cholesky_with_jitter(mat, Some(&JitterPolicy::default())).unwrap();
+
+        assert_eq!(jitter, 0.0);
+    }
+
+    /// The error is returned when the cap is too small
+    #[test]
+    fn test_jitter_exhausted() {
+        let mat = DMatrix::from_vec(2, 2, vec![1.0, 1.0, 1.0, 1.0]);
+        // too small to change the diagonal of a singular matrix
+        let policy = JitterPolicy {
+            initial: 1e-20,
+            factor: 10.0,
+            max: 1e-18,
+        };
+
+        assert_eq!(
+            cholesky_with_jitter(mat.clone(), None).unwrap_err(),
+            GPCompilationError::NonPositiveDefiniteError
+        );
+        assert_eq!(
+            cholesky_with_jitter(mat, Some(&policy)).unwrap_err(),
+            GPCompilationError::NonPositiveDefiniteError
+        );
+    }
+}
@@ -1,4 +1,15 @@
 mod base;
+mod derivative;
 pub mod errors;
+mod jitter;
+mod noise;
+pub mod optimize;
+mod posterior;
+mod prediction;
+mod sample;
 
 pub use base::*;
+pub use derivative::*;
+pub use jitter::*;
+pub use noise::*;
+pub use prediction::*;
@@ -0,0 +1,88 @@
+use nalgebra::{DMatrix, DVector};
+
+use crate::linalg::{
+    errors::IncompatibleShapeError,
+    util::{par_add_diagonal_mut_unchecked, par_add_diagonal_vec_mut_unchecked},
+};
+
+/// Variance of the observation noise
+///
+/// # Examples
+/// ```rust
+/// use gprs::gp::Noise;
+/// use nalgebra::DVector;
+///
+/// let homoscedastic = Noise::from(0.1);
+/// let heteroscedastic = Noise::PerPoint(DVector::from_vec(vec![0.1, 0.5, 0.2]));
+///
+/// assert_eq!(homoscedastic.constant(), Some(0.1));
+/// assert_eq!(heteroscedastic.constant(), None);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum Noise {
+    /// The same variance for every observation
+    Constant(f64),
+    /// One variance per observation, in the same order as the columns of the data
+    PerPoint(DVector<f64>),
+}
+
+impl Noise {
+    /// The noise variance, if it is the same for every observation
+    pub fn constant(&self) -> Option<f64> {
+        match self {
+            Noise::Constant(noise) => Some(*noise),
+            Noise::PerPoint(_) => None,
+        }
+    }
+
+    /// Check that there is a noise variance for each of `n` observations
+    pub(crate) fn check_len(&self, n: usize) -> Result<(), IncompatibleShapeError> {
+        match self {
+            Noise::PerPoint(noise) if noise.len() != n => Err(IncompatibleShapeError {
+                shapes: vec![noise.shape(), (n, 1)],
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Add the noise to the diagonal of a square matrix, in-place
+    pub(crate) fn add_to_diagonal(
+        &self,
+        mat: &mut DMatrix<f64>,
+    ) -> Result<(), IncompatibleShapeError> {
+        if !mat.is_square() {
+            return Err(IncompatibleShapeError {
+                shapes: vec![mat.shape()],
+            });
+        }
+        self.check_len(mat.nrows())?;
+
+        // SAFETY: mat is square, and there is one noise variance per row
+        unsafe {
+            match self {
+                Noise::Constant(noise) => par_add_diagonal_mut_unchecked(mat, noise),
+                Noise::PerPoint(noise) => par_add_diagonal_vec_mut_unchecked(mat, noise.as_slice()),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Add the noise to a vector of variances, in-place
+    pub(crate) fn add_to(&self, var: &mut DVector<f64>) -> Result<(), IncompatibleShapeError> {
+        self.check_len(var.len())?;
+
+        match self {
+            Noise::Constant(noise) => var.add_scalar_mut(*noise),
+            Noise::PerPoint(noise) => *var += noise,
+        }
+
+        Ok(())
+    }
+}
+
+impl From<f64> for Noise {
+    fn from(noise: f64) -> Self {
+        Noise::Constant(noise)
+    }
+}
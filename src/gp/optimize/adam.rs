@@ -0,0 +1,137 @@
+//! The Adam method for stochastic gradients (Kingma & Ba, 2015)
+//!
+//! Unlike L-BFGS, Adam does not need the objective itself and tolerates noisy gradients,
+//! such as those estimated from a minibatch of the data.
+
+/// Settings for the Adam minimizer
+#[derive(Debug, Clone)]
+pub struct AdamOptions {
+    /// The size of each step
+    pub learning_rate: f64,
+    /// Decay rate of the running mean of the gradient
+    pub beta1: f64,
+    /// Decay rate of the running mean of the squared gradient
+    pub beta2: f64,
+    /// Added to the root mean squared gradient to avoid dividing by zero
+    pub epsilon: f64,
+}
+
+impl Default for AdamOptions {
+    fn default() -> Self {
+        AdamOptions {
+            learning_rate: 0.01,
+            beta1: 0.9,
+            beta2: 0.999,
+            epsilon: 1e-8,
+        }
+    }
+}
+
+/// The running moments of an Adam minimizer
+///
+/// # Examples
+/// ```rust
+/// use gprs::gp::optimize::adam::{Adam, AdamOptions};
+///
+/// // minimize (x - 3)^2
+/// let mut x = vec![0.0];
+/// let mut adam = Adam::new(1, AdamOptions { learning_rate: 0.1, ..Default::default() });
+///
+/// for _ in 0..500 {
+///     let grad = vec![2.0 * (x[0] - 3.0)];
+///     adam.step(&mut x, &grad);
+/// }
+///
+/// assert!((x[0] - 3.0).abs() < 1e-3);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Adam {
+    options: AdamOptions,
+    /// The running mean of the gradient
+    m: Vec<f64>,
+    /// The running mean of the squared gradient
+    v: Vec<f64>,
+    /// The number of steps taken
+    t: i32,
+}
+
+impl Adam {
+    /// Create a minimizer for `n` variables
+    pub fn new(n: usize, options: AdamOptions) -> Self {
+        Adam {
+            options,
+            m: vec![0.0; n],
+            v: vec![0.0; n],
+            t: 0,
+        }
+    }
+
+    /// Move `x` against the gradient `grad`, in-place
+    ///
+    /// # Panics
+    /// If `x` or `grad` do not have one entry per variable
+    pub fn step(&mut self, x: &mut [f64], grad: &[f64]) {
+        assert_eq!(x.len(), self.m.len());
+        assert_eq!(grad.len(), self.m.len());
+
+        let AdamOptions {
+            learning_rate,
+            beta1,
+            beta2,
+            epsilon,
+        } = self.options;
+
+        self.t += 1;
+        // correct the bias towards zero of the running means
+        let correction1 = 1.0 - beta1.powi(self.t);
+        let correction2 = 1.0 - beta2.powi(self.t);
+
+        for (((xi, gi), mi), vi) in x.iter_mut().zip(grad).zip(&mut self.m).zip(&mut self.v) {
+            *mi = beta1 * *mi + (1.0 - beta1) * gi;
+            *vi = beta2 * *vi + (1.0 - beta2) * gi * gi;
+
+            let m_hat = *mi / correction1;
+            let v_hat = *vi / correction2;
+            *xi -= learning_rate * m_hat / (v_hat.sqrt() + epsilon);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Adam, AdamOptions};
+
+    /// The first step has the size of the learning rate, whatever the gradient's scale
+    #[test]
+    fn test_first_step() {
+        for scale in [1e-3, 1.0, 1e6] {
+            let mut x = vec![1.0, 1.0];
+            let mut adam = Adam::new(2, AdamOptions::default());
+            adam.step(&mut x, &[scale, -scale]);
+
+            assert!((x[0] - 0.99).abs() < 1e-6, "{}", x[0]);
+            assert!((x[1] - 1.01).abs() < 1e-6);
+        }
+    }
+
+    /// A badly scaled quadratic is minimized
+    #[test]
+    fn test_quadratic() {
+        let mut x = vec![5.0, -5.0];
+        let mut adam = Adam::new(
+            2,
+            AdamOptions {
+                learning_rate: 0.05,
+                ..Default::default()
+            },
+        );
+
+        for _ in 0..2000 {
+            let grad = [200.0 * (x[0] - 1.0), 0.02 * (x[1] + 2.0)];
+            adam.step(&mut x, &grad);
+        }
+
+        assert!((x[0] - 1.0).abs() < 1e-3);
+        assert!((x[1] + 2.0).abs() < 1e-2);
+    }
+}
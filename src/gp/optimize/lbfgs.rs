@@ -0,0 +1,302 @@
+use std::collections::VecDeque;
+
+/// Settings for the L-BFGS minimizer
+#[derive(Debug, Clone)]
+pub struct LbfgsOptions {
+    /// Maximum number of iterations
+    pub max_iter: usize,
+    /// Number of past updates used to approximate the inverse hessian
+    pub history: usize,
+    /// Stop when the largest projected gradient component falls below this value
+    pub gtol: f64,
+    /// Stop when the relative decrease in the objective falls below this value
+    pub ftol: f64,
+}
+
+impl Default for LbfgsOptions {
+    fn default() -> Self {
+        LbfgsOptions {
+            max_iter: 200,
+            history: 10,
+            gtol: 1e-5,
+            ftol: 1e-10,
+        }
+    }
+}
+
+/// The outcome of a minimization
+#[derive(Debug, Clone)]
+pub struct LbfgsResult {
+    /// The best point found
+    pub x: Vec<f64>,
+    /// The objective value at `x`
+    pub f: f64,
+    /// The number of iterations run
+    pub iterations: usize,
+    /// Whether a convergence criterion was met before running out of iterations
+    pub converged: bool,
+}
+
+/// Maximum number of step halvings in the line search
+const MAX_BACKTRACKS: usize = 30;
+
+/// Sufficient decrease constant for the armijo condition
+const ARMIJO: f64 = 1e-4;
+
+/// Minimize a function subject to box constraints with projected L-BFGS
+///
+/// `f` returns the objective value and its gradient, or `None` if the point cannot be evaluated.
+/// Points that cannot be evaluated are treated as infinitely bad, so the line search backs away from them.
+///
+/// Returns `None` if the starting point (clamped to the bounds) cannot be evaluated.
+///
+/// # Examples
+/// ```rust
+/// use gprs::gp::optimize::lbfgs::{minimize, LbfgsOptions};
+///
+/// // minimize (x - 3)^2 with x in [0, 2]
+/// let result = minimize(
+///     |x: &[f64]| Some(((x[0] - 3.0).powi(2), vec![2.0 * (x[0] - 3.0)])),
+///     &[0.5],
+///     &[0.0],
+///     &[2.0],
+///     &LbfgsOptions::default(),
+/// )
+/// .unwrap();
+///
+/// assert!((result.x[0] - 2.0).abs() < 1e-12);
+/// ```
+pub fn minimize<F>(
+    mut f: F,
+    x0: &[f64],
+    lower: &[f64],
+    upper: &[f64],
+    options: &LbfgsOptions,
+) -> Option<LbfgsResult>
+where
+    F: FnMut(&[f64]) -> Option<(f64, Vec<f64>)>,
+{
+    let mut x = x0.to_vec();
+    project(&mut x, lower, upper);
+
+    let (mut fx, mut g) = f(&x)?;
+    let mut history: VecDeque<(Vec<f64>, Vec<f64>, f64)> = VecDeque::with_capacity(options.history);
+
+    let mut iterations = 0;
+    let mut converged = false;
+
+    while iterations < options.max_iter {
+        let pg = projected_gradient(&x, &g, lower, upper);
+        if pg.iter().all(|v| v.abs() < options.gtol) {
+            converged = true;
+            break;
+        }
+
+        iterations += 1;
+
+        let mut d = direction(&pg, &history);
+        if dot(&d, &pg) >= 0.0 {
+            // the curvature history no longer describes a descent direction
+            history.clear();
+            d = pg.iter().map(|v| -v).collect();
+        }
+
+        // the first steepest-descent step has no scale information, so keep it short
+        let mut t = if history.is_empty() {
+            (1.0 / norm(&d)).min(1.0)
+        } else {
+            1.0
+        };
+
+        let mut accepted = None;
+        for _ in 0..MAX_BACKTRACKS {
+            let mut x_new: Vec<f64> = x.iter().zip(&d).map(|(xi, di)| xi + t * di).collect();
+            project(&mut x_new, lower, upper);
+
+            if let Some((f_new, g_new)) = f(&x_new) {
+                let step: Vec<f64> = x_new.iter().zip(&x).map(|(a, b)| a - b).collect();
+                if f_new.is_finite() && f_new <= fx + ARMIJO * dot(&g, &step) {
+                    accepted = Some((x_new, f_new, g_new, step));
+                    break;
+                }
+            }
+
+            t *= 0.5;
+        }
+
+        let (x_new, f_new, g_new, s) = match accepted {
+            Some(v) => v,
+            None if !history.is_empty() => {
+                // retry from steepest descent before giving up
+                history.clear();
+                continue;
+            }
+            None => break,
+        };
+
+        let y: Vec<f64> = g_new.iter().zip(&g).map(|(a, b)| a - b).collect();
+        let sy = dot(&s, &y);
+        if sy > 1e-10 {
+            if history.len() == options.history {
+                history.pop_front();
+            }
+            history.push_back((s, y, 1.0 / sy));
+        }
+
+        let decrease = fx - f_new;
+        x = x_new;
+        g = g_new;
+        let scale = fx.abs().max(f_new.abs()).max(1.0);
+        fx = f_new;
+
+        if decrease <= options.ftol * scale {
+            converged = true;
+            break;
+        }
+    }
+
+    Some(LbfgsResult {
+        x,
+        f: fx,
+        iterations,
+        converged,
+    })
+}
+
+/// Clamp `x` to lie within the bounds
+fn project(x: &mut [f64], lower: &[f64], upper: &[f64]) {
+    x.iter_mut()
+        .zip(lower)
+        .zip(upper)
+        .for_each(|((v, lo), hi)| *v = v.max(*lo).min(*hi));
+}
+
+/// Zero the gradient components that would push a variable further past an active bound
+fn projected_gradient(x: &[f64], g: &[f64], lower: &[f64], upper: &[f64]) -> Vec<f64> {
+    x.iter()
+        .zip(g)
+        .zip(lower.iter().zip(upper))
+        .map(|((xi, gi), (lo, hi))| {
+            if (*xi <= *lo && *gi > 0.0) || (*xi >= *hi && *gi < 0.0) {
+                0.0
+            } else {
+                *gi
+            }
+        })
+        .collect()
+}
+
+/// Compute the quasi-newton search direction with the two-loop recursion
+fn direction(g: &[f64], history: &VecDeque<(Vec<f64>, Vec<f64>, f64)>) -> Vec<f64> {
+    let mut q = g.to_vec();
+    let mut alphas = Vec::with_capacity(history.len());
+
+    for (s, y, rho) in history.iter().rev() {
+        let a = rho * dot(s, &q);
+        q.iter_mut().zip(y).for_each(|(qi, yi)| *qi -= a * yi);
+        alphas.push(a);
+    }
+
+    if let Some((s, y, _)) = history.back() {
+        let gamma = dot(s, y) / dot(y, y);
+        q.iter_mut().for_each(|qi| *qi *= gamma);
+    }
+
+    for ((s, y, rho), a) in history.iter().zip(alphas.into_iter().rev()) {
+        let b = rho * dot(y, &q);
+        q.iter_mut().zip(s).for_each(|(qi, si)| *qi += (a - b) * si);
+    }
+
+    // keep variables pinned at a bound where the projected gradient is zero
+    q.iter()
+        .zip(g)
+        .map(|(qi, gi)| if *gi == 0.0 { 0.0 } else { -qi })
+        .collect()
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn norm(a: &[f64]) -> f64 {
+    dot(a, a).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{minimize, LbfgsOptions};
+
+    fn rosenbrock(x: &[f64]) -> Option<(f64, Vec<f64>)> {
+        let (a, b) = (x[0], x[1]);
+        let f = (1.0 - a).powi(2) + 100.0 * (b - a * a).powi(2);
+        let g = vec![
+            -2.0 * (1.0 - a) - 400.0 * a * (b - a * a),
+            200.0 * (b - a * a),
+        ];
+        Some((f, g))
+    }
+
+    /// The unconstrained minimum of the rosenbrock function is found
+    #[test]
+    fn test_rosenbrock() {
+        let inf = f64::INFINITY;
+        let result = minimize(
+            rosenbrock,
+            &[-1.2, 1.0],
+            &[-inf, -inf],
+            &[inf, inf],
+            &LbfgsOptions::default(),
+        )
+        .unwrap();
+
+        assert!((result.x[0] - 1.0).abs() < 1e-4);
+        assert!((result.x[1] - 1.0).abs() < 1e-4);
+    }
+
+    /// A minimum outside the bounds is clamped to the nearest bound
+    #[test]
+    fn test_bounded() {
+        let f = |x: &[f64]| {
+            let f = x.iter().map(|v| (v - 2.0).powi(2)).sum();
+            let g = x.iter().map(|v| 2.0 * (v - 2.0)).collect();
+            Some((f, g))
+        };
+
+        let result = minimize(
+            f,
+            &[0.0, 0.0],
+            &[-1.0, -1.0],
+            &[1.0, 3.0],
+            &LbfgsOptions::default(),
+        )
+        .unwrap();
+
+        assert!((result.x[0] - 1.0).abs() < 1e-8);
+        assert!((result.x[1] - 2.0).abs() < 1e-4);
+        assert!(result.converged);
+    }
+
+    /// Regions that cannot be evaluated are avoided by the line search
+    #[test]
+    fn test_infeasible_region() {
+        let inf = f64::INFINITY;
+        // log barrier: only defined for x > 0, minimum at x = 1
+        let f = |x: &[f64]| {
+            if x[0] <= 0.0 {
+                return None;
+            }
+            Some((x[0] - x[0].ln(), vec![1.0 - 1.0 / x[0]]))
+        };
+
+        let result = minimize(f, &[5.0], &[-inf], &[inf], &LbfgsOptions::default()).unwrap();
+
+        assert!((result.x[0] - 1.0).abs() < 1e-4);
+    }
+
+    /// An infeasible starting point returns None
+    #[test]
+    fn test_infeasible_start() {
+        let result = minimize(|_| None, &[0.0], &[-1.0], &[1.0], &LbfgsOptions::default());
+        assert!(result.is_none());
+    }
+}
@@ -0,0 +1,116 @@
+use crate::{gp::errors::GPCompilationError, linalg::errors::IncompatibleShapeError};
+
+/// Search variables over the log magnitude of parameters, which keep their sign during the search
+///
+/// Each parameter is `θ = s * exp(u)` for its fixed sign `s` and search variable `u`,
+/// so bounds are expressed as magnitudes.
+#[derive(Debug, Clone)]
+pub(crate) struct LogMagnitudes {
+    /// The sign of each parameter
+    signs: Vec<f64>,
+    /// The lower bound of each search variable
+    pub(crate) lower: Vec<f64>,
+    /// The upper bound of each search variable
+    pub(crate) upper: Vec<f64>,
+}
+
+impl LogMagnitudes {
+    /// Search over `params`, with one bound per parameter, or `default_bounds` for every parameter
+    ///
+    /// Returns `InvalidOptionsError` if a bound is not positive and finite, or a lower bound is above its upper bound.
+    pub(crate) fn new(
+        params: &[f64],
+        bounds: Option<&[(f64, f64)]>,
+        default_bounds: (f64, f64),
+    ) -> Result<Self, GPCompilationError> {
+        let bounds = match bounds {
+            Some(bounds) if bounds.len() != params.len() => {
+                return Err(GPCompilationError::IncompatibleShapeError(
+                    IncompatibleShapeError {
+                        shapes: vec![(bounds.len(), 1), (params.len(), 1)],
+                    },
+                ));
+            }
+            Some(bounds) => bounds.to_vec(),
+            None => vec![default_bounds; params.len()],
+        };
+        if !bounds.iter().all(|b| is_valid(*b)) {
+            return Err(GPCompilationError::InvalidOptionsError);
+        }
+
+        Ok(LogMagnitudes {
+            signs: params
+                .iter()
+                .map(|p| if *p < 0.0 { -1.0 } else { 1.0 })
+                .collect(),
+            lower: bounds.iter().map(|(lo, _)| lo.ln()).collect(),
+            upper: bounds.iter().map(|(_, hi)| hi.ln()).collect(),
+        })
+    }
+
+    /// Search over `params` without bounds
+    pub(crate) fn unbounded(params: &[f64]) -> Self {
+        LogMagnitudes {
+            signs: params
+                .iter()
+                .map(|p| if *p < 0.0 { -1.0 } else { 1.0 })
+                .collect(),
+            lower: vec![f64::NEG_INFINITY; params.len()],
+            upper: vec![f64::INFINITY; params.len()],
+        }
+    }
+
+    /// Add a positive parameter, such as the noise variance
+    ///
+    /// Returns `InvalidOptionsError` if the bounds are invalid, like `new`.
+    pub(crate) fn push_positive(
+        &mut self,
+        (lower, upper): (f64, f64),
+    ) -> Result<(), GPCompilationError> {
+        if !is_valid((lower, upper)) {
+            return Err(GPCompilationError::InvalidOptionsError);
+        }
+        self.signs.push(1.0);
+        self.lower.push(lower.ln());
+        self.upper.push(upper.ln());
+        Ok(())
+    }
+
+    /// The number of parameters
+    pub(crate) fn len(&self) -> usize {
+        self.signs.len()
+    }
+
+    /// The search variables for `params`. A parameter of exactly zero starts at its lower bound.
+    pub(crate) fn to_search(&self, params: &[f64]) -> Vec<f64> {
+        params
+            .iter()
+            .zip(&self.lower)
+            .map(|(p, lo)| p.abs().ln().max(*lo))
+            .collect()
+    }
+
+    /// The parameters for the leading search variables `u`
+    pub(crate) fn to_params(&self, u: &[f64]) -> Vec<f64> {
+        self.signs
+            .iter()
+            .zip(u)
+            .map(|(s, ui)| s * ui.exp())
+            .collect()
+    }
+
+    /// Convert the gradient w.r.t. the parameters to the gradient w.r.t. the search variables
+    ///
+    /// `dθ/du = θ`, since `θ = s * exp(u)`
+    pub(crate) fn to_search_gradient(&self, u: &[f64], grad: &[f64]) -> Vec<f64> {
+        grad.iter()
+            .zip(self.to_params(u))
+            .map(|(g, p)| g * p)
+            .collect()
+    }
+}
+
+/// Whether bounds on a magnitude are positive, finite and ordered
+fn is_valid((lower, upper): (f64, f64)) -> bool {
+    lower > 0.0 && lower <= upper && upper.is_finite()
+}
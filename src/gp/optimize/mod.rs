@@ -0,0 +1,506 @@
+//! Hyperparameter optimization for gaussian processes
+//!
+//! Kernel parameters, the noise and the mean function parameters are optimized by maximizing
+//! the log marginal likelihood, or the leave-one-out score, with L-BFGS.
+//! The search runs over the log of each kernel parameter's magnitude, so kernel parameters keep
+//! their sign and bounds are expressed as magnitudes. Mean function parameters are searched
+//! directly, without bounds.
+
+pub mod adam;
+pub mod lbfgs;
+mod magnitude;
+mod multistart;
+
+pub(crate) use magnitude::LogMagnitudes;
+pub use multistart::*;
+
+use nalgebra::{DMatrix, DVector};
+
+use crate::{
+    kernels::Kernel,
+    means::MeanFunction,
+    parameterized::{Jacobian, Parameterized},
+};
+
+use self::lbfgs::{minimize, LbfgsOptions};
+
+use super::{errors::GPCompilationError, CompiledGP, Noise, GP};
+
+/// The quantity maximized by hyperparameter optimization
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Objective {
+    /// The log marginal likelihood of the training data
+    #[default]
+    LogMarginalLikelihood,
+    /// The leave-one-out log predictive probability, which is less sensitive to a misspecified kernel
+    LeaveOneOut,
+}
+
+impl Objective {
+    /// Evaluate the objective of a compiled GP
+    pub(crate) fn score<K, M>(&self, compiled: &CompiledGP<K, M>) -> Option<f64>
+    where
+        K: Kernel,
+        M: MeanFunction,
+    {
+        match self {
+            Objective::LogMarginalLikelihood => Some(compiled.log_marginal_likelihood()),
+            Objective::LeaveOneOut => compiled.loo().ok().map(|loo| loo.score()),
+        }
+    }
+
+    /// Evaluate the objective, and its gradient w.r.t. the kernel parameters, noise and mean function
+    ///
+    /// The mean function gradient is empty unless `with_mean` is set.
+    fn evaluate<K, M>(
+        &self,
+        compiled: &CompiledGP<K, M>,
+        with_mean: bool,
+    ) -> Option<(f64, Vec<f64>, f64, Vec<f64>)>
+    where
+        K: Kernel + for<'a> Jacobian<'a>,
+        M: MeanFunction + for<'a> Jacobian<'a>,
+    {
+        match self {
+            Objective::LogMarginalLikelihood => {
+                let (kernel_grad, noise_grad) =
+                    compiled.log_marginal_likelihood_gradients().ok()?;
+                let mean_grad = if with_mean {
+                    compiled.log_marginal_likelihood_mean_gradient().ok()?
+                } else {
+                    Vec::new()
+                };
+                let lml = compiled.log_marginal_likelihood();
+                Some((lml, kernel_grad, noise_grad, mean_grad))
+            }
+            Objective::LeaveOneOut => {
+                let (kernel_grad, noise_grad, mut mean_grad) = compiled.loo_gradients().ok()?;
+                if !with_mean {
+                    mean_grad.clear();
+                }
+                let score = compiled.loo().ok()?.score();
+                Some((score, kernel_grad, noise_grad, mean_grad))
+            }
+        }
+    }
+}
+
+/// Settings for hyperparameter optimization
+#[derive(Debug, Clone)]
+pub struct OptimizeOptions {
+    /// Bounds `(lower, upper)` on the magnitude of each kernel parameter, in `get_params` order.
+    ///
+    /// `None` applies `default_bounds` to every parameter.
+    pub bounds: Option<Vec<(f64, f64)>>,
+    /// Bounds on the magnitude of kernel parameters when `bounds` is `None`
+    pub default_bounds: (f64, f64),
+    /// Bounds on the noise variance
+    pub noise_bounds: (f64, f64),
+    /// Whether to optimize the noise variance, or keep it fixed.
+    ///
+    /// Per-point noise is always kept fixed.
+    pub optimize_noise: bool,
+    /// Whether to optimize the mean function parameters, or keep them fixed
+    pub optimize_mean: bool,
+    /// The quantity to maximize
+    pub objective: Objective,
+    /// Settings for the L-BFGS minimizer
+    pub lbfgs: LbfgsOptions,
+}
+
+impl Default for OptimizeOptions {
+    fn default() -> Self {
+        OptimizeOptions {
+            bounds: None,
+            default_bounds: (1e-5, 1e5),
+            noise_bounds: (1e-8, 1e5),
+            optimize_noise: true,
+            optimize_mean: true,
+            objective: Objective::default(),
+            lbfgs: LbfgsOptions::default(),
+        }
+    }
+}
+
+/// Fit the kernel parameters, noise and mean function of a GP by maximizing the log marginal likelihood,
+/// or the objective set in `options`.
+///
+/// The GP's current parameters are the starting point. A kernel parameter of exactly zero starts at its lower bound.
+///
+/// # Examples
+/// ```rust
+/// use gprs::{
+///     gp::{optimize::{fit, OptimizeOptions}, GP},
+///     kernels::RBF,
+/// };
+/// use nalgebra::{DMatrix, DVector};
+///
+/// let x = DMatrix::from_fn(1, 20, |_, j| j as f64 * 0.5);
+/// let y = DVector::from_fn(20, |i, _| (i as f64 * 0.5).sin());
+///
+/// let gp = GP::new(RBF::new(vec![0.1], 1.0), 1.0);
+/// let initial = GP::new(RBF::new(vec![0.1], 1.0), 1.0)
+///     .compile(x.clone(), &y)
+///     .unwrap()
+///     .log_marginal_likelihood();
+///
+/// let fitted = fit(gp, x, &y, &OptimizeOptions::default()).unwrap();
+///
+/// assert!(fitted.log_marginal_likelihood() > initial);
+/// ```
+pub fn fit<K, M>(
+    gp: GP<K, M>,
+    x: DMatrix<f64>,
+    y: &DVector<f64>,
+    options: &OptimizeOptions,
+) -> Result<CompiledGP<K, M>, GPCompilationError>
+where
+    K: Kernel + Clone + for<'a> Parameterized<'a> + for<'a> Jacobian<'a>,
+    M: MeanFunction + Clone + for<'a> Parameterized<'a> + for<'a> Jacobian<'a>,
+{
+    let space = SearchSpace::new(&gp, options)?;
+    let u0 = space.initial(&gp);
+    let u = search(&gp, &x, y, &space, u0, options);
+
+    space.to_gp(&gp, &u).compile(x, y)
+}
+
+/// Run L-BFGS from the search variables `u0`, returning the best search variables found
+fn search<K, M>(
+    gp: &GP<K, M>,
+    x: &DMatrix<f64>,
+    y: &DVector<f64>,
+    space: &SearchSpace,
+    u0: Vec<f64>,
+    options: &OptimizeOptions,
+) -> Vec<f64>
+where
+    K: Kernel + Clone + for<'a> Parameterized<'a> + for<'a> Jacobian<'a>,
+    M: MeanFunction + Clone + for<'a> Parameterized<'a> + for<'a> Jacobian<'a>,
+{
+    let objective = |u: &[f64]| {
+        let compiled = space.to_gp(gp, u).compile(x.clone(), y).ok()?;
+        let (value, kernel_grad, noise_grad, mean_grad) =
+            options.objective.evaluate(&compiled, space.nmean > 0)?;
+        Some(space.to_objective(u, value, &kernel_grad, noise_grad, &mean_grad))
+    };
+
+    minimize(objective, &u0, &space.lower, &space.upper, &options.lbfgs)
+        .map(|result| result.x)
+        .unwrap_or(u0)
+}
+
+/// Mapping between GP parameters and the search variables of L-BFGS
+///
+/// The search variables are the log kernel parameters, then the log noise, then the mean function parameters.
+struct SearchSpace {
+    /// The kernel parameters, followed by the noise if it is optimized
+    magnitudes: LogMagnitudes,
+    /// The number of kernel parameters
+    nkernel: usize,
+    /// Whether the noise follows the kernel parameters
+    optimize_noise: bool,
+    /// The number of mean function parameters at the end, or zero if they are fixed
+    nmean: usize,
+    lower: Vec<f64>,
+    upper: Vec<f64>,
+}
+
+impl SearchSpace {
+    fn new<K, M>(gp: &GP<K, M>, options: &OptimizeOptions) -> Result<Self, GPCompilationError>
+    where
+        K: Kernel + for<'a> Parameterized<'a>,
+        M: MeanFunction + for<'a> Parameterized<'a>,
+    {
+        let params = gp.kernel().get_params();
+        let mut magnitudes =
+            LogMagnitudes::new(&params, options.bounds.as_deref(), options.default_bounds)?;
+
+        let optimize_noise = options.optimize_noise && gp.noise().constant().is_some();
+        if optimize_noise {
+            magnitudes.push_positive(options.noise_bounds)?;
+        }
+
+        let nmean = if options.optimize_mean {
+            gp.mean_function().get_params().len()
+        } else {
+            0
+        };
+        let mut lower = magnitudes.lower.clone();
+        let mut upper = magnitudes.upper.clone();
+        lower.extend(std::iter::repeat_n(f64::NEG_INFINITY, nmean));
+        upper.extend(std::iter::repeat_n(f64::INFINITY, nmean));
+
+        Ok(SearchSpace {
+            magnitudes,
+            nkernel: params.len(),
+            optimize_noise,
+            nmean,
+            lower,
+            upper,
+        })
+    }
+
+    /// The search variables for the GP's current parameters
+    fn initial<K, M>(&self, gp: &GP<K, M>) -> Vec<f64>
+    where
+        K: Kernel + for<'a> Parameterized<'a>,
+        M: MeanFunction + for<'a> Parameterized<'a>,
+    {
+        let mut params = gp.kernel().get_params();
+        if self.optimize_noise {
+            params.extend(gp.noise().constant());
+        }
+
+        let mut u = self.magnitudes.to_search(&params);
+
+        if self.nmean > 0 {
+            u.extend(gp.mean_function().get_params());
+        }
+
+        u
+    }
+
+    /// The index of the first mean function parameter in the search variables
+    fn mean_offset(&self) -> usize {
+        self.magnitudes.len()
+    }
+
+    /// Create a GP from search variables
+    fn to_gp<K, M>(&self, gp: &GP<K, M>, u: &[f64]) -> GP<K, M>
+    where
+        K: Kernel + Clone + for<'a> Parameterized<'a>,
+        M: MeanFunction + Clone + for<'a> Parameterized<'a>,
+    {
+        let params = self.magnitudes.to_params(u);
+
+        let mut kernel = gp.kernel().clone();
+        kernel.set_params(&params[..self.nkernel]);
+
+        let noise = if self.optimize_noise {
+            Noise::Constant(params[self.nkernel])
+        } else {
+            gp.noise().clone()
+        };
+
+        let mut mean = gp.mean_function().clone();
+        if self.nmean > 0 {
+            mean.set_params(&u[self.mean_offset()..]);
+        }
+
+        gp.rebuild(kernel, noise, mean)
+    }
+
+    /// Convert the maximized objective and its gradient to the objective minimized in the search space
+    fn to_objective(
+        &self,
+        u: &[f64],
+        value: f64,
+        kernel_grad: &[f64],
+        noise_grad: f64,
+        mean_grad: &[f64],
+    ) -> (f64, Vec<f64>) {
+        let mut params_grad = kernel_grad.to_vec();
+        if self.optimize_noise {
+            params_grad.push(noise_grad);
+        }
+
+        let mut grad = self.magnitudes.to_search_gradient(u, &params_grad);
+        grad.extend_from_slice(mean_grad);
+
+        (-value, grad.iter().map(|g| -g).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DMatrix, DVector};
+
+    use crate::{
+        gp::{errors::GPCompilationError, Noise, GP},
+        kernels::{Matern32, RBF},
+        means::{Linear, Polynomial},
+        parameterized::Parameterized,
+    };
+
+    use super::{fit, Objective, OptimizeOptions};
+
+    fn data() -> (DMatrix<f64>, DVector<f64>) {
+        let x = DMatrix::from_fn(1, 30, |_, j| j as f64 * 0.3);
+        let y = DVector::from_fn(30, |i, _| (i as f64 * 0.3).sin());
+        (x, y)
+    }
+
+    /// Fitting finds a length scale close to the scale of the data
+    #[test]
+    fn test_fit_length_scale() {
+        let (x, y) = data();
+        let gp = GP::new(RBF::new(vec![0.2], 1.0), 0.5);
+
+        let fitted = fit(gp, x, &y, &OptimizeOptions::default()).unwrap();
+
+        // gamma = -0.5 / l^2
+        let gamma = fitted.kernel().get_params()[1];
+        let length_scale = (-0.5 / gamma).sqrt();
+        assert!(length_scale > 0.5 && length_scale < 5.0);
+        assert!(fitted.noise().constant().unwrap() < 0.01);
+    }
+
+    /// The noise is not changed when it is not optimized
+    #[test]
+    fn test_fixed_noise() {
+        let (x, y) = data();
+        let gp = GP::new(RBF::new(vec![0.5], 1.0), 0.3);
+
+        let options = OptimizeOptions {
+            optimize_noise: false,
+            ..Default::default()
+        };
+        let fitted = fit(gp, x, &y, &options).unwrap();
+
+        assert_eq!(fitted.noise(), &Noise::Constant(0.3));
+    }
+
+    /// Parameters stay within their bounds
+    #[test]
+    fn test_bounds() {
+        let (x, y) = data();
+        let gp = GP::new(RBF::new(vec![0.5], 1.0), 0.3);
+
+        let options = OptimizeOptions {
+            bounds: Some(vec![(0.5, 2.0), (1.0, 10.0)]),
+            ..Default::default()
+        };
+        let fitted = fit(gp, x, &y, &options).unwrap();
+        let params = fitted.kernel().get_params();
+
+        assert!(params[0] >= 0.5 - 1e-12 && params[0] <= 2.0 + 1e-12);
+        assert!(-params[1] >= 1.0 - 1e-12 && -params[1] <= 10.0 + 1e-12);
+    }
+
+    /// Bounds with the wrong length are rejected
+    #[test]
+    fn test_bounds_shape() {
+        let (x, y) = data();
+        let gp = GP::new(RBF::new(vec![0.5], 1.0), 0.3);
+
+        let options = OptimizeOptions {
+            bounds: Some(vec![(0.5, 2.0)]),
+            ..Default::default()
+        };
+
+        assert!(fit(gp, x, &y, &options).is_err());
+    }
+
+    /// Bounds that are not positive, finite and ordered are rejected
+    #[test]
+    fn test_invalid_bounds() {
+        let (x, y) = data();
+        let gp = || GP::new(RBF::new(vec![0.5], 1.0), 0.3);
+
+        for bounds in [
+            (0.0, 2.0),
+            (-1.0, 2.0),
+            (2.0, 0.5),
+            (0.5, f64::INFINITY),
+            (f64::NAN, 2.0),
+        ] {
+            let options = OptimizeOptions {
+                bounds: Some(vec![bounds, (1.0, 10.0)]),
+                ..Default::default()
+            };
+            let result = fit(gp(), x.clone(), &y, &options);
+            assert_eq!(result.err(), Some(GPCompilationError::InvalidOptionsError));
+
+            let options = OptimizeOptions {
+                noise_bounds: bounds,
+                ..Default::default()
+            };
+            let result = fit(gp(), x.clone(), &y, &options);
+            assert_eq!(result.err(), Some(GPCompilationError::InvalidOptionsError));
+        }
+    }
+
+    /// Composite kernels are fit over their concatenated parameters
+    #[test]
+    fn test_fit_composite() {
+        let (x, y) = data();
+        let kernel = RBF::new(vec![1.0], 1.0) + Matern32::new(vec![1.0], 0.5);
+        let gp = GP::new(kernel.clone(), 0.3);
+
+        let initial = GP::new(kernel, 0.3)
+            .compile(x.clone(), &y)
+            .unwrap()
+            .log_marginal_likelihood();
+        let fitted = fit(gp, x, &y, &OptimizeOptions::default()).unwrap();
+
+        assert_eq!(fitted.kernel().get_params().len(), 4);
+        assert!(fitted.log_marginal_likelihood() > initial);
+    }
+
+    /// Mean function coefficients are learned together with the kernel parameters
+    #[test]
+    fn test_fit_mean() {
+        let (x, y) = data();
+        let trend = DVector::from_fn(30, |i, _| 5.0 + 2.0 * (i as f64 * 0.3));
+        let y = y + trend;
+        let gp = || GP::new(RBF::new(vec![1.0], 1.0), 0.1).with_mean(Polynomial::zeros(1, 1));
+
+        let fitted = fit(gp(), x.clone(), &y, &OptimizeOptions::default()).unwrap();
+        let frozen = OptimizeOptions {
+            optimize_mean: false,
+            ..Default::default()
+        };
+        let baseline = fit(gp(), x, &y, &frozen).unwrap();
+
+        // the intercept trades off against the kernel, but the slope is identifiable
+        let slope = fitted.mean_function().get_params()[1];
+        assert!((slope - 2.0).abs() < 0.2, "{}", slope);
+        assert!(fitted.log_marginal_likelihood() > baseline.log_marginal_likelihood());
+    }
+
+    /// The mean function is not changed when it is not optimized
+    #[test]
+    fn test_fixed_mean() {
+        let (x, y) = data();
+        let gp = GP::new(RBF::new(vec![1.0], 1.0), 0.1).with_mean(Linear::new(vec![0.3], 1.0));
+
+        let options = OptimizeOptions {
+            optimize_mean: false,
+            ..Default::default()
+        };
+        let fitted = fit(gp, x, &y, &options).unwrap();
+
+        assert_eq!(fitted.mean_function().get_params(), vec![1.0, 0.3]);
+    }
+
+    /// Per-point noise is kept fixed while the kernel is fit
+    #[test]
+    fn test_fit_heteroscedastic() {
+        let (x, y) = data();
+        let noise = DVector::from_fn(30, |i, _| 0.01 + 0.001 * i as f64);
+        let gp = GP::new_heteroscedastic(RBF::new(vec![0.5], 1.0), noise.clone());
+
+        let fitted = fit(gp, x, &y, &OptimizeOptions::default()).unwrap();
+
+        assert_eq!(fitted.noise(), &Noise::PerPoint(noise));
+    }
+
+    /// Fitting can maximize the leave-one-out score instead of the marginal likelihood
+    #[test]
+    fn test_fit_loo() {
+        let (x, y) = data();
+        let gp = || GP::new(RBF::new(vec![0.2], 1.0), 0.5);
+
+        let initial = gp().compile(x.clone(), &y).unwrap().loo().unwrap().score();
+
+        let options = OptimizeOptions {
+            objective: Objective::LeaveOneOut,
+            ..Default::default()
+        };
+        let fitted = fit(gp(), x, &y, &options).unwrap();
+
+        assert!(fitted.loo().unwrap().score() > initial);
+        assert!(fitted.noise().constant().unwrap() < 0.01);
+    }
+}
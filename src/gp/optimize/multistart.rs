@@ -0,0 +1,340 @@
+use nalgebra::{DMatrix, DVector};
+use rand::Rng;
+use rand_distr::StandardNormal;
+use rayon::prelude::*;
+
+use crate::{
+    gp::{errors::GPCompilationError, CompiledGP, GP},
+    kernels::Kernel,
+    linalg::errors::IncompatibleShapeError,
+    means::{MeanFunction, Zero},
+    parameterized::{Jacobian, Parameterized},
+};
+
+use super::{search, OptimizeOptions, SearchSpace};
+
+/// Distribution of the starting magnitude of a parameter
+#[derive(Debug, Clone)]
+pub enum Prior {
+    /// Log-uniform between the magnitudes `(lower, upper)`, which must satisfy `0 < lower <= upper`
+    LogUniform(f64, f64),
+    /// Log-normal, given the mean and standard deviation of the log magnitude
+    LogNormal(f64, f64),
+}
+
+impl Prior {
+    /// Check that the distribution can be sampled
+    fn is_valid(&self) -> bool {
+        match self {
+            Prior::LogUniform(lower, upper) => *lower > 0.0 && lower <= upper && upper.is_finite(),
+            Prior::LogNormal(mean, std) => mean.is_finite() && std.is_finite(),
+        }
+    }
+
+    /// Draw the log magnitude of a parameter
+    fn sample_log<R: Rng + ?Sized>(&self, rng: &mut R) -> f64 {
+        match self {
+            Prior::LogUniform(lower, upper) => rng.gen_range(lower.ln()..=upper.ln()),
+            Prior::LogNormal(mean, std) => mean + std * rng.sample::<f64, _>(StandardNormal),
+        }
+    }
+}
+
+/// Settings for fitting from multiple starting points
+#[derive(Debug, Clone)]
+pub struct MultiStartOptions {
+    /// Total number of starts, including the GP's current parameters. Must be at least one.
+    pub starts: usize,
+    /// Distributions to draw the kernel parameter magnitudes from, in `get_params` order.
+    ///
+    /// `None` draws log-uniformly within the optimization bounds.
+    pub priors: Option<Vec<Prior>>,
+    /// Distribution to draw the noise variance from.
+    ///
+    /// `None` draws log-uniformly within the noise bounds.
+    pub noise_prior: Option<Prior>,
+    /// Settings for each optimization run
+    pub optimize: OptimizeOptions,
+}
+
+impl Default for MultiStartOptions {
+    fn default() -> Self {
+        MultiStartOptions {
+            starts: 8,
+            priors: None,
+            noise_prior: None,
+            optimize: OptimizeOptions::default(),
+        }
+    }
+}
+
+/// The outcome of fitting from multiple starting points
+#[derive(Debug)]
+pub struct MultiStartResult<K: Kernel, M: MeanFunction = Zero> {
+    /// The fitted GP with the highest objective
+    pub best: CompiledGP<K, M>,
+    /// The final objective of each start, or `None` if the start could not be compiled.
+    ///
+    /// This is the log marginal likelihood unless another objective was chosen in the options.
+    ///
+    /// The first entry is the run from the GP's current parameters.
+    pub likelihoods: Vec<Option<f64>>,
+}
+
+/// Fit a GP from several starting points in parallel, and keep the best result.
+///
+/// The first start is the GP's current parameters. The remaining starts draw the kernel parameters
+/// and noise from the priors, and all start from the GP's current mean function parameters.
+/// Parameters keep the sign they have in the GP, so priors only describe magnitudes.
+///
+/// Returns an error if no start could be compiled, and `InvalidOptionsError` if there are no starts,
+/// a bound is invalid, or a prior cannot be sampled.
+///
+/// # Examples
+/// ```rust
+/// use gprs::{
+///     gp::{optimize::{fit_multistart, MultiStartOptions}, GP},
+///     kernels::RBF,
+/// };
+/// use nalgebra::{DMatrix, DVector};
+/// use rand::{rngs::StdRng, SeedableRng};
+///
+/// let x = DMatrix::from_fn(1, 20, |_, j| j as f64 * 0.5);
+/// let y = DVector::from_fn(20, |i, _| (i as f64 * 0.5).sin());
+///
+/// let gp = GP::new(RBF::new(vec![1.0], 1.0), 0.1);
+/// let mut rng = StdRng::seed_from_u64(0);
+///
+/// let result = fit_multistart(gp, x, &y, &MultiStartOptions::default(), &mut rng).unwrap();
+///
+/// assert_eq!(result.likelihoods.len(), 8);
+/// ```
+pub fn fit_multistart<K, M, R>(
+    gp: GP<K, M>,
+    x: DMatrix<f64>,
+    y: &DVector<f64>,
+    options: &MultiStartOptions,
+    rng: &mut R,
+) -> Result<MultiStartResult<K, M>, GPCompilationError>
+where
+    K: Kernel + Clone + Send + Sync + for<'a> Parameterized<'a> + for<'a> Jacobian<'a>,
+    M: MeanFunction + Clone + Send + Sync + for<'a> Parameterized<'a> + for<'a> Jacobian<'a>,
+    R: Rng + ?Sized,
+{
+    if options.starts == 0 {
+        return Err(GPCompilationError::InvalidOptionsError);
+    }
+
+    let space = SearchSpace::new(&gp, &options.optimize)?;
+    let priors = start_priors(&gp, options, &space)?;
+
+    let initial = space.initial(&gp);
+    let mean_params = &initial[space.mean_offset()..];
+
+    let mut starts = Vec::with_capacity(options.starts);
+    for _ in 1..options.starts {
+        let mut u0: Vec<f64> = priors.iter().map(|p| p.sample_log(rng)).collect();
+        u0.extend_from_slice(mean_params);
+        starts.push(u0);
+    }
+    starts.insert(0, initial);
+
+    let runs: Vec<Result<CompiledGP<K, M>, GPCompilationError>> = starts
+        .into_par_iter()
+        .map(|u0| {
+            let u = search(&gp, &x, y, &space, u0, &options.optimize);
+            space.to_gp(&gp, &u).compile(x.clone(), y)
+        })
+        .collect();
+
+    let likelihoods: Vec<Option<f64>> = runs
+        .iter()
+        .map(|run| {
+            run.as_ref()
+                .ok()
+                .and_then(|gp| options.optimize.objective.score(gp))
+        })
+        .collect();
+
+    let mut best: Option<(CompiledGP<K, M>, f64)> = None;
+    let mut error = None;
+
+    for (run, score) in runs.into_iter().zip(&likelihoods) {
+        match (run, score) {
+            (Ok(gp), Some(score)) => {
+                let better = best.as_ref().is_none_or(|(_, b)| score > b);
+                if better {
+                    best = Some((gp, *score));
+                }
+            }
+            (Ok(_), None) => {}
+            (Err(e), _) => error = Some(e),
+        }
+    }
+
+    match best {
+        Some((best, _)) => Ok(MultiStartResult { best, likelihoods }),
+        None => Err(error.unwrap_or(GPCompilationError::NonPositiveDefiniteError)),
+    }
+}
+
+/// The distribution of every search variable, filling in defaults from the bounds
+fn start_priors<K, M>(
+    gp: &GP<K, M>,
+    options: &MultiStartOptions,
+    space: &SearchSpace,
+) -> Result<Vec<Prior>, GPCompilationError>
+where
+    K: Kernel + for<'a> Parameterized<'a>,
+    M: MeanFunction,
+{
+    let nparams = gp.kernel().get_params().len();
+
+    let mut priors = match (&options.priors, &options.optimize.bounds) {
+        (Some(priors), _) if priors.len() != nparams => {
+            return Err(GPCompilationError::IncompatibleShapeError(
+                IncompatibleShapeError {
+                    shapes: vec![(priors.len(), 1), (nparams, 1)],
+                },
+            ));
+        }
+        (Some(priors), _) => priors.clone(),
+        (None, Some(bounds)) => bounds
+            .iter()
+            .map(|(lo, hi)| Prior::LogUniform(*lo, *hi))
+            .collect(),
+        (None, None) => {
+            let (lo, hi) = options.optimize.default_bounds;
+            vec![Prior::LogUniform(lo, hi); nparams]
+        }
+    };
+
+    if space.optimize_noise {
+        let (lo, hi) = options.optimize.noise_bounds;
+        priors.push(
+            options
+                .noise_prior
+                .clone()
+                .unwrap_or(Prior::LogUniform(lo, hi)),
+        );
+    }
+
+    if !priors.iter().all(Prior::is_valid) {
+        return Err(GPCompilationError::InvalidOptionsError);
+    }
+
+    Ok(priors)
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DMatrix, DVector};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{
+        gp::{errors::GPCompilationError, GP},
+        kernels::RBF,
+    };
+
+    use super::{fit_multistart, MultiStartOptions, Prior};
+
+    fn data() -> (DMatrix<f64>, DVector<f64>) {
+        let x = DMatrix::from_fn(1, 30, |_, j| j as f64 * 0.3);
+        let y = DVector::from_fn(30, |i, _| (i as f64 * 0.3).sin());
+        (x, y)
+    }
+
+    /// The best result is at least as good as every start
+    #[test]
+    fn test_best() {
+        let (x, y) = data();
+        // this start is stuck on a plateau where the kernel matrix is almost the identity
+        let gp = GP::new(RBF::new(vec![0.01], 1.0), 0.5);
+        let mut rng = StdRng::seed_from_u64(1);
+
+        let options = MultiStartOptions {
+            starts: 6,
+            priors: Some(vec![
+                Prior::LogUniform(0.1, 10.0),
+                Prior::LogNormal(0.0, 1.0),
+            ]),
+            ..Default::default()
+        };
+
+        let result = fit_multistart(gp, x, &y, &options, &mut rng).unwrap();
+        let best = result.best.log_marginal_likelihood();
+
+        assert_eq!(result.likelihoods.len(), 6);
+        assert!(result
+            .likelihoods
+            .iter()
+            .flatten()
+            .all(|lml| *lml <= best));
+        assert!(best > result.likelihoods[0].unwrap());
+    }
+
+    /// The same seed gives the same starts
+    #[test]
+    fn test_seeded() {
+        let run = || {
+            let (x, y) = data();
+            let gp = GP::new(RBF::new(vec![1.0], 1.0), 0.5);
+            let mut rng = StdRng::seed_from_u64(7);
+            let options = MultiStartOptions {
+                starts: 3,
+                ..Default::default()
+            };
+            fit_multistart(gp, x, &y, &options, &mut rng)
+                .unwrap()
+                .likelihoods
+        };
+
+        assert_eq!(run(), run());
+    }
+
+    /// Priors with the wrong length are rejected
+    #[test]
+    fn test_priors_shape() {
+        let (x, y) = data();
+        let gp = GP::new(RBF::new(vec![1.0], 1.0), 0.5);
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let options = MultiStartOptions {
+            priors: Some(vec![Prior::LogUniform(0.1, 10.0)]),
+            ..Default::default()
+        };
+
+        assert!(fit_multistart(gp, x, &y, &options, &mut rng).is_err());
+    }
+
+    /// Zero starts and empty prior ranges are rejected rather than panicking
+    #[test]
+    fn test_invalid_options() {
+        let (x, y) = data();
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let invalid = [
+            MultiStartOptions {
+                starts: 0,
+                ..Default::default()
+            },
+            MultiStartOptions {
+                priors: Some(vec![
+                    Prior::LogUniform(10.0, 0.1),
+                    Prior::LogNormal(0.0, 1.0),
+                ]),
+                ..Default::default()
+            },
+            MultiStartOptions {
+                noise_prior: Some(Prior::LogUniform(0.0, 1.0)),
+                ..Default::default()
+            },
+        ];
+
+        for options in invalid {
+            let gp = GP::new(RBF::new(vec![1.0], 1.0), 0.5);
+            let result = fit_multistart(gp, x.clone(), &y, &options, &mut rng);
+            assert_eq!(result.err(), Some(GPCompilationError::InvalidOptionsError));
+        }
+    }
+}
@@ -0,0 +1,63 @@
+//! Posterior computations shared by GPs conditioned on different kinds of observations
+//!
+//! Each takes the cholesky factor `L` of the covariance of the observations, `a = [LL']^-1 y`
+//! for the observation residuals `y`, and the cross-covariance `K*` between the observations
+//! and the test points, with one row per observation.
+
+use std::f64::consts::PI;
+
+use nalgebra::{Cholesky, DMatrix, DVector, Dynamic};
+use rayon::prelude::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
+
+use crate::linalg::{par_solve_lower_triangular_unchecked, par_tr_matmul, par_tr_matmul_diag};
+
+use super::base::GPResult;
+
+/// `log p(y) = -0.5 y'a - sum(log(diag(L))) - 0.5 n log(2 pi)`
+pub(super) fn log_marginal_likelihood(
+    cholesky: &Cholesky<f64, Dynamic>,
+    y: &DVector<f64>,
+    alpha: &DVector<f64>,
+) -> f64 {
+    let n = y.len() as f64;
+    let data_fit = y.dot(alpha);
+    // log|K + S| = 2 * sum(log(diag(L)))
+    let half_log_det = cholesky
+        .l_dirty()
+        .diagonal()
+        .iter()
+        .map(|v| v.ln())
+        .sum::<f64>();
+
+    -0.5 * data_fit - half_log_det - 0.5 * n * (2.0 * PI).ln()
+}
+
+/// `m* + K*'a`, given the prior mean `m*` at the test points
+pub(super) fn mean(
+    prior: DVector<f64>,
+    cross: &DMatrix<f64>,
+    alpha: &DVector<f64>,
+) -> GPResult<DVector<f64>> {
+    let res = par_tr_matmul(cross, alpha)?;
+    Ok(prior + DVector::from_vec(res))
+}
+
+/// `diag(K**) - diag(K*' [LL']^-1 K*)`, given the prior variance `diag(K**)` at the test points
+///
+/// Rounding error can make the difference slightly negative near the observations, so it is clamped at zero.
+pub(super) fn var(
+    mut prior: Vec<f64>,
+    cross: &DMatrix<f64>,
+    cholesky: &Cholesky<f64, Dynamic>,
+) -> GPResult<DVector<f64>> {
+    let fact = par_solve_lower_triangular_unchecked(cholesky.l_dirty(), cross);
+    let explained = par_tr_matmul_diag(&fact, &fact)?;
+
+    prior
+        .as_mut_slice()
+        .into_par_iter()
+        .zip(explained)
+        .for_each(|(l, r)| *l = (*l - r).max(0.0));
+
+    Ok(DVector::from_vec(prior))
+}
@@ -0,0 +1,267 @@
+use std::f64::consts::PI;
+
+use nalgebra::DVector;
+
+use crate::{
+    linalg::errors::IncompatibleShapeError,
+    stats::{normal_cdf, normal_pdf, normal_quantile},
+};
+
+use super::noise::Noise;
+
+/// Independent normal predictions at a set of points
+///
+/// # Examples
+/// ```rust
+/// use gprs::{gp::GP, kernels::RBF};
+/// use nalgebra::{DVector, DMatrix};
+///
+/// let x = DMatrix::from_vec(1, 3, vec![0.0, 1.0, 2.0]);
+/// let y = DVector::from_vec(vec![0.0, 1.0, 0.5]);
+///
+/// let compiled = GP::new(RBF::new(vec![1.0], 1.0), 0.1).compile(x, &y).unwrap();
+///
+/// let x_test = DMatrix::from_vec(1, 2, vec![0.5, 1.5]);
+/// let prediction = compiled.predict(&x_test).unwrap();
+///
+/// let (lower, upper) = prediction.interval(0.95);
+/// assert!(lower[0] < prediction.mean[0] && prediction.mean[0] < upper[0]);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Prediction {
+    /// The predicted mean at each point
+    pub mean: DVector<f64>,
+    /// The predicted variance at each point
+    pub var: DVector<f64>,
+}
+
+impl Prediction {
+    /// The predicted standard deviation at each point
+    pub fn std(&self) -> DVector<f64> {
+        self.var.map(f64::sqrt)
+    }
+
+    /// The value below which each prediction falls with probability `p`
+    ///
+    /// # Panics
+    /// If `p` is not within `[0, 1]`
+    pub fn quantile(&self, p: f64) -> DVector<f64> {
+        assert!(
+            (0.0..=1.0).contains(&p),
+            "quantile {} is not a probability",
+            p
+        );
+
+        let z = normal_quantile(p);
+        self.mean
+            .zip_map(&self.var, |mean, var| mean + z * var.sqrt())
+    }
+
+    /// The lower and upper bounds of the central credible interval containing probability `level`
+    ///
+    /// # Panics
+    /// If `level` is not within `[0, 1]`
+    pub fn interval(&self, level: f64) -> (DVector<f64>, DVector<f64>) {
+        assert!(
+            (0.0..=1.0).contains(&level),
+            "level {} is not a probability",
+            level
+        );
+
+        let tail = 0.5 * (1.0 - level);
+        (self.quantile(tail), self.quantile(1.0 - tail))
+    }
+
+    /// Add observation noise to the predicted variance, so the prediction is of new measurements
+    pub fn with_noise(mut self, noise: &Noise) -> Result<Self, IncompatibleShapeError> {
+        noise.add_to(&mut self.var)?;
+        Ok(self)
+    }
+
+    /// The log density of each of `y` under its prediction
+    ///
+    /// A prediction with zero variance is a point mass, so the density is `+inf` at its mean and `-inf` elsewhere.
+    pub fn log_predictive_density(
+        &self,
+        y: &DVector<f64>,
+    ) -> Result<DVector<f64>, IncompatibleShapeError> {
+        self.check_len(y)?;
+
+        Ok(DVector::from_fn(y.len(), |i, _| {
+            let (error, var) = (y[i] - self.mean[i], self.var[i]);
+            if var == 0.0 {
+                return if error == 0.0 {
+                    f64::INFINITY
+                } else {
+                    f64::NEG_INFINITY
+                };
+            }
+            -0.5 * (error * error / var + (2.0 * PI * var).ln())
+        }))
+    }
+
+    /// The continuous ranked probability score of each of `y` under its prediction
+    ///
+    /// `CRPS = s (z (2 Phi(z) - 1) + 2 phi(z) - 1 / sqrt(pi))`, where `z = (y - f) / s`.
+    ///
+    /// It has the same units as `y`, and unlike the log density, lower is better.
+    /// A prediction with zero variance scores the absolute error.
+    pub fn crps(&self, y: &DVector<f64>) -> Result<DVector<f64>, IncompatibleShapeError> {
+        self.check_len(y)?;
+
+        Ok(DVector::from_fn(y.len(), |i, _| {
+            let std = self.var[i].sqrt();
+            if std == 0.0 {
+                return (y[i] - self.mean[i]).abs();
+            }
+            let z = (y[i] - self.mean[i]) / std;
+            std * (z * (2.0 * normal_cdf(z) - 1.0) + 2.0 * normal_pdf(z) - 1.0 / PI.sqrt())
+        }))
+    }
+
+    fn check_len(&self, y: &DVector<f64>) -> Result<(), IncompatibleShapeError> {
+        if y.len() != self.mean.len() {
+            return Err(IncompatibleShapeError {
+                shapes: vec![self.mean.shape(), y.shape()],
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DMatrix, DVector};
+
+    use crate::{
+        gp::{Noise, GP},
+        kernels::RBF,
+        stats::normal_cdf,
+    };
+
+    use super::Prediction;
+
+    fn prediction() -> Prediction {
+        Prediction {
+            mean: DVector::from_vec(vec![0.0, 1.0, -2.0]),
+            var: DVector::from_vec(vec![1.0, 4.0, 0.25]),
+        }
+    }
+
+    /// Intervals are centered on the mean and contain the requested probability
+    #[test]
+    fn test_interval() {
+        let prediction = prediction();
+        let std = prediction.std();
+        let (lower, upper) = prediction.interval(0.9);
+
+        for i in 0..3 {
+            let mass = normal_cdf((upper[i] - prediction.mean[i]) / std[i])
+                - normal_cdf((lower[i] - prediction.mean[i]) / std[i]);
+
+            assert!((mass - 0.9).abs() < 1e-12);
+            assert!((upper[i] + lower[i] - 2.0 * prediction.mean[i]).abs() < 1e-12);
+        }
+
+        assert_eq!(prediction.quantile(0.5), prediction.mean);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_quantile_out_of_range() {
+        prediction().quantile(1.1);
+    }
+
+    /// The closed form matches numerical integration, and reduces to the absolute error for a point prediction
+    #[test]
+    fn test_crps() {
+        let y = DVector::from_vec(vec![0.5, 1.0, -4.0]);
+        let crps = prediction().crps(&y).unwrap();
+
+        // numerically integrate (F(t) - 1(t >= y))^2
+        for i in 0..3 {
+            let (mean, std) = (prediction().mean[i], prediction().var[i].sqrt());
+            let steps = 200_000;
+            let (lo, hi) = (mean - 12.0 * std, mean + 12.0 * std);
+            let dt = (hi - lo) / steps as f64;
+
+            let integral: f64 = (0..steps)
+                .map(|k| {
+                    let t = lo + (k as f64 + 0.5) * dt;
+                    let step = if t >= y[i] { 1.0 } else { 0.0 };
+                    (normal_cdf((t - mean) / std) - step).powi(2) * dt
+                })
+                .sum();
+
+            assert!(
+                (crps[i] - integral).abs() < 1e-4,
+                "{} != {}",
+                crps[i],
+                integral
+            );
+        }
+
+        let sharp = Prediction {
+            mean: DVector::from_vec(vec![0.0]),
+            var: DVector::from_vec(vec![1e-20]),
+        };
+        let crps = sharp.crps(&DVector::from_vec(vec![3.0])).unwrap();
+        assert!((crps[0] - 3.0).abs() < 1e-9);
+    }
+
+    /// Predictions at the training points of a noiseless GP have finite intervals and scores
+    #[test]
+    fn test_noiseless_training_points() {
+        let x = DMatrix::from_fn(1, 8, |_, j| j as f64 * 0.3);
+        let y = DVector::from_fn(8, |i, _| (i as f64 * 0.3).sin());
+
+        let compiled = GP::new(RBF::new(vec![1.0], 1.0), 0.0)
+            .compile(x.clone(), &y)
+            .unwrap();
+        let prediction = compiled.predict(&x).unwrap();
+        assert!(prediction.var.iter().all(|v| *v >= 0.0));
+
+        let (lower, upper) = prediction.interval(0.95);
+        assert!(lower.iter().chain(upper.iter()).all(|v| v.is_finite()));
+
+        let crps = prediction.crps(&y).unwrap();
+        assert!(crps.iter().all(|v| v.is_finite() && *v < 1e-3), "{}", crps);
+
+        let density = prediction.log_predictive_density(&y).unwrap();
+        assert!(density.iter().all(|v| !v.is_nan()), "{}", density);
+    }
+
+    /// A clamped zero variance is a point mass at the mean
+    #[test]
+    fn test_zero_variance() {
+        let prediction = Prediction {
+            mean: DVector::from_vec(vec![1.0, 1.0]),
+            var: DVector::from_vec(vec![0.0, 0.0]),
+        };
+        let y = DVector::from_vec(vec![1.0, 1.5]);
+
+        let density = prediction.log_predictive_density(&y).unwrap();
+        assert_eq!(density, DVector::from_vec(vec![f64::INFINITY, f64::NEG_INFINITY]));
+        assert_eq!(prediction.crps(&y).unwrap(), DVector::from_vec(vec![0.0, 0.5]));
+    }
+
+    /// Noise widens the prediction and lowers the density of likely values
+    #[test]
+    fn test_with_noise() {
+        let y = prediction().mean;
+        let latent = prediction().log_predictive_density(&y).unwrap();
+        let noisy = prediction()
+            .with_noise(&Noise::Constant(1.0))
+            .unwrap()
+            .log_predictive_density(&y)
+            .unwrap();
+
+        assert!(noisy.iter().zip(latent.iter()).all(|(n, l)| n < l));
+        assert!((latent[0] + 0.5 * (2.0 * std::f64::consts::PI).ln()).abs() < 1e-12);
+
+        assert!(prediction()
+            .with_noise(&Noise::PerPoint(DVector::from_vec(vec![1.0])))
+            .is_err());
+        assert!(prediction().crps(&DVector::from_vec(vec![1.0])).is_err());
+    }
+}
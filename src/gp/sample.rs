@@ -0,0 +1,111 @@
+use nalgebra::{DMatrix, DVector};
+use rand::Rng;
+use rand_distr::StandardNormal;
+
+use crate::linalg::par_matmul;
+
+use super::{
+    errors::GPCompilationError,
+    jitter::{cholesky_with_jitter, JitterPolicy},
+};
+
+/// Draw samples from a multivariate normal distribution, one per column of the result
+///
+/// Only the lower triangle of `cov` is used. Jitter is added if `cov` is not numerically positive definite,
+/// which is common for posteriors at or near the training points. The jitter from `policy`, or the default
+/// `JitterPolicy`, is scaled by the mean of the diagonal of `cov`, since the rounding error grows with the
+/// magnitude of the covariance. Returns the samples and the jitter that was added.
+pub(crate) fn sample_mvn<R>(
+    mean: &DVector<f64>,
+    cov: DMatrix<f64>,
+    n_samples: usize,
+    policy: Option<&JitterPolicy>,
+    rng: &mut R,
+) -> Result<(DMatrix<f64>, f64), GPCompilationError>
+where
+    R: Rng + ?Sized,
+{
+    let n = mean.len();
+
+    let policy = relative_policy(policy.copied().unwrap_or_default(), &cov);
+    let (cholesky, jitter) = cholesky_with_jitter(cov, Some(&policy))?;
+    let z = DMatrix::<f64>::from_fn(n, n_samples, |_, _| rng.sample(StandardNormal));
+
+    let samples =
+        par_matmul(&cholesky.unpack(), &z).map_err(GPCompilationError::IncompatibleShapeError)?;
+    let mut samples = DMatrix::from_vec(n, n_samples, samples);
+
+    samples
+        .column_iter_mut()
+        .for_each(|mut sample| sample += mean);
+
+    Ok((samples, jitter))
+}
+
+/// Scale `policy` by the mean of the diagonal of `cov`
+fn relative_policy(policy: JitterPolicy, cov: &DMatrix<f64>) -> JitterPolicy {
+    let scale = cov.diagonal().mean();
+    if !(scale.is_finite() && scale > 0.0) {
+        return policy;
+    }
+
+    JitterPolicy {
+        initial: policy.initial * scale,
+        max: policy.max * scale,
+        ..policy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DMatrix, DVector};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::gp::JitterPolicy;
+
+    use super::sample_mvn;
+
+    /// The sample moments approach the distribution's moments
+    #[test]
+    fn test_moments() {
+        let mean = DVector::from_vec(vec![1.0, -2.0]);
+        let cov = DMatrix::from_vec(2, 2, vec![2.0, 0.6, 0.6, 0.5]);
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let (samples, jitter) = sample_mvn(&mean, cov.clone(), 20000, None, &mut rng).unwrap();
+        assert_eq!(samples.shape(), (2, 20000));
+        assert_eq!(jitter, 0.0);
+
+        let sample_mean = samples.column_mean();
+        let centered = &samples - &sample_mean * DMatrix::from_element(1, 20000, 1.0);
+        let sample_cov = &centered * centered.transpose() / 19999.0;
+
+        assert!((sample_mean - mean).amax() < 0.05);
+        assert!((sample_cov - cov).amax() < 0.05);
+    }
+
+    /// A singular covariance is factorized with jitter, which is reported
+    #[test]
+    fn test_singular() {
+        let mean = DVector::from_vec(vec![0.0, 0.0]);
+        let cov = DMatrix::from_element(2, 2, 1.0);
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let (samples, jitter) = sample_mvn(&mean, cov.clone(), 10, None, &mut rng).unwrap();
+        assert!(jitter > 0.0);
+        assert!((samples.row(0) - samples.row(1)).amax() < 1e-3);
+
+        // the jitter grows with the magnitude of the covariance
+        let (_, scaled) = sample_mvn(&mean, cov.clone() * 1e12, 10, None, &mut rng).unwrap();
+        assert_eq!(scaled, jitter * 1e12);
+
+        // a given policy is scaled the same way
+        let policy = JitterPolicy {
+            initial: 1e-3,
+            factor: 10.0,
+            max: 1e-3,
+        };
+        let (_, jitter) = sample_mvn(&mean, cov * 1e6, 10, Some(&policy), &mut rng).unwrap();
+        assert_eq!(jitter, 1e3);
+    }
+}
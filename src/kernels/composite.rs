@@ -0,0 +1,823 @@
+use std::ops::{Add, Mul};
+
+use nalgebra::DMatrix;
+use rayon::prelude::*;
+
+use crate::{
+    linalg::errors::IncompatibleShapeError,
+    parameterized::{FromParams, Jacobian, Parameterized},
+};
+
+use super::{
+    constant::Constant,
+    kernel::{Kernel, KernelGradient, KernelHessian, KernelJacobian, TriangleSide},
+    matern::{Matern, Smoothness},
+    periodic::{LocallyPeriodic, Periodic},
+    polynomial::{Linear, Polynomial},
+    rational_quadratic::RationalQuadratic,
+    rbf::RBF,
+    white_noise::WhiteNoise,
+};
+
+/// Combine `rhs` into `lhs` element-wise, in parallel
+fn par_combine<F>(lhs: &mut [f64], rhs: &[f64], op: F)
+where
+    F: Fn(&mut f64, f64) + Sync,
+{
+    lhs.par_iter_mut()
+        .zip(rhs.par_iter())
+        .for_each(|(l, r)| op(l, *r));
+}
+
+/// Multiply each column of `jac` element-wise by `k`
+fn scale_columns(mut jac: DMatrix<f64>, k: &[f64]) -> DMatrix<f64> {
+    let len = k.len().max(1);
+    jac.as_mut_slice()
+        .par_chunks_exact_mut(len)
+        .for_each(|col| col.iter_mut().zip(k).for_each(|(j, k)| *j *= k));
+    jac
+}
+
+/// Place the columns of `rhs` after the columns of `lhs`
+fn concat_columns(lhs: &DMatrix<f64>, rhs: &DMatrix<f64>) -> DMatrix<f64> {
+    let mut jac = DMatrix::zeros(lhs.nrows(), lhs.ncols() + rhs.ncols());
+    jac.columns_mut(0, lhs.ncols()).copy_from(lhs);
+    jac.columns_mut(lhs.ncols(), rhs.ncols()).copy_from(rhs);
+    jac
+}
+
+/// Evaluate the training covariance `K(x, x)` for a jacobian, where the shapes have already been checked by the caller
+///
+/// This is built from the lower triangle like the covariance that gets factorized,
+/// so kernels like `WhiteNoise` do not correlate duplicate points.
+fn call_square<K: Kernel>(kernel: &K, x: &DMatrix<f64>) -> DMatrix<f64> {
+    let mut k = kernel
+        .call_triangular(x, TriangleSide::LOWER)
+        .expect("x must have one row per kernel dimension");
+    k.fill_upper_triangle_with_lower_triangle();
+    k
+}
+
+/// Sum of two kernels
+///
+/// `K = A + B`
+///
+/// The parameters are the parameters of `A` followed by the parameters of `B`.
+///
+/// # Examples
+///
+/// ```rust
+/// use gprs::kernels::{Kernel, Matern12, RBF};
+/// use nalgebra::DMatrix;
+///
+/// let kern = RBF::new(vec![1.0], 1.0) + Matern12::new(vec![0.5], 0.5);
+///
+/// let x = DMatrix::from_vec(1, 2, vec![0.0, 1.0]);
+/// let k = kern.call(&x, &x).unwrap();
+///
+/// assert_eq!(k[(0, 0)], 1.25);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Sum<A: Kernel, B: Kernel> {
+    a: A,
+    b: B,
+}
+
+impl<A: Kernel, B: Kernel> Sum<A, B> {
+    pub fn new(a: A, b: B) -> Self {
+        Sum { a, b }
+    }
+}
+
+impl<A: Kernel, B: Kernel> Kernel for Sum<A, B> {
+    fn call(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let mut value = self.a.call(x, y)?;
+        let other = self.b.call(x, y)?;
+        par_combine(value.as_mut_slice(), other.as_slice(), |l, r| *l += r);
+        Ok(value)
+    }
+
+    fn call_inplace(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+        into: &mut DMatrix<f64>,
+    ) -> Result<(), IncompatibleShapeError> {
+        self.a.call_inplace(x, y, into)?;
+        let other = self.b.call(x, y)?;
+        par_combine(into.as_mut_slice(), other.as_slice(), |l, r| *l += r);
+        Ok(())
+    }
+
+    fn call_triangular(
+        &self,
+        x: &DMatrix<f64>,
+        side: TriangleSide,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let mut value = self.a.call_triangular(x, side)?;
+        let other = self.b.call_triangular(x, side)?;
+        par_combine(value.as_mut_slice(), other.as_slice(), |l, r| *l += r);
+        Ok(value)
+    }
+
+    fn call_diagonal(&self, x: &DMatrix<f64>) -> Result<Vec<f64>, IncompatibleShapeError> {
+        let mut value = self.a.call_diagonal(x)?;
+        let other = self.b.call_diagonal(x)?;
+        par_combine(&mut value, &other, |l, r| *l += r);
+        Ok(value)
+    }
+
+    fn call_disjoint(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let mut value = self.a.call_disjoint(x, y)?;
+        let other = self.b.call_disjoint(x, y)?;
+        par_combine(value.as_mut_slice(), other.as_slice(), |l, r| *l += r);
+        Ok(value)
+    }
+}
+
+impl<'a, A, B> Parameterized<'a> for Sum<A, B>
+where
+    A: Kernel + for<'b> Parameterized<'b>,
+    B: Kernel + for<'b> Parameterized<'b>,
+{
+    fn get_params(&'a self) -> Vec<f64> {
+        let mut params = self.a.get_params();
+        params.extend(self.b.get_params());
+        params
+    }
+
+    fn set_params(&'a mut self, params: &[f64]) {
+        let split = self.a.get_params().len();
+        self.a.set_params(&params[..split]);
+        self.b.set_params(&params[split..]);
+    }
+}
+
+impl<'a, A, B> Jacobian<'a> for Sum<A, B>
+where
+    A: Kernel + for<'b> Jacobian<'b>,
+    B: Kernel + for<'b> Jacobian<'b>,
+{
+    /// `dK/dθ = [dA/dθa, dB/dθb]`
+    fn jacobian(&'a self, x: &DMatrix<f64>) -> DMatrix<f64> {
+        concat_columns(&self.a.jacobian(x), &self.b.jacobian(x))
+    }
+}
+
+impl<A: KernelJacobian, B: KernelJacobian> KernelJacobian for Sum<A, B> {
+    /// `dK/dθ = [dA/dθa, dB/dθb]`
+    fn cross_jacobian(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        Ok(concat_columns(
+            &self.a.cross_jacobian(x, y)?,
+            &self.b.cross_jacobian(x, y)?,
+        ))
+    }
+
+    fn diagonal_jacobian(&self, x: &DMatrix<f64>) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        Ok(concat_columns(
+            &self.a.diagonal_jacobian(x)?,
+            &self.b.diagonal_jacobian(x)?,
+        ))
+    }
+}
+
+impl<A: KernelGradient, B: KernelGradient> KernelGradient for Sum<A, B> {
+    /// `dK/dy = dA/dy + dB/dy`
+    fn gradient(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<Vec<DMatrix<f64>>, IncompatibleShapeError> {
+        let mut grad = self.a.gradient(x, y)?;
+        let other = self.b.gradient(x, y)?;
+        grad.iter_mut()
+            .zip(&other)
+            .for_each(|(l, r)| par_combine(l.as_mut_slice(), r.as_slice(), |l, r| *l += r));
+        Ok(grad)
+    }
+
+    fn diagonal_gradient(&self, x: &DMatrix<f64>) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        Ok(self.a.diagonal_gradient(x)? + self.b.diagonal_gradient(x)?)
+    }
+}
+
+impl<A: KernelHessian, B: KernelHessian> KernelHessian for Sum<A, B> {
+    /// `d2K/(dx dy) = d2A/(dx dy) + d2B/(dx dy)`
+    fn hessian(
+        &self,
+        x: &DMatrix<f64>,
+        x_dims: &[usize],
+        y: &DMatrix<f64>,
+        y_dims: &[usize],
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let mut value = self.a.hessian(x, x_dims, y, y_dims)?;
+        let other = self.b.hessian(x, x_dims, y, y_dims)?;
+        par_combine(value.as_mut_slice(), other.as_slice(), |l, r| *l += r);
+        Ok(value)
+    }
+}
+
+/// Element-wise product of two kernels
+///
+/// `K = A * B`
+///
+/// The parameters are the parameters of `A` followed by the parameters of `B`.
+///
+/// # Examples
+///
+/// ```rust
+/// use gprs::kernels::{Kernel, Matern12, RBF};
+/// use nalgebra::DMatrix;
+///
+/// let kern = RBF::new(vec![1.0], 2.0) * Matern12::new(vec![0.5], 0.5);
+///
+/// let x = DMatrix::from_vec(1, 2, vec![0.0, 1.0]);
+/// let k = kern.call(&x, &x).unwrap();
+///
+/// assert_eq!(k[(0, 0)], 1.0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Product<A: Kernel, B: Kernel> {
+    a: A,
+    b: B,
+}
+
+impl<A: Kernel, B: Kernel> Product<A, B> {
+    pub fn new(a: A, b: B) -> Self {
+        Product { a, b }
+    }
+}
+
+impl<A: Kernel, B: Kernel> Kernel for Product<A, B> {
+    fn call(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let mut value = self.a.call(x, y)?;
+        let other = self.b.call(x, y)?;
+        par_combine(value.as_mut_slice(), other.as_slice(), |l, r| *l *= r);
+        Ok(value)
+    }
+
+    fn call_inplace(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+        into: &mut DMatrix<f64>,
+    ) -> Result<(), IncompatibleShapeError> {
+        self.a.call_inplace(x, y, into)?;
+        let other = self.b.call(x, y)?;
+        par_combine(into.as_mut_slice(), other.as_slice(), |l, r| *l *= r);
+        Ok(())
+    }
+
+    fn call_triangular(
+        &self,
+        x: &DMatrix<f64>,
+        side: TriangleSide,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let mut value = self.a.call_triangular(x, side)?;
+        let other = self.b.call_triangular(x, side)?;
+        par_combine(value.as_mut_slice(), other.as_slice(), |l, r| *l *= r);
+        Ok(value)
+    }
+
+    fn call_diagonal(&self, x: &DMatrix<f64>) -> Result<Vec<f64>, IncompatibleShapeError> {
+        let mut value = self.a.call_diagonal(x)?;
+        let other = self.b.call_diagonal(x)?;
+        par_combine(&mut value, &other, |l, r| *l *= r);
+        Ok(value)
+    }
+
+    fn call_disjoint(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let mut value = self.a.call_disjoint(x, y)?;
+        let other = self.b.call_disjoint(x, y)?;
+        par_combine(value.as_mut_slice(), other.as_slice(), |l, r| *l *= r);
+        Ok(value)
+    }
+}
+
+impl<'a, A, B> Parameterized<'a> for Product<A, B>
+where
+    A: Kernel + for<'b> Parameterized<'b>,
+    B: Kernel + for<'b> Parameterized<'b>,
+{
+    fn get_params(&'a self) -> Vec<f64> {
+        let mut params = self.a.get_params();
+        params.extend(self.b.get_params());
+        params
+    }
+
+    fn set_params(&'a mut self, params: &[f64]) {
+        let split = self.a.get_params().len();
+        self.a.set_params(&params[..split]);
+        self.b.set_params(&params[split..]);
+    }
+}
+
+impl<'a, A, B> Jacobian<'a> for Product<A, B>
+where
+    A: Kernel + for<'b> Jacobian<'b>,
+    B: Kernel + for<'b> Jacobian<'b>,
+{
+    /// `dK/dθ = [dA/dθa * B, A * dB/dθb]`
+    fn jacobian(&'a self, x: &DMatrix<f64>) -> DMatrix<f64> {
+        let ja = scale_columns(self.a.jacobian(x), call_square(&self.b, x).as_slice());
+        let jb = scale_columns(self.b.jacobian(x), call_square(&self.a, x).as_slice());
+
+        concat_columns(&ja, &jb)
+    }
+}
+
+impl<A: KernelJacobian, B: KernelJacobian> KernelJacobian for Product<A, B> {
+    /// `dK/dθ = [dA/dθa * B, A * dB/dθb]`
+    fn cross_jacobian(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let ja = scale_columns(self.a.cross_jacobian(x, y)?, self.b.call(x, y)?.as_slice());
+        let jb = scale_columns(self.b.cross_jacobian(x, y)?, self.a.call(x, y)?.as_slice());
+
+        Ok(concat_columns(&ja, &jb))
+    }
+
+    fn diagonal_jacobian(&self, x: &DMatrix<f64>) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let ja = scale_columns(self.a.diagonal_jacobian(x)?, &self.b.call_diagonal(x)?);
+        let jb = scale_columns(self.b.diagonal_jacobian(x)?, &self.a.call_diagonal(x)?);
+
+        Ok(concat_columns(&ja, &jb))
+    }
+}
+
+impl<A: KernelGradient, B: KernelGradient> KernelGradient for Product<A, B> {
+    /// `dK/dy = dA/dy * B + A * dB/dy`
+    fn gradient(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<Vec<DMatrix<f64>>, IncompatibleShapeError> {
+        let ka = self.a.call(x, y)?;
+        let kb = self.b.call(x, y)?;
+
+        let mut grad = self.a.gradient(x, y)?;
+        let other = self.b.gradient(x, y)?;
+        grad.iter_mut().zip(other).for_each(|(l, r)| {
+            *l = l.component_mul(&kb) + r.component_mul(&ka);
+        });
+        Ok(grad)
+    }
+
+    /// `dk(x, x)/dx = dA(x, x)/dx * B(x, x) + A(x, x) * dB(x, x)/dx`
+    fn diagonal_gradient(&self, x: &DMatrix<f64>) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let ka = self.a.call_diagonal(x)?;
+        let kb = self.b.call_diagonal(x)?;
+
+        let mut grad = self.a.diagonal_gradient(x)?;
+        let other = self.b.diagonal_gradient(x)?;
+        grad.column_iter_mut()
+            .zip(other.column_iter())
+            .zip(ka.iter().zip(&kb))
+            .for_each(|((mut l, r), (a, b))| {
+                l *= *b;
+                l.axpy(*a, &r, 1.0);
+            });
+        Ok(grad)
+    }
+}
+
+impl<A: KernelHessian, B: KernelHessian> KernelHessian for Product<A, B> {
+    /// `d2K/(dx dy) = d2A/(dx dy) * B + dA/dx * dB/dy + dA/dy * dB/dx + A * d2B/(dx dy)`
+    fn hessian(
+        &self,
+        x: &DMatrix<f64>,
+        x_dims: &[usize],
+        y: &DMatrix<f64>,
+        y_dims: &[usize],
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let ha = self.a.hessian(x, x_dims, y, y_dims)?;
+        let hb = self.b.hessian(x, x_dims, y, y_dims)?;
+        let ka = self.a.call(x, y)?;
+        let kb = self.b.call(x, y)?;
+
+        // the kernels are symmetric, so the derivatives w.r.t. x are the transposed gradients of k(y, x)
+        let (ga_y, ga_x) = (self.a.gradient(x, y)?, self.a.gradient(y, x)?);
+        let (gb_y, gb_x) = (self.b.gradient(x, y)?, self.b.gradient(y, x)?);
+
+        Ok(DMatrix::from_fn(x.ncols(), y.ncols(), |i, j| {
+            let (a, b) = (x_dims[i], y_dims[j]);
+            ha[(i, j)] * kb[(i, j)]
+                + ga_x[a][(j, i)] * gb_y[b][(i, j)]
+                + ga_y[b][(i, j)] * gb_x[a][(j, i)]
+                + ka[(i, j)] * hb[(i, j)]
+        }))
+    }
+}
+
+/// A kernel multiplied by a constant
+///
+/// `K = c * A`
+///
+/// The parameters are `c` followed by the parameters of `A`.
+///
+/// # Examples
+///
+/// ```rust
+/// use gprs::kernels::{Kernel, RBF};
+/// use nalgebra::DMatrix;
+///
+/// let kern = RBF::new(vec![1.0], 1.0) * 3.0;
+///
+/// let x = DMatrix::from_vec(1, 2, vec![0.0, 1.0]);
+/// let k = kern.call(&x, &x).unwrap();
+///
+/// assert_eq!(k[(0, 0)], 3.0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Scaled<K: Kernel> {
+    kernel: K,
+    scale: f64,
+}
+
+impl<K: Kernel> Scaled<K> {
+    pub fn new(kernel: K, scale: f64) -> Self {
+        Scaled { kernel, scale }
+    }
+}
+
+impl<K: Kernel> Kernel for Scaled<K> {
+    fn call(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let mut value = self.kernel.call(x, y)?;
+        let scale = self.scale;
+        value.as_mut_slice().par_iter_mut().for_each(|v| *v *= scale);
+        Ok(value)
+    }
+
+    fn call_inplace(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+        into: &mut DMatrix<f64>,
+    ) -> Result<(), IncompatibleShapeError> {
+        self.kernel.call_inplace(x, y, into)?;
+        let scale = self.scale;
+        into.as_mut_slice().par_iter_mut().for_each(|v| *v *= scale);
+        Ok(())
+    }
+
+    fn call_triangular(
+        &self,
+        x: &DMatrix<f64>,
+        side: TriangleSide,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let mut value = self.kernel.call_triangular(x, side)?;
+        let scale = self.scale;
+        value.as_mut_slice().par_iter_mut().for_each(|v| *v *= scale);
+        Ok(value)
+    }
+
+    fn call_diagonal(&self, x: &DMatrix<f64>) -> Result<Vec<f64>, IncompatibleShapeError> {
+        let mut value = self.kernel.call_diagonal(x)?;
+        let scale = self.scale;
+        value.par_iter_mut().for_each(|v| *v *= scale);
+        Ok(value)
+    }
+
+    fn call_disjoint(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let mut value = self.kernel.call_disjoint(x, y)?;
+        let scale = self.scale;
+        value.as_mut_slice().par_iter_mut().for_each(|v| *v *= scale);
+        Ok(value)
+    }
+}
+
+impl<'a, K> Parameterized<'a> for Scaled<K>
+where
+    K: Kernel + for<'b> Parameterized<'b>,
+{
+    fn get_params(&'a self) -> Vec<f64> {
+        let mut params = vec![self.scale];
+        params.extend(self.kernel.get_params());
+        params
+    }
+
+    fn set_params(&'a mut self, params: &[f64]) {
+        self.scale = params[0];
+        self.kernel.set_params(&params[1..]);
+    }
+}
+
+impl<K> FromParams for Scaled<K>
+where
+    K: Kernel + FromParams,
+{
+    fn from_params(params: &[f64]) -> Self {
+        Scaled {
+            kernel: K::from_params(&params[1..]),
+            scale: params[0],
+        }
+    }
+}
+
+impl<'a, K> Jacobian<'a> for Scaled<K>
+where
+    K: Kernel + for<'b> Jacobian<'b>,
+{
+    /// `dK/dθ = [A, c * dA/dθa]`
+    fn jacobian(&'a self, x: &DMatrix<f64>) -> DMatrix<f64> {
+        let k = call_square(&self.kernel, x);
+        concat_columns(
+            &DMatrix::from_column_slice(k.len(), 1, k.as_slice()),
+            &(self.kernel.jacobian(x) * self.scale),
+        )
+    }
+}
+
+impl<K: KernelJacobian> KernelJacobian for Scaled<K> {
+    /// `dK/dθ = [A, c * dA/dθa]`
+    fn cross_jacobian(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let k = self.kernel.call(x, y)?;
+        Ok(concat_columns(
+            &DMatrix::from_column_slice(k.len(), 1, k.as_slice()),
+            &(self.kernel.cross_jacobian(x, y)? * self.scale),
+        ))
+    }
+
+    fn diagonal_jacobian(&self, x: &DMatrix<f64>) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let k = self.kernel.call_diagonal(x)?;
+        Ok(concat_columns(
+            &DMatrix::from_column_slice(k.len(), 1, &k),
+            &(self.kernel.diagonal_jacobian(x)? * self.scale),
+        ))
+    }
+}
+
+impl<K: KernelGradient> KernelGradient for Scaled<K> {
+    /// `dK/dy = c * dA/dy`
+    fn gradient(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<Vec<DMatrix<f64>>, IncompatibleShapeError> {
+        let mut grad = self.kernel.gradient(x, y)?;
+        grad.iter_mut().for_each(|g| *g *= self.scale);
+        Ok(grad)
+    }
+
+    fn diagonal_gradient(&self, x: &DMatrix<f64>) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        Ok(self.kernel.diagonal_gradient(x)? * self.scale)
+    }
+}
+
+impl<K: KernelHessian> KernelHessian for Scaled<K> {
+    /// `d2K/(dx dy) = c * d2A/(dx dy)`
+    fn hessian(
+        &self,
+        x: &DMatrix<f64>,
+        x_dims: &[usize],
+        y: &DMatrix<f64>,
+        y_dims: &[usize],
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        Ok(self.kernel.hessian(x, x_dims, y, y_dims)? * self.scale)
+    }
+}
+
+/// Implement `+` and `*` between kernels, and `*` by a constant, for a kernel type
+macro_rules! impl_kernel_ops {
+    ($t:ty $(where $($gen:ident: $bound:path),+)?) => {
+        impl<$($($gen: $bound,)+)? R: Kernel> Add<R> for $t {
+            type Output = Sum<Self, R>;
+
+            fn add(self, rhs: R) -> Self::Output {
+                Sum::new(self, rhs)
+            }
+        }
+
+        impl<$($($gen: $bound,)+)? R: Kernel> Mul<R> for $t {
+            type Output = Product<Self, R>;
+
+            fn mul(self, rhs: R) -> Self::Output {
+                Product::new(self, rhs)
+            }
+        }
+
+        impl$(<$($gen: $bound),+>)? Mul<f64> for $t {
+            type Output = Scaled<Self>;
+
+            fn mul(self, rhs: f64) -> Self::Output {
+                Scaled::new(self, rhs)
+            }
+        }
+    };
+}
+
+impl_kernel_ops!(Sum<A, B> where A: Kernel, B: Kernel);
+impl_kernel_ops!(Product<A, B> where A: Kernel, B: Kernel);
+impl_kernel_ops!(Scaled<K> where K: Kernel);
+impl_kernel_ops!(RBF);
+impl_kernel_ops!(Matern<S> where S: Smoothness);
+impl_kernel_ops!(WhiteNoise);
+impl_kernel_ops!(Constant);
+impl_kernel_ops!(Periodic);
+impl_kernel_ops!(LocallyPeriodic);
+impl_kernel_ops!(RationalQuadratic);
+impl_kernel_ops!(Linear);
+impl_kernel_ops!(Polynomial);
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::DMatrix;
+
+    use crate::{
+        kernels::{
+            test_util::{check_jacobian, check_triangular_diagonal},
+            Constant, Kernel, KernelGradient, KernelHessian, Matern32, TriangleSide, WhiteNoise,
+            RBF,
+        },
+        parameterized::{Jacobian, Parameterized},
+    };
+
+    use super::{Product, Scaled, Sum};
+
+    fn points() -> DMatrix<f64> {
+        DMatrix::from_vec(1, 3, vec![0.0, 0.7, 2.0])
+    }
+
+    /// Sums, products and scales combine the underlying covariance element-wise
+    #[test]
+    fn test_combinators() {
+        let x = points();
+        let a = RBF::new(vec![1.0], 1.5);
+        let b = Matern32::new(vec![0.5], 0.5);
+
+        let ka = a.call(&x, &x).unwrap();
+        let kb = b.call(&x, &x).unwrap();
+
+        let sum = (a.clone() + b.clone()).call(&x, &x).unwrap();
+        let product = (a.clone() * b).call(&x, &x).unwrap();
+        let scaled = (a * 2.0).call(&x, &x).unwrap();
+
+        assert_eq!(sum, &ka + &kb);
+        assert_eq!(product, ka.component_mul(&kb));
+        assert_eq!(scaled, ka * 2.0);
+    }
+
+    /// The triangular and diagonal paths agree with the full computation
+    #[test]
+    fn test_triangular_diagonal() {
+        let kern = (RBF::new(vec![1.0], 1.5) + Matern32::new(vec![0.5], 0.5)) * 0.5
+            * RBF::new(vec![2.0], 1.0);
+
+        check_triangular_diagonal(&kern, &points());
+    }
+
+    /// Parameters are concatenated in a flat vector
+    #[test]
+    fn test_params() {
+        let mut kern = Scaled::new(
+            Sum::new(RBF::new(vec![1.0], 1.0), Matern32::new(vec![0.5], 2.0)),
+            3.0,
+        );
+
+        assert_eq!(kern.get_params(), vec![3.0, 1.0, -0.5, 4.0, 0.5]);
+
+        kern.set_params(&[1.0, 2.0, -1.0, 3.0, 0.25]);
+        assert_eq!(kern.get_params(), vec![1.0, 2.0, -1.0, 3.0, 0.25]);
+    }
+
+    /// Composite jacobians match finite-difference approximations
+    #[test]
+    fn test_jacobian_finite_difference() {
+        let kern = Scaled::new(
+            Product::new(RBF::new(vec![1.0], 1.0), Matern32::new(vec![0.5], 2.0)),
+            1.5,
+        ) + RBF::new(vec![0.3], 0.5)
+            + Constant::new(0.4) * WhiteNoise::new(0.2);
+
+        check_jacobian(&kern, &points());
+    }
+
+    /// Composite jacobians match the training covariance when the inputs contain duplicates,
+    /// where `WhiteNoise` only fills the diagonal
+    #[test]
+    fn test_jacobian_duplicates() {
+        let x = DMatrix::from_vec(1, 4, vec![0.0, 0.7, 0.0, 2.0]);
+        let kern = Scaled::new(
+            Product::new(RBF::new(vec![1.0], 1.0), WhiteNoise::new(0.2)),
+            1.5,
+        ) + Scaled::new(WhiteNoise::new(0.3), 2.0);
+
+        let training = |k: &Sum<_, _>| {
+            let mut cov = k.call_triangular(&x, TriangleSide::LOWER).unwrap();
+            cov.fill_upper_triangle_with_lower_triangle();
+            cov
+        };
+
+        let jac = kern.jacobian(&x);
+        let params = kern.get_params();
+        let eps = 1e-6;
+        for p in 0..params.len() {
+            let mut up = kern.clone();
+            let mut v = params.clone();
+            v[p] += eps;
+            up.set_params(&v);
+
+            let mut down = kern.clone();
+            let mut v = params.clone();
+            v[p] -= eps;
+            down.set_params(&v);
+
+            let fd = (training(&up) - training(&down)) / (2.0 * eps);
+            for (a, b) in jac.column(p).iter().zip(fd.iter()) {
+                assert!((a - b).abs() < 1e-6, "{} != {}", a, b);
+            }
+        }
+        // the duplicate points are uncorrelated in the training covariance
+        assert!(jac.row(2).iter().all(|j| *j == 0.0));
+    }
+
+    /// Composite input gradients match finite-difference approximations
+    #[test]
+    fn test_gradient_finite_difference() {
+        let x = points();
+        let y = DMatrix::from_vec(1, 2, vec![0.3, 1.5]);
+        let kern = Scaled::new(
+            Product::new(RBF::new(vec![1.0], 1.0), RBF::new(vec![0.5], 2.0)),
+            1.5,
+        ) + Constant::new(0.5);
+
+        let grad = kern.gradient(&x, &y).unwrap();
+        assert_eq!(grad.len(), 1);
+        assert_eq!(grad[0].shape(), (3, 2));
+
+        let eps = 1e-6;
+        let fd = (kern.call(&x, &y.add_scalar(eps)).unwrap()
+            - kern.call(&x, &y.add_scalar(-eps)).unwrap())
+            / (2.0 * eps);
+
+        for (a, b) in grad[0].iter().zip(fd.iter()) {
+            assert!((a - b).abs() < 1e-6, "{} != {}", a, b);
+        }
+        assert_eq!(kern.diagonal_gradient(&x).unwrap(), DMatrix::zeros(1, 3));
+    }
+
+    /// Composite hessians match finite-difference approximations of the input gradient
+    #[test]
+    fn test_hessian_finite_difference() {
+        let x = DMatrix::from_vec(2, 3, vec![0.0, 1.0, 0.5, -0.3, 1.2, 0.4]);
+        let y = DMatrix::from_vec(2, 2, vec![0.2, 0.7, -1.0, 0.1]);
+        let kern = RBF::new(vec![0.8, 1.5], 1.2) * (RBF::new(vec![1.1, 0.6], 0.7) * 2.0)
+            + Constant::new(0.5);
+
+        let eps = 1e-6;
+        for a in 0..2 {
+            for b in 0..2 {
+                let hessian = kern.hessian(&x, &[a; 3], &y, &[b; 2]).unwrap();
+
+                let mut up = x.clone();
+                up.row_mut(a).add_scalar_mut(eps);
+                let mut down = x.clone();
+                down.row_mut(a).add_scalar_mut(-eps);
+
+                let fd = (&kern.gradient(&up, &y).unwrap()[b]
+                    - &kern.gradient(&down, &y).unwrap()[b])
+                    / (2.0 * eps);
+
+                for (h, f) in hessian.iter().zip(fd.iter()) {
+                    assert!((h - f).abs() < 1e-6, "{} != {}", h, f);
+                }
+            }
+        }
+
+        assert!(kern.hessian(&x, &[0, 1, 2], &y, &[0, 0]).is_err());
+    }
+}
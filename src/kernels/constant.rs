@@ -0,0 +1,182 @@
+use nalgebra::DMatrix;
+
+use crate::{
+    linalg::errors::IncompatibleShapeError,
+    parameterized::{FromParams, Jacobian, Parameterized},
+};
+
+use super::{
+    eval::{check_hessian_shapes, check_shapes},
+    kernel::{Kernel, KernelGradient, KernelHessian, KernelJacobian, TriangleSide},
+};
+
+/// Constant kernel
+///
+/// `K = c`
+///
+/// Adding this to another kernel models an unknown constant offset in the outputs,
+/// with prior variance `c`.
+///
+/// The parameters are `[c]`.
+///
+/// # Examples
+///
+/// ```rust
+/// use gprs::kernels::{Constant, Kernel, RBF};
+/// use nalgebra::DMatrix;
+///
+/// let kern = RBF::new(vec![1.0], 1.0) + Constant::new(4.0);
+/// let x = DMatrix::from_vec(1, 2, vec![0.0, 100.0]);
+///
+/// // far away points are still correlated through the offset
+/// assert!((kern.call(&x, &x).unwrap()[(1, 0)] - 4.0).abs() < 1e-12);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Constant {
+    value: f64,
+}
+
+impl Constant {
+    /// Create a new kernel from a constant covariance
+    pub fn new(value: f64) -> Self {
+        Constant { value }
+    }
+}
+
+impl Kernel for Constant {
+    fn call_inplace(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+        into: &mut DMatrix<f64>,
+    ) -> Result<(), IncompatibleShapeError> {
+        check_shapes(x.nrows(), x.shape(), y.shape(), into.shape())?;
+        into.fill(self.value);
+
+        Ok(())
+    }
+
+    fn call(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let mut value = DMatrix::<f64>::zeros(x.ncols(), y.ncols());
+
+        self.call_inplace(x, y, &mut value)?;
+
+        Ok(value)
+    }
+
+    fn call_triangular(
+        &self,
+        x: &DMatrix<f64>,
+        _side: TriangleSide,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        self.call(x, x)
+    }
+
+    fn call_diagonal(&self, x: &DMatrix<f64>) -> Result<Vec<f64>, IncompatibleShapeError> {
+        Ok(vec![self.value; x.ncols()])
+    }
+}
+
+impl KernelGradient for Constant {
+    /// `dk/dy = 0`
+    fn gradient(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<Vec<DMatrix<f64>>, IncompatibleShapeError> {
+        check_shapes(x.nrows(), x.shape(), y.shape(), (x.ncols(), y.ncols()))?;
+        Ok(vec![DMatrix::zeros(x.ncols(), y.ncols()); x.nrows()])
+    }
+
+    fn diagonal_gradient(&self, x: &DMatrix<f64>) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        Ok(DMatrix::zeros(x.nrows(), x.ncols()))
+    }
+}
+
+impl KernelHessian for Constant {
+    /// `d2k/(dx dy) = 0`
+    fn hessian(
+        &self,
+        x: &DMatrix<f64>,
+        x_dims: &[usize],
+        y: &DMatrix<f64>,
+        y_dims: &[usize],
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        check_hessian_shapes(x.nrows(), x.shape(), x_dims, y.shape(), y_dims)?;
+        Ok(DMatrix::zeros(x.ncols(), y.ncols()))
+    }
+}
+
+impl<'a> Parameterized<'a> for Constant {
+    fn get_params(&'a self) -> Vec<f64> {
+        vec![self.value]
+    }
+
+    fn set_params(&'a mut self, params: &[f64]) {
+        self.value = params[0];
+    }
+}
+
+impl FromParams for Constant {
+    fn from_params(params: &[f64]) -> Self {
+        Constant { value: params[0] }
+    }
+}
+
+impl<'a> Jacobian<'a> for Constant {
+    /// `dK/dc = 1`
+    fn jacobian(&'a self, x: &DMatrix<f64>) -> DMatrix<f64> {
+        let n = x.ncols();
+        DMatrix::from_element(n * n, 1, 1.0)
+    }
+}
+
+impl KernelJacobian for Constant {
+    /// `dK/dc = 1`
+    fn cross_jacobian(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        check_shapes(x.nrows(), x.shape(), y.shape(), (x.ncols(), y.ncols()))?;
+        Ok(DMatrix::from_element(x.ncols() * y.ncols(), 1, 1.0))
+    }
+
+    fn diagonal_jacobian(&self, x: &DMatrix<f64>) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        Ok(DMatrix::from_element(x.ncols(), 1, 1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::DMatrix;
+
+    use crate::kernels::Kernel;
+
+    use super::Constant;
+
+    /// Every pair of points has the same covariance
+    #[test]
+    fn test_call() {
+        let kern = Constant::new(1.5);
+        let x = DMatrix::from_vec(2, 2, vec![0.0, 1.0, 0.0, 2.0]);
+        let y = DMatrix::from_vec(2, 3, vec![0.0, 2.0, 0.0, 1.0, 1.0, 1.0]);
+
+        assert_eq!(kern.call(&x, &y).unwrap(), DMatrix::from_element(2, 3, 1.5));
+        assert_eq!(kern.call_diagonal(&x).unwrap(), vec![1.5, 1.5]);
+    }
+
+    /// Points must have the same dimension
+    #[test]
+    fn test_mismatched() {
+        let kern = Constant::new(1.5);
+        let x = DMatrix::from_vec(2, 1, vec![0.0, 1.0]);
+        let y = DMatrix::from_vec(1, 1, vec![0.0]);
+
+        assert!(kern.call(&x, &y).is_err());
+    }
+}
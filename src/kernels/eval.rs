@@ -0,0 +1,293 @@
+//! Parallel evaluation of covariance functions defined on pairs of points
+
+use nalgebra::DMatrix;
+use rayon::prelude::*;
+
+use crate::{
+    indexing::{index_to_2d, slice_indices},
+    linalg::errors::IncompatibleShapeError,
+};
+
+use super::kernel::TriangleSide;
+
+/// Check that `x` and `y` have `dims` rows, and `into` has one row per `x` column and one column per `y` column
+pub(crate) fn check_shapes(
+    dims: usize,
+    x_shape: (usize, usize),
+    y_shape: (usize, usize),
+    into_shape: (usize, usize),
+) -> Result<(), IncompatibleShapeError> {
+    if x_shape.0 != dims || y_shape.0 != dims || into_shape != (x_shape.1, y_shape.1) {
+        return Err(IncompatibleShapeError {
+            shapes: vec![x_shape, y_shape, (1, dims), into_shape],
+        });
+    }
+
+    Ok(())
+}
+
+/// Evaluate `f` between every column of `x` and every column of `y`, in parallel
+///
+/// Shapes must already be checked with `check_shapes`.
+pub(crate) fn par_call_inplace<F>(x: &DMatrix<f64>, y: &DMatrix<f64>, into: &mut DMatrix<f64>, f: F)
+where
+    F: Fn(&[f64], &[f64]) -> f64 + Sync,
+{
+    let dims = x.nrows();
+    let nx = x.ncols();
+    let x_sl = x.as_slice();
+    let y_sl = y.as_slice();
+
+    into.as_mut_slice()
+        .into_par_iter()
+        .enumerate()
+        .for_each(|(index, v)| {
+            // `into` is column-major, so the row (x point) is the minor index
+            let (j, i) = index_to_2d(index, nx);
+            let (xs, xe) = slice_indices(i, dims);
+            let (ys, ye) = slice_indices(j, dims);
+
+            // SAFETY: the indices are valid because the shapes were checked by the caller
+            unsafe {
+                let x_point = x_sl.get_unchecked(xs..xe);
+                let y_point = y_sl.get_unchecked(ys..ye);
+                *v = f(x_point, y_point);
+            }
+        });
+}
+
+/// Evaluate `f` between pairs of columns of `x`, only on one side of the diagonal (inclusive)
+///
+/// Shapes must already be checked with `check_shapes`.
+pub(crate) fn par_call_triangular_inplace<F>(
+    x: &DMatrix<f64>,
+    side: TriangleSide,
+    into: &mut DMatrix<f64>,
+    f: F,
+) where
+    F: Fn(&[f64], &[f64]) -> f64 + Sync,
+{
+    let dims = x.nrows();
+    let n = x.ncols();
+    let x_sl = x.as_slice();
+
+    into.as_mut_slice()
+        .into_par_iter()
+        .enumerate()
+        .map(|(index, v)| {
+            let (i, j) = index_to_2d(index, n);
+            (i, j, v)
+        })
+        .filter(|(i, j, _v)| match side {
+            TriangleSide::LOWER => i <= j,
+            TriangleSide::UPPER => i >= j,
+        })
+        .for_each(|(i, j, v)| {
+            let (xs, xe) = slice_indices(i, dims);
+            let (ys, ye) = slice_indices(j, dims);
+
+            // SAFETY: the indices are valid because the shapes were checked by the caller
+            unsafe {
+                let x_point = x_sl.get_unchecked(xs..xe);
+                let y_point = x_sl.get_unchecked(ys..ye);
+                *v = f(x_point, y_point);
+            }
+        });
+}
+
+/// Evaluate the parameter gradient of a covariance function between every pair of columns of `x`
+///
+/// `f` writes the gradient w.r.t. each of the `nparams` parameters for a pair of points.
+/// Column `p` of the result is `dK/dθp` flattened column-major.
+///
+/// # Panics
+/// If `x` does not have `dims` rows
+pub(crate) fn par_jacobian<F>(x: &DMatrix<f64>, dims: usize, nparams: usize, f: F) -> DMatrix<f64>
+where
+    F: Fn(&[f64], &[f64], &mut [f64]) + Sync,
+{
+    assert_eq!(x.nrows(), dims, "x must have one row per kernel dimension");
+
+    par_cross_jacobian(x, x, nparams, f)
+}
+
+/// Evaluate the parameter gradient of a covariance function between every column of `x` and every column of `y`
+///
+/// Column `p` of the result is `dK(x, y)/dθp` flattened column-major.
+///
+/// Shapes must already be checked with `check_shapes`.
+pub(crate) fn par_cross_jacobian<F>(
+    x: &DMatrix<f64>,
+    y: &DMatrix<f64>,
+    nparams: usize,
+    f: F,
+) -> DMatrix<f64>
+where
+    F: Fn(&[f64], &[f64], &mut [f64]) + Sync,
+{
+    let dims = x.nrows();
+    let nx = x.ncols();
+    let x_sl = x.as_slice();
+    let y_sl = y.as_slice();
+
+    // one column per covariance entry, so each entry is only evaluated once
+    let mut grads = DMatrix::<f64>::zeros(nparams, nx * y.ncols());
+
+    grads
+        .as_mut_slice()
+        .par_chunks_exact_mut(nparams.max(1))
+        .enumerate()
+        .for_each(|(index, grad)| {
+            let (j, i) = index_to_2d(index, nx);
+            let (xs, xe) = slice_indices(i, dims);
+            let (ys, ye) = slice_indices(j, dims);
+            f(&x_sl[xs..xe], &y_sl[ys..ye], grad);
+        });
+
+    grads.transpose()
+}
+
+/// Evaluate the parameter gradient of a covariance function between each column of `x` and itself
+///
+/// Row `i` of the result is the gradient of `k(x_i, x_i)`.
+///
+/// Shapes must already be checked with `check_shapes`.
+pub(crate) fn par_diagonal_jacobian<F>(x: &DMatrix<f64>, nparams: usize, f: F) -> DMatrix<f64>
+where
+    F: Fn(&[f64], &[f64], &mut [f64]) + Sync,
+{
+    let dims = x.nrows();
+    let x_sl = x.as_slice();
+
+    let mut grads = DMatrix::<f64>::zeros(nparams, x.ncols());
+
+    grads
+        .as_mut_slice()
+        .par_chunks_exact_mut(nparams.max(1))
+        .enumerate()
+        .for_each(|(i, grad)| {
+            let (xs, xe) = slice_indices(i, dims);
+            let point = &x_sl[xs..xe];
+            f(point, point, grad);
+        });
+
+    grads.transpose()
+}
+
+/// Evaluate `f` between each column of `x` and itself, in parallel
+///
+/// Shapes must already be checked with `check_shapes`.
+pub(crate) fn par_call_diagonal<F>(x: &DMatrix<f64>, f: F) -> Vec<f64>
+where
+    F: Fn(&[f64], &[f64]) -> f64 + Sync,
+{
+    let dims = x.nrows();
+    let x_sl = x.as_slice();
+
+    (0..x.ncols())
+        .into_par_iter()
+        .map(|i| {
+            let (xs, xe) = slice_indices(i, dims);
+            let point = &x_sl[xs..xe];
+            f(point, point)
+        })
+        .collect()
+}
+
+/// Evaluate the input gradient of a covariance function between every column of `x` and every column of `y`
+///
+/// `f` writes the derivative w.r.t. each dimension of the `y` point for a pair of points.
+/// Entry `d` of the result is the derivative w.r.t. dimension `d`, shaped like `K(x, y)`.
+///
+/// Shapes must already be checked with `check_shapes`.
+pub(crate) fn par_gradient<F>(x: &DMatrix<f64>, y: &DMatrix<f64>, f: F) -> Vec<DMatrix<f64>>
+where
+    F: Fn(&[f64], &[f64], &mut [f64]) + Sync,
+{
+    let dims = x.nrows();
+    let nx = x.ncols();
+    let ny = y.ncols();
+    let x_sl = x.as_slice();
+    let y_sl = y.as_slice();
+
+    // one column per covariance entry, so each entry is only evaluated once
+    let mut grads = DMatrix::<f64>::zeros(dims, nx * ny);
+
+    grads
+        .as_mut_slice()
+        .par_chunks_exact_mut(dims.max(1))
+        .enumerate()
+        .for_each(|(index, grad)| {
+            let (j, i) = index_to_2d(index, nx);
+            let (xs, xe) = slice_indices(i, dims);
+            let (ys, ye) = slice_indices(j, dims);
+            f(&x_sl[xs..xe], &y_sl[ys..ye], grad);
+        });
+
+    grads
+        .row_iter()
+        .map(|row| DMatrix::from_iterator(nx, ny, row.iter().copied()))
+        .collect()
+}
+
+/// Check that `x` and `y` have `dims` rows, and that each of their columns has a derivative dimension below `dims`
+pub(crate) fn check_hessian_shapes(
+    dims: usize,
+    x_shape: (usize, usize),
+    x_dims: &[usize],
+    y_shape: (usize, usize),
+    y_dims: &[usize],
+) -> Result<(), IncompatibleShapeError> {
+    let valid = |n: usize, d: &[usize]| d.len() == n && d.iter().all(|d| *d < dims);
+
+    if x_shape.0 != dims
+        || y_shape.0 != dims
+        || !valid(x_shape.1, x_dims)
+        || !valid(y_shape.1, y_dims)
+    {
+        return Err(IncompatibleShapeError {
+            shapes: vec![
+                x_shape,
+                (x_dims.len(), 1),
+                y_shape,
+                (y_dims.len(), 1),
+                (1, dims),
+            ],
+        });
+    }
+
+    Ok(())
+}
+
+/// Evaluate `f` between every column of `x` and every column of `y`, along with their derivative dimensions
+///
+/// Shapes must already be checked with `check_hessian_shapes`.
+pub(crate) fn par_hessian<F>(
+    x: &DMatrix<f64>,
+    x_dims: &[usize],
+    y: &DMatrix<f64>,
+    y_dims: &[usize],
+    f: F,
+) -> DMatrix<f64>
+where
+    F: Fn(&[f64], usize, &[f64], usize) -> f64 + Sync,
+{
+    let dims = x.nrows();
+    let nx = x.ncols();
+    let x_sl = x.as_slice();
+    let y_sl = y.as_slice();
+
+    let mut into = DMatrix::<f64>::zeros(nx, y.ncols());
+
+    into.as_mut_slice()
+        .into_par_iter()
+        .enumerate()
+        .for_each(|(index, v)| {
+            let (j, i) = index_to_2d(index, nx);
+            let (xs, xe) = slice_indices(i, dims);
+            let (ys, ye) = slice_indices(j, dims);
+            *v = f(&x_sl[xs..xe], x_dims[i], &y_sl[ys..ye], y_dims[j]);
+        });
+
+    into
+}
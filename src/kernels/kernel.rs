@@ -1,7 +1,8 @@
 use nalgebra::DMatrix;
 
-use crate::linalg::errors::IncompatibleShapeError;
+use crate::{linalg::errors::IncompatibleShapeError, parameterized::Jacobian};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TriangleSide {
     UPPER,
     LOWER,
@@ -35,4 +36,70 @@ pub trait Kernel {
 
     /// Compute only the diagonal portion of the covariance matrix
     fn call_diagonal(&self, x: &DMatrix<f64>) -> Result<Vec<f64>, IncompatibleShapeError>;
+
+    /// Compute the covariance between two disjoint sets of training points
+    ///
+    /// This is the off-diagonal block of `call_triangular` on both sets together, and is used when
+    /// adding observations to a compiled GP. It is the same as `call`, unless the kernel treats identical
+    /// points in different observations as uncorrelated, like `WhiteNoise`.
+    fn call_disjoint(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        self.call(x, y)
+    }
+}
+
+/// Derivatives of a covariance function w.r.t. its inputs
+pub trait KernelGradient: Kernel {
+    /// Compute the derivative of the covariance between each column of `x` and each column of `y`,
+    /// w.r.t. the column of `y`
+    ///
+    /// Entry `d` of the result is `dk(x_i, y_j)/dy_jd`, shaped like `call(x, y)`.
+    fn gradient(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<Vec<DMatrix<f64>>, IncompatibleShapeError>;
+
+    /// Compute the derivative of each point's variance `k(x, x)` w.r.t. the point
+    ///
+    /// The result has the same shape as `x`, and is zero for stationary kernels.
+    fn diagonal_gradient(&self, x: &DMatrix<f64>) -> Result<DMatrix<f64>, IncompatibleShapeError>;
+}
+
+/// Derivatives of a covariance function w.r.t. its parameters, between any two sets of points
+///
+/// This extends `Jacobian`, which only covers the training covariance of one set of points.
+pub trait KernelJacobian: Kernel + for<'a> Jacobian<'a> {
+    /// Compute the derivative of `call(x, y)` w.r.t. each parameter
+    ///
+    /// Column `p` of the result is `dK(x, y)/dθp` flattened column-major, where `θ` is `get_params()`.
+    fn cross_jacobian(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError>;
+
+    /// Compute the derivative of `call_diagonal(x)` w.r.t. each parameter
+    ///
+    /// Row `i` of the result is the gradient of `k(x_i, x_i)`.
+    fn diagonal_jacobian(&self, x: &DMatrix<f64>) -> Result<DMatrix<f64>, IncompatibleShapeError>;
+}
+
+/// Second derivatives of a covariance function w.r.t. both of its inputs
+pub trait KernelHessian: KernelGradient {
+    /// Compute `d2k(x_i, y_j)/(dx_ia dy_jb)` between each column of `x` and each column of `y`,
+    /// where `a = x_dims[i]` and `b = y_dims[j]`
+    ///
+    /// This is the covariance between derivatives of the function, taken along one dimension at each point.
+    /// The result is shaped like `call(x, y)`.
+    fn hessian(
+        &self,
+        x: &DMatrix<f64>,
+        x_dims: &[usize],
+        y: &DMatrix<f64>,
+        y_dims: &[usize],
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError>;
 }
@@ -0,0 +1,357 @@
+use std::{fmt::Debug, marker::PhantomData};
+
+use crate::{
+    linalg::errors::IncompatibleShapeError,
+    parameterized::{FromParams, Jacobian, Parameterized},
+};
+
+use super::{
+    eval::{
+        check_shapes, par_call_inplace, par_call_triangular_inplace, par_cross_jacobian,
+        par_diagonal_jacobian, par_jacobian,
+    },
+    kernel::{Kernel, KernelJacobian, TriangleSide},
+};
+use nalgebra::DMatrix;
+
+const SQRT_3: f64 = 1.732_050_807_568_877_2;
+const SQRT_5: f64 = 2.236_067_977_499_79;
+
+/// The smoothness `ν` of a Matérn kernel
+pub trait Smoothness: Debug + Clone + Send + Sync {
+    /// The correlation at scaled distance `r`
+    fn correlation(r: f64) -> f64;
+
+    /// The derivative of the correlation w.r.t. `r`, divided by `r`
+    ///
+    /// This is finite at `r = 0` for `ν > 1/2`. For `ν = 1/2` it is taken as 0 at `r = 0`,
+    /// where it is only ever multiplied by a zero distance.
+    fn scaled_derivative(r: f64) -> f64;
+}
+
+/// `ν = 1/2`, the exponential kernel
+#[derive(Debug, Clone)]
+pub struct Half;
+
+/// `ν = 3/2`, once-differentiable sample paths
+#[derive(Debug, Clone)]
+pub struct ThreeHalves;
+
+/// `ν = 5/2`, twice-differentiable sample paths
+#[derive(Debug, Clone)]
+pub struct FiveHalves;
+
+impl Smoothness for Half {
+    fn correlation(r: f64) -> f64 {
+        (-r).exp()
+    }
+
+    fn scaled_derivative(r: f64) -> f64 {
+        if r == 0.0 {
+            0.0
+        } else {
+            -(-r).exp() / r
+        }
+    }
+}
+
+impl Smoothness for ThreeHalves {
+    fn correlation(r: f64) -> f64 {
+        let s = SQRT_3 * r;
+        (1.0 + s) * (-s).exp()
+    }
+
+    fn scaled_derivative(r: f64) -> f64 {
+        -3.0 * (-SQRT_3 * r).exp()
+    }
+}
+
+impl Smoothness for FiveHalves {
+    fn correlation(r: f64) -> f64 {
+        let s = SQRT_5 * r;
+        (1.0 + s + s * s / 3.0) * (-s).exp()
+    }
+
+    fn scaled_derivative(r: f64) -> f64 {
+        let s = SQRT_5 * r;
+        -5.0 / 3.0 * (1.0 + s) * (-s).exp()
+    }
+}
+
+/// Matérn kernel with smoothness `ν = 1/2`
+pub type Matern12 = Matern<Half>;
+/// Matérn kernel with smoothness `ν = 3/2`
+pub type Matern32 = Matern<ThreeHalves>;
+/// Matérn kernel with smoothness `ν = 5/2`
+pub type Matern52 = Matern<FiveHalves>;
+
+/// Matérn kernel family
+///
+/// `K = s^2 * k(r)`, where `r = sqrt(sum(((x - x') / l)^2))`
+///
+/// - `ν = 1/2`: `k(r) = exp(-r)`
+/// - `ν = 3/2`: `k(r) = (1 + sqrt(3) r) exp(-sqrt(3) r)`
+/// - `ν = 5/2`: `k(r) = (1 + sqrt(5) r + 5 r^2 / 3) exp(-sqrt(5) r)`
+///
+/// where `l` is the length scale of each dimension.
+///
+/// The parameters are `[s^2, l...]`.
+///
+/// # Examples
+///
+/// ```rust
+/// use gprs::kernels::{Kernel, Matern32};
+/// use nalgebra::DMatrix;
+///
+/// // create a 2-d kernel with a length scale per dimension
+/// let kern = Matern32::new(vec![1.0, 2.0], 1.0);
+///
+/// let x = DMatrix::from_vec(2, 3, vec![
+///     1.8, 5.5,
+///     1.5, 4.5,
+///     2.3, 4.6
+/// ]);
+///
+/// let k = kern.call(&x, &x).unwrap();
+/// assert_eq!(k.shape(), (3, 3));
+/// assert_eq!(k[(0, 0)], 1.0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Matern<S: Smoothness> {
+    length_scale: Vec<f64>,
+    amplitude: f64,
+    smoothness: PhantomData<S>,
+}
+
+impl<S: Smoothness> Matern<S> {
+    /// Create a new kernel from a length scale per dimension and a standard deviation
+    pub fn new<I>(length_scale: I, sigma: f64) -> Self
+    where
+        I: IntoIterator<Item = f64>,
+    {
+        Matern {
+            length_scale: length_scale.into_iter().collect(),
+            amplitude: sigma * sigma,
+            smoothness: PhantomData,
+        }
+    }
+
+    /// Compute the scaled distance between 2 points
+    fn distance(&self, x_point: &[f64], y_point: &[f64]) -> f64 {
+        self.length_scale
+            .iter()
+            .zip(x_point)
+            .zip(y_point)
+            .map(|((l, x), y)| {
+                let diff = (x - y) / l;
+                diff * diff
+            })
+            .sum::<f64>()
+            .sqrt()
+    }
+
+    /// Compute the covariance between 2 points
+    fn call_point(&self, x_point: &[f64], y_point: &[f64]) -> f64 {
+        self.amplitude * S::correlation(self.distance(x_point, y_point))
+    }
+
+    /// Compute the gradient of the covariance between 2 points w.r.t. each parameter
+    fn jacobian_point(&self, x_point: &[f64], y_point: &[f64], grad: &mut [f64]) {
+        let r = self.distance(x_point, y_point);
+        grad[0] = S::correlation(r);
+
+        let dk = self.amplitude * S::scaled_derivative(r);
+        grad[1..]
+            .iter_mut()
+            .zip(&self.length_scale)
+            .zip(x_point.iter().zip(y_point))
+            .for_each(|((g, l), (x, y))| {
+                let diff = x - y;
+                *g = -dk * diff * diff / (l * l * l);
+            });
+    }
+}
+
+impl<S: Smoothness> Kernel for Matern<S> {
+    fn call_inplace(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+        into: &mut DMatrix<f64>,
+    ) -> Result<(), IncompatibleShapeError> {
+        check_shapes(self.length_scale.len(), x.shape(), y.shape(), into.shape())?;
+        par_call_inplace(x, y, into, |x_point, y_point| self.call_point(x_point, y_point));
+
+        Ok(())
+    }
+
+    fn call(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let mut value = DMatrix::<f64>::zeros(x.ncols(), y.ncols());
+
+        self.call_inplace(x, y, &mut value)?;
+
+        Ok(value)
+    }
+
+    fn call_triangular(
+        &self,
+        x: &DMatrix<f64>,
+        side: TriangleSide,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let x_shape = x.shape();
+        let mut value = DMatrix::<f64>::zeros(x_shape.1, x_shape.1);
+
+        check_shapes(self.length_scale.len(), x_shape, x_shape, value.shape())?;
+        par_call_triangular_inplace(x, side, &mut value, |x_point, y_point| {
+            self.call_point(x_point, y_point)
+        });
+
+        Ok(value)
+    }
+
+    fn call_diagonal(&self, x: &DMatrix<f64>) -> Result<Vec<f64>, IncompatibleShapeError> {
+        Ok(vec![self.amplitude; x.shape().1])
+    }
+}
+
+impl<'a, S: Smoothness> Parameterized<'a> for Matern<S> {
+    fn get_params(&'a self) -> Vec<f64> {
+        let mut params = Vec::with_capacity(1 + self.length_scale.len());
+        params.push(self.amplitude);
+        params.extend(self.length_scale.iter());
+        params
+    }
+
+    fn set_params(&'a mut self, params: &[f64]) {
+        self.amplitude = params[0];
+        params[1..].clone_into(&mut self.length_scale);
+    }
+}
+
+impl<S: Smoothness> FromParams for Matern<S> {
+    fn from_params(params: &[f64]) -> Self {
+        Matern {
+            length_scale: params[1..].to_vec(),
+            amplitude: params[0],
+            smoothness: PhantomData,
+        }
+    }
+}
+
+impl<'a, S: Smoothness> Jacobian<'a> for Matern<S> {
+    /// Compute the derivative of the covariance of `x` with itself w.r.t. each parameter
+    ///
+    /// Column `p` of the result is `dK/dθp` flattened column-major, where `θ` is `get_params()`.
+    ///
+    /// `dK/dl = -s^2 * (dk/dr / r) * (x - x')^2 / l^3`
+    ///
+    /// # Panics
+    /// If `x` does not have one row per length scale
+    fn jacobian(&'a self, x: &DMatrix<f64>) -> DMatrix<f64> {
+        let dims = self.length_scale.len();
+
+        par_jacobian(x, dims, 1 + dims, |x_point, y_point, grad| {
+            self.jacobian_point(x_point, y_point, grad)
+        })
+    }
+}
+
+impl<S: Smoothness> KernelJacobian for Matern<S> {
+    fn cross_jacobian(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let dims = self.length_scale.len();
+        check_shapes(dims, x.shape(), y.shape(), (x.ncols(), y.ncols()))?;
+
+        Ok(par_cross_jacobian(x, y, 1 + dims, |x_point, y_point, grad| {
+            self.jacobian_point(x_point, y_point, grad)
+        }))
+    }
+
+    fn diagonal_jacobian(&self, x: &DMatrix<f64>) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let dims = self.length_scale.len();
+        check_shapes(dims, x.shape(), x.shape(), (x.ncols(), x.ncols()))?;
+
+        Ok(par_diagonal_jacobian(x, 1 + dims, |x_point, y_point, grad| {
+            self.jacobian_point(x_point, y_point, grad)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::DMatrix;
+
+    use crate::kernels::{
+        test_util::{check_jacobian, check_triangular_diagonal},
+        Kernel,
+    };
+
+    use super::{Matern12, Matern32, Matern52};
+
+    fn points() -> DMatrix<f64> {
+        DMatrix::from_vec(2, 3, vec![0.0, 1.0, 0.5, -0.3, 1.2, 0.4])
+    }
+
+    /// Each kernel matches its closed form in 1-d
+    #[test]
+    fn test_1d_correctness() {
+        let x = DMatrix::from_vec(1, 1, vec![1.0]);
+        let y = DMatrix::from_vec(1, 1, vec![2.0]);
+        // r = 1 / 0.5 = 2
+        let r: f64 = 2.0;
+
+        let k = Matern12::new(vec![0.5], 2.0).call(&x, &y).unwrap();
+        assert!((k[0] - 4.0 * (-r).exp()).abs() < 1e-12);
+
+        let k = Matern32::new(vec![0.5], 2.0).call(&x, &y).unwrap();
+        let s = 3.0_f64.sqrt() * r;
+        assert!((k[0] - 4.0 * (1.0 + s) * (-s).exp()).abs() < 1e-12);
+
+        let k = Matern52::new(vec![0.5], 2.0).call(&x, &y).unwrap();
+        let s = 5.0_f64.sqrt() * r;
+        assert!((k[0] - 4.0 * (1.0 + s + s * s / 3.0) * (-s).exp()).abs() < 1e-12);
+    }
+
+    /// Length scales apply per dimension
+    #[test]
+    fn test_2d_anisotropic() {
+        let kern = Matern12::new(vec![1.0, 4.0], 1.0);
+
+        let x = DMatrix::from_vec(2, 1, vec![0.0, 0.0]);
+        let y = DMatrix::from_vec(2, 1, vec![3.0, 16.0]);
+        let k = kern.call(&x, &y).unwrap();
+
+        // r = sqrt(3^2 + 4^2) = 5
+        assert!((k[0] - (-5.0_f64).exp()).abs() < 1e-12);
+    }
+
+    /// The triangular and diagonal paths agree with the full computation
+    #[test]
+    fn test_triangular_diagonal() {
+        check_triangular_diagonal(&Matern52::new(vec![0.7, 1.3], 1.5), &points());
+    }
+
+    /// Passing invalid data will return an error
+    #[test]
+    fn test_mismatched() {
+        let kern = Matern32::new(vec![1.0], 1.0);
+        let x = DMatrix::from_vec(2, 1, vec![1.0, 1.0]);
+
+        assert!(kern.call(&x, &x).is_err());
+    }
+
+    /// The jacobians match finite-difference approximations
+    #[test]
+    fn test_jacobian_finite_difference() {
+        check_jacobian(&Matern12::new(vec![0.8, 1.5], 1.2), &points());
+        check_jacobian(&Matern32::new(vec![0.8, 1.5], 1.2), &points());
+        check_jacobian(&Matern52::new(vec![0.8, 1.5], 1.2), &points());
+    }
+}
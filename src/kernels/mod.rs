@@ -1,5 +1,22 @@
+mod composite;
+mod constant;
+mod eval;
 mod kernel;
+mod matern;
+mod periodic;
+mod polynomial;
+mod rational_quadratic;
 mod rbf;
+#[cfg(test)]
+mod test_util;
+mod white_noise;
 
+pub use composite::*;
+pub use constant::*;
 pub use kernel::*;
+pub use matern::*;
+pub use periodic::*;
+pub use polynomial::*;
+pub use rational_quadratic::*;
 pub use rbf::*;
+pub use white_noise::*;
@@ -0,0 +1,426 @@
+use std::f64::consts::PI;
+
+use nalgebra::DMatrix;
+
+use crate::{
+    linalg::errors::IncompatibleShapeError,
+    parameterized::{FromParams, Jacobian, Parameterized},
+};
+
+use super::{
+    eval::{
+        check_shapes, par_call_inplace, par_call_triangular_inplace, par_cross_jacobian,
+        par_diagonal_jacobian, par_jacobian,
+    },
+    kernel::{Kernel, KernelJacobian, TriangleSide},
+};
+
+/// Compute the euclidean distance between 2 points
+fn distance(x_point: &[f64], y_point: &[f64]) -> f64 {
+    x_point
+        .iter()
+        .zip(y_point)
+        .map(|(x, y)| {
+            let diff = x - y;
+            diff * diff
+        })
+        .sum::<f64>()
+        .sqrt()
+}
+
+/// Periodic (Exp-Sine-Squared) kernel
+///
+/// `K = s^2 * exp(-2 * sin^2(pi * ||x - x'|| / p) / l^2)`
+///
+/// where `||x - x'||` is the euclidean distance between vectors x and x',
+/// `p` is the period and `l` is the length scale
+///
+/// The parameters are `[s^2, p, l]`.
+///
+/// # Examples
+///
+/// ```rust
+/// use gprs::kernels::{Kernel, Periodic};
+/// use nalgebra::DMatrix;
+///
+/// let kern = Periodic::new(2.0, 1.0, 1.0);
+///
+/// let x = DMatrix::from_vec(1, 1, vec![0.0]);
+/// let y = DMatrix::from_vec(1, 1, vec![6.0]);
+///
+/// // points a whole number of periods apart are perfectly correlated
+/// assert!((kern.call(&x, &y).unwrap()[0] - 1.0).abs() < 1e-12);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Periodic {
+    amplitude: f64,
+    period: f64,
+    length_scale: f64,
+}
+
+impl Periodic {
+    /// Create a new kernel from a period, length scale and standard deviation
+    pub fn new(period: f64, length_scale: f64, sigma: f64) -> Self {
+        Periodic {
+            amplitude: sigma * sigma,
+            period,
+            length_scale,
+        }
+    }
+
+    /// Compute the covariance between 2 points, without the amplitude
+    fn call_point_unscaled(&self, r: f64) -> f64 {
+        let s = (PI * r / self.period).sin();
+        (-2.0 * s * s / (self.length_scale * self.length_scale)).exp()
+    }
+
+    /// Compute the gradient of the unscaled covariance w.r.t. the period and length scale
+    fn gradient_unscaled(&self, r: f64, unscaled: f64) -> (f64, f64) {
+        let l2 = self.length_scale * self.length_scale;
+        let u = PI * r / self.period;
+        let (s, c) = u.sin_cos();
+
+        let d_period = unscaled * 4.0 * s * c * u / (l2 * self.period);
+        let d_length = unscaled * 4.0 * s * s / (l2 * self.length_scale);
+        (d_period, d_length)
+    }
+
+    /// Compute the gradient of the covariance between 2 points w.r.t. each parameter
+    fn jacobian_point(&self, x_point: &[f64], y_point: &[f64], grad: &mut [f64]) {
+        let r = distance(x_point, y_point);
+        let unscaled = self.call_point_unscaled(r);
+        let (d_period, d_length) = self.gradient_unscaled(r, unscaled);
+
+        grad[0] = unscaled;
+        grad[1] = self.amplitude * d_period;
+        grad[2] = self.amplitude * d_length;
+    }
+}
+
+impl Kernel for Periodic {
+    fn call_inplace(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+        into: &mut DMatrix<f64>,
+    ) -> Result<(), IncompatibleShapeError> {
+        check_shapes(x.nrows(), x.shape(), y.shape(), into.shape())?;
+        par_call_inplace(x, y, into, |x_point, y_point| {
+            self.amplitude * self.call_point_unscaled(distance(x_point, y_point))
+        });
+
+        Ok(())
+    }
+
+    fn call(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let mut value = DMatrix::<f64>::zeros(x.ncols(), y.ncols());
+
+        self.call_inplace(x, y, &mut value)?;
+
+        Ok(value)
+    }
+
+    fn call_triangular(
+        &self,
+        x: &DMatrix<f64>,
+        side: TriangleSide,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let x_shape = x.shape();
+        let mut value = DMatrix::<f64>::zeros(x_shape.1, x_shape.1);
+
+        check_shapes(x_shape.0, x_shape, x_shape, value.shape())?;
+        par_call_triangular_inplace(x, side, &mut value, |x_point, y_point| {
+            self.amplitude * self.call_point_unscaled(distance(x_point, y_point))
+        });
+
+        Ok(value)
+    }
+
+    fn call_diagonal(&self, x: &DMatrix<f64>) -> Result<Vec<f64>, IncompatibleShapeError> {
+        Ok(vec![self.amplitude; x.shape().1])
+    }
+}
+
+impl<'a> Parameterized<'a> for Periodic {
+    fn get_params(&'a self) -> Vec<f64> {
+        vec![self.amplitude, self.period, self.length_scale]
+    }
+
+    fn set_params(&'a mut self, params: &[f64]) {
+        self.amplitude = params[0];
+        self.period = params[1];
+        self.length_scale = params[2];
+    }
+}
+
+impl FromParams for Periodic {
+    fn from_params(params: &[f64]) -> Self {
+        Periodic {
+            amplitude: params[0],
+            period: params[1],
+            length_scale: params[2],
+        }
+    }
+}
+
+impl<'a> Jacobian<'a> for Periodic {
+    /// Compute the derivative of the covariance of `x` with itself w.r.t. each parameter
+    ///
+    /// Column `p` of the result is `dK/dθp` flattened column-major, where `θ` is `get_params()`.
+    fn jacobian(&'a self, x: &DMatrix<f64>) -> DMatrix<f64> {
+        par_jacobian(x, x.nrows(), 3, |x_point, y_point, grad| {
+            self.jacobian_point(x_point, y_point, grad)
+        })
+    }
+}
+
+impl KernelJacobian for Periodic {
+    fn cross_jacobian(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        check_shapes(x.nrows(), x.shape(), y.shape(), (x.ncols(), y.ncols()))?;
+
+        Ok(par_cross_jacobian(x, y, 3, |x_point, y_point, grad| {
+            self.jacobian_point(x_point, y_point, grad)
+        }))
+    }
+
+    fn diagonal_jacobian(&self, x: &DMatrix<f64>) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        check_shapes(x.nrows(), x.shape(), x.shape(), (x.ncols(), x.ncols()))?;
+
+        Ok(par_diagonal_jacobian(x, 3, |x_point, y_point, grad| {
+            self.jacobian_point(x_point, y_point, grad)
+        }))
+    }
+}
+
+/// Locally periodic kernel, the product of a periodic and an RBF kernel
+///
+/// `K = s^2 * exp(-2 * sin^2(pi * ||x - x'|| / p) / l^2) * exp(-||x - x'||^2 / (2 * d^2))`
+///
+/// where `p` is the period, `l` is the periodic length scale, and `d` is the length scale
+/// over which the periodic pattern decays
+///
+/// The parameters are `[s^2, p, l, d]`.
+///
+/// # Examples
+///
+/// ```rust
+/// use gprs::kernels::{Kernel, LocallyPeriodic};
+/// use nalgebra::DMatrix;
+///
+/// let kern = LocallyPeriodic::new(2.0, 1.0, 10.0, 1.0);
+///
+/// let x = DMatrix::from_vec(1, 1, vec![0.0]);
+/// let y = DMatrix::from_vec(1, 2, vec![2.0, 20.0]);
+///
+/// let k = kern.call(&x, &y).unwrap();
+///
+/// // correlation between matching phases decays with distance
+/// assert!(k[(0, 0)] > k[(0, 1)]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct LocallyPeriodic {
+    periodic: Periodic,
+    decay: f64,
+}
+
+impl LocallyPeriodic {
+    /// Create a new kernel from a period, periodic length scale, decay length scale and standard deviation
+    pub fn new(period: f64, length_scale: f64, decay: f64, sigma: f64) -> Self {
+        LocallyPeriodic {
+            periodic: Periodic::new(period, length_scale, sigma),
+            decay,
+        }
+    }
+
+    /// Compute the covariance between 2 points, without the amplitude
+    fn call_point_unscaled(&self, r: f64) -> f64 {
+        self.periodic.call_point_unscaled(r) * self.decay_unscaled(r)
+    }
+
+    /// Compute the RBF decay term
+    fn decay_unscaled(&self, r: f64) -> f64 {
+        (-0.5 * r * r / (self.decay * self.decay)).exp()
+    }
+
+    /// Compute the gradient of the covariance between 2 points w.r.t. each parameter
+    fn jacobian_point(&self, x_point: &[f64], y_point: &[f64], grad: &mut [f64]) {
+        let amplitude = self.periodic.amplitude;
+        let r = distance(x_point, y_point);
+        let periodic = self.periodic.call_point_unscaled(r);
+        let decay = self.decay_unscaled(r);
+        let (d_period, d_length) = self.periodic.gradient_unscaled(r, periodic);
+
+        grad[0] = periodic * decay;
+        grad[1] = amplitude * decay * d_period;
+        grad[2] = amplitude * decay * d_length;
+        grad[3] = amplitude * periodic * decay * r * r / (self.decay * self.decay * self.decay);
+    }
+}
+
+impl Kernel for LocallyPeriodic {
+    fn call_inplace(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+        into: &mut DMatrix<f64>,
+    ) -> Result<(), IncompatibleShapeError> {
+        check_shapes(x.nrows(), x.shape(), y.shape(), into.shape())?;
+        par_call_inplace(x, y, into, |x_point, y_point| {
+            self.periodic.amplitude * self.call_point_unscaled(distance(x_point, y_point))
+        });
+
+        Ok(())
+    }
+
+    fn call(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let mut value = DMatrix::<f64>::zeros(x.ncols(), y.ncols());
+
+        self.call_inplace(x, y, &mut value)?;
+
+        Ok(value)
+    }
+
+    fn call_triangular(
+        &self,
+        x: &DMatrix<f64>,
+        side: TriangleSide,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let x_shape = x.shape();
+        let mut value = DMatrix::<f64>::zeros(x_shape.1, x_shape.1);
+
+        check_shapes(x_shape.0, x_shape, x_shape, value.shape())?;
+        par_call_triangular_inplace(x, side, &mut value, |x_point, y_point| {
+            self.periodic.amplitude * self.call_point_unscaled(distance(x_point, y_point))
+        });
+
+        Ok(value)
+    }
+
+    fn call_diagonal(&self, x: &DMatrix<f64>) -> Result<Vec<f64>, IncompatibleShapeError> {
+        Ok(vec![self.periodic.amplitude; x.shape().1])
+    }
+}
+
+impl<'a> Parameterized<'a> for LocallyPeriodic {
+    fn get_params(&'a self) -> Vec<f64> {
+        let mut params = self.periodic.get_params();
+        params.push(self.decay);
+        params
+    }
+
+    fn set_params(&'a mut self, params: &[f64]) {
+        self.periodic.set_params(&params[..3]);
+        self.decay = params[3];
+    }
+}
+
+impl FromParams for LocallyPeriodic {
+    fn from_params(params: &[f64]) -> Self {
+        LocallyPeriodic {
+            periodic: Periodic::from_params(&params[..3]),
+            decay: params[3],
+        }
+    }
+}
+
+impl<'a> Jacobian<'a> for LocallyPeriodic {
+    /// Compute the derivative of the covariance of `x` with itself w.r.t. each parameter
+    ///
+    /// Column `p` of the result is `dK/dθp` flattened column-major, where `θ` is `get_params()`.
+    fn jacobian(&'a self, x: &DMatrix<f64>) -> DMatrix<f64> {
+        par_jacobian(x, x.nrows(), 4, |x_point, y_point, grad| {
+            self.jacobian_point(x_point, y_point, grad)
+        })
+    }
+}
+
+impl KernelJacobian for LocallyPeriodic {
+    fn cross_jacobian(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        check_shapes(x.nrows(), x.shape(), y.shape(), (x.ncols(), y.ncols()))?;
+
+        Ok(par_cross_jacobian(x, y, 4, |x_point, y_point, grad| {
+            self.jacobian_point(x_point, y_point, grad)
+        }))
+    }
+
+    fn diagonal_jacobian(&self, x: &DMatrix<f64>) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        check_shapes(x.nrows(), x.shape(), x.shape(), (x.ncols(), x.ncols()))?;
+
+        Ok(par_diagonal_jacobian(x, 4, |x_point, y_point, grad| {
+            self.jacobian_point(x_point, y_point, grad)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use nalgebra::DMatrix;
+
+    use crate::kernels::{
+        test_util::{check_jacobian, check_triangular_diagonal},
+        Kernel,
+    };
+
+    use super::{LocallyPeriodic, Periodic};
+
+    fn points() -> DMatrix<f64> {
+        DMatrix::from_vec(2, 3, vec![0.0, 1.0, 0.5, -0.3, 1.2, 0.4])
+    }
+
+    /// The periodic kernel matches its closed form
+    #[test]
+    fn test_periodic_correctness() {
+        let kern = Periodic::new(3.0, 0.5, 2.0);
+        let x = DMatrix::from_vec(1, 1, vec![1.0]);
+        let y = DMatrix::from_vec(1, 1, vec![2.0]);
+
+        let s = (PI / 3.0).sin();
+        let expected = 4.0 * (-2.0 * s * s / 0.25).exp();
+
+        assert!((kern.call(&x, &y).unwrap()[0] - expected).abs() < 1e-12);
+    }
+
+    /// The locally periodic kernel is the product of a periodic and RBF kernel
+    #[test]
+    fn test_locally_periodic_correctness() {
+        let kern = LocallyPeriodic::new(3.0, 0.5, 2.0, 2.0);
+        let x = DMatrix::from_vec(1, 1, vec![1.0]);
+        let y = DMatrix::from_vec(1, 1, vec![2.0]);
+
+        let s = (PI / 3.0).sin();
+        let expected = 4.0 * (-2.0 * s * s / 0.25).exp() * (-0.5_f64 / 4.0).exp();
+
+        assert!((kern.call(&x, &y).unwrap()[0] - expected).abs() < 1e-12);
+    }
+
+    /// The triangular and diagonal paths agree with the full computation
+    #[test]
+    fn test_triangular_diagonal() {
+        check_triangular_diagonal(&LocallyPeriodic::new(1.3, 0.8, 2.0, 1.5), &points());
+    }
+
+    /// The jacobians match finite-difference approximations
+    #[test]
+    fn test_jacobian_finite_difference() {
+        check_jacobian(&Periodic::new(1.3, 0.8, 1.5), &points());
+        check_jacobian(&LocallyPeriodic::new(1.3, 0.8, 2.0, 1.5), &points());
+    }
+}
@@ -0,0 +1,421 @@
+use nalgebra::DMatrix;
+
+use crate::{
+    linalg::errors::IncompatibleShapeError,
+    parameterized::{FromParams, Jacobian, Parameterized},
+};
+
+use super::{
+    eval::{
+        check_shapes, par_call_diagonal, par_call_inplace, par_call_triangular_inplace,
+        par_cross_jacobian, par_diagonal_jacobian, par_jacobian,
+    },
+    kernel::{Kernel, KernelJacobian, TriangleSide},
+};
+
+/// Compute the dot product between 2 points
+fn dot(x_point: &[f64], y_point: &[f64]) -> f64 {
+    x_point.iter().zip(y_point).map(|(x, y)| x * y).sum()
+}
+
+/// Linear (dot product) kernel
+///
+/// `K = c + sum(v * x * x')`
+///
+/// where `v` is the variance of the slope in each dimension, and `c` is the variance of the offset.
+/// A GP with this kernel is equivalent to bayesian linear regression.
+///
+/// The parameters are `[c, v...]`.
+///
+/// # Examples
+///
+/// ```rust
+/// use gprs::kernels::{Kernel, Linear};
+/// use nalgebra::DMatrix;
+///
+/// let kern = Linear::new(vec![1.0, 2.0], 0.5);
+///
+/// let x = DMatrix::from_vec(2, 2, vec![
+///     1.0, 1.0,
+///     2.0, 0.0,
+/// ]);
+///
+/// // the variance grows away from the origin
+/// assert_eq!(kern.call_diagonal(&x).unwrap(), vec![3.5, 4.5]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Linear {
+    variance: Vec<f64>,
+    offset: f64,
+}
+
+impl Linear {
+    /// Create a new kernel from a slope variance per dimension and an offset variance
+    pub fn new<I>(variance: I, offset: f64) -> Self
+    where
+        I: IntoIterator<Item = f64>,
+    {
+        Linear {
+            variance: variance.into_iter().collect(),
+            offset,
+        }
+    }
+
+    /// Compute the covariance between 2 points
+    fn call_point(&self, x_point: &[f64], y_point: &[f64]) -> f64 {
+        self.offset
+            + self
+                .variance
+                .iter()
+                .zip(x_point)
+                .zip(y_point)
+                .map(|((v, x), y)| v * x * y)
+                .sum::<f64>()
+    }
+
+    /// Compute the gradient of the covariance between 2 points w.r.t. each parameter
+    fn jacobian_point(&self, x_point: &[f64], y_point: &[f64], grad: &mut [f64]) {
+        grad[0] = 1.0;
+        grad[1..]
+            .iter_mut()
+            .zip(x_point.iter().zip(y_point))
+            .for_each(|(g, (x, y))| *g = x * y);
+    }
+}
+
+impl Kernel for Linear {
+    fn call_inplace(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+        into: &mut DMatrix<f64>,
+    ) -> Result<(), IncompatibleShapeError> {
+        check_shapes(self.variance.len(), x.shape(), y.shape(), into.shape())?;
+        par_call_inplace(x, y, into, |x_point, y_point| self.call_point(x_point, y_point));
+
+        Ok(())
+    }
+
+    fn call(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let mut value = DMatrix::<f64>::zeros(x.ncols(), y.ncols());
+
+        self.call_inplace(x, y, &mut value)?;
+
+        Ok(value)
+    }
+
+    fn call_triangular(
+        &self,
+        x: &DMatrix<f64>,
+        side: TriangleSide,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let x_shape = x.shape();
+        let mut value = DMatrix::<f64>::zeros(x_shape.1, x_shape.1);
+
+        check_shapes(self.variance.len(), x_shape, x_shape, value.shape())?;
+        par_call_triangular_inplace(x, side, &mut value, |x_point, y_point| {
+            self.call_point(x_point, y_point)
+        });
+
+        Ok(value)
+    }
+
+    fn call_diagonal(&self, x: &DMatrix<f64>) -> Result<Vec<f64>, IncompatibleShapeError> {
+        let x_shape = x.shape();
+        check_shapes(self.variance.len(), x_shape, x_shape, (x_shape.1, x_shape.1))?;
+
+        Ok(par_call_diagonal(x, |x_point, y_point| {
+            self.call_point(x_point, y_point)
+        }))
+    }
+}
+
+impl<'a> Parameterized<'a> for Linear {
+    fn get_params(&'a self) -> Vec<f64> {
+        let mut params = Vec::with_capacity(1 + self.variance.len());
+        params.push(self.offset);
+        params.extend(self.variance.iter());
+        params
+    }
+
+    fn set_params(&'a mut self, params: &[f64]) {
+        self.offset = params[0];
+        params[1..].clone_into(&mut self.variance);
+    }
+}
+
+impl FromParams for Linear {
+    fn from_params(params: &[f64]) -> Self {
+        Linear {
+            variance: params[1..].to_vec(),
+            offset: params[0],
+        }
+    }
+}
+
+impl<'a> Jacobian<'a> for Linear {
+    /// Compute the derivative of the covariance of `x` with itself w.r.t. each parameter
+    ///
+    /// Column `p` of the result is `dK/dθp` flattened column-major, where `θ` is `get_params()`.
+    ///
+    /// `dK/dc = 1`
+    ///
+    /// `dK/dv = x * x'`
+    ///
+    /// # Panics
+    /// If `x` does not have one row per slope variance
+    fn jacobian(&'a self, x: &DMatrix<f64>) -> DMatrix<f64> {
+        let dims = self.variance.len();
+
+        par_jacobian(x, dims, 1 + dims, |x_point, y_point, grad| {
+            self.jacobian_point(x_point, y_point, grad)
+        })
+    }
+}
+
+impl KernelJacobian for Linear {
+    fn cross_jacobian(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let dims = self.variance.len();
+        check_shapes(dims, x.shape(), y.shape(), (x.ncols(), y.ncols()))?;
+
+        Ok(par_cross_jacobian(x, y, 1 + dims, |x_point, y_point, grad| {
+            self.jacobian_point(x_point, y_point, grad)
+        }))
+    }
+
+    fn diagonal_jacobian(&self, x: &DMatrix<f64>) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let dims = self.variance.len();
+        check_shapes(dims, x.shape(), x.shape(), (x.ncols(), x.ncols()))?;
+
+        Ok(par_diagonal_jacobian(x, 1 + dims, |x_point, y_point, grad| {
+            self.jacobian_point(x_point, y_point, grad)
+        }))
+    }
+}
+
+/// Polynomial kernel
+///
+/// `K = (s * x'x + c)^d`
+///
+/// where `s` scales the dot product, `c` is the offset, and `d` is the integer degree.
+///
+/// The parameters are `[c, s]`. The degree is fixed when the kernel is created.
+///
+/// # Examples
+///
+/// ```rust
+/// use gprs::kernels::{Kernel, Polynomial};
+/// use nalgebra::DMatrix;
+///
+/// let kern = Polynomial::new(2, 1.0, 0.5);
+///
+/// let x = DMatrix::from_vec(1, 2, vec![1.0, 2.0]);
+///
+/// // (0.5 * 2 + 1)^2
+/// assert_eq!(kern.call(&x, &x).unwrap()[(1, 0)], 4.0);
+/// assert_eq!(kern.call_diagonal(&x).unwrap(), vec![2.25, 9.0]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Polynomial {
+    degree: i32,
+    offset: f64,
+    scale: f64,
+}
+
+impl Polynomial {
+    /// Create a new kernel from a degree, offset and dot product scale
+    pub fn new(degree: u16, offset: f64, scale: f64) -> Self {
+        Polynomial {
+            degree: degree as i32,
+            offset,
+            scale,
+        }
+    }
+
+    /// Compute the covariance between 2 points
+    fn call_point(&self, x_point: &[f64], y_point: &[f64]) -> f64 {
+        (self.scale * dot(x_point, y_point) + self.offset).powi(self.degree)
+    }
+
+    /// Compute the gradient of the covariance between 2 points w.r.t. each parameter
+    fn jacobian_point(&self, x_point: &[f64], y_point: &[f64], grad: &mut [f64]) {
+        // K = 1 for degree 0, and `0 * base^-1` would be NaN where the base is zero
+        if self.degree == 0 {
+            grad.fill(0.0);
+            return;
+        }
+
+        let xy = dot(x_point, y_point);
+        let base = self.degree as f64 * (self.scale * xy + self.offset).powi(self.degree - 1);
+        grad[0] = base;
+        grad[1] = base * xy;
+    }
+}
+
+impl Kernel for Polynomial {
+    fn call_inplace(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+        into: &mut DMatrix<f64>,
+    ) -> Result<(), IncompatibleShapeError> {
+        check_shapes(x.nrows(), x.shape(), y.shape(), into.shape())?;
+        par_call_inplace(x, y, into, |x_point, y_point| self.call_point(x_point, y_point));
+
+        Ok(())
+    }
+
+    fn call(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let mut value = DMatrix::<f64>::zeros(x.ncols(), y.ncols());
+
+        self.call_inplace(x, y, &mut value)?;
+
+        Ok(value)
+    }
+
+    fn call_triangular(
+        &self,
+        x: &DMatrix<f64>,
+        side: TriangleSide,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let x_shape = x.shape();
+        let mut value = DMatrix::<f64>::zeros(x_shape.1, x_shape.1);
+
+        par_call_triangular_inplace(x, side, &mut value, |x_point, y_point| {
+            self.call_point(x_point, y_point)
+        });
+
+        Ok(value)
+    }
+
+    fn call_diagonal(&self, x: &DMatrix<f64>) -> Result<Vec<f64>, IncompatibleShapeError> {
+        Ok(par_call_diagonal(x, |x_point, y_point| {
+            self.call_point(x_point, y_point)
+        }))
+    }
+}
+
+impl<'a> Parameterized<'a> for Polynomial {
+    fn get_params(&'a self) -> Vec<f64> {
+        vec![self.offset, self.scale]
+    }
+
+    fn set_params(&'a mut self, params: &[f64]) {
+        self.offset = params[0];
+        self.scale = params[1];
+    }
+}
+
+impl<'a> Jacobian<'a> for Polynomial {
+    /// Compute the derivative of the covariance of `x` with itself w.r.t. each parameter
+    ///
+    /// Column `p` of the result is `dK/dθp` flattened column-major, where `θ` is `get_params()`.
+    ///
+    /// `dK/dc = d * (s * x'x + c)^(d - 1)`
+    ///
+    /// `dK/ds = d * (s * x'x + c)^(d - 1) * x'x`
+    fn jacobian(&'a self, x: &DMatrix<f64>) -> DMatrix<f64> {
+        par_jacobian(x, x.nrows(), 2, |x_point, y_point, grad| {
+            self.jacobian_point(x_point, y_point, grad)
+        })
+    }
+}
+
+impl KernelJacobian for Polynomial {
+    fn cross_jacobian(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        check_shapes(x.nrows(), x.shape(), y.shape(), (x.ncols(), y.ncols()))?;
+
+        Ok(par_cross_jacobian(x, y, 2, |x_point, y_point, grad| {
+            self.jacobian_point(x_point, y_point, grad)
+        }))
+    }
+
+    fn diagonal_jacobian(&self, x: &DMatrix<f64>) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        Ok(par_diagonal_jacobian(x, 2, |x_point, y_point, grad| {
+            self.jacobian_point(x_point, y_point, grad)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::DMatrix;
+
+    use crate::{
+        kernels::{
+            test_util::{check_jacobian, check_triangular_diagonal},
+            Kernel,
+        },
+        parameterized::Jacobian,
+    };
+
+    use super::{Linear, Polynomial};
+
+    fn points() -> DMatrix<f64> {
+        DMatrix::from_vec(2, 3, vec![0.0, 1.0, 0.5, -0.3, 1.2, 0.4])
+    }
+
+    /// The linear kernel is a weighted dot product plus an offset
+    #[test]
+    fn test_linear_correctness() {
+        let kern = Linear::new(vec![2.0, 3.0], 0.5);
+        let x = DMatrix::from_vec(2, 1, vec![1.0, 2.0]);
+        let y = DMatrix::from_vec(2, 2, vec![3.0, 4.0, -1.0, 0.0]);
+
+        let k = kern.call(&x, &y).unwrap();
+
+        assert_eq!(k[(0, 0)], 0.5 + 2.0 * 3.0 + 3.0 * 8.0);
+        assert_eq!(k[(0, 1)], 0.5 - 2.0);
+    }
+
+    /// The diagonal is computed per point, and agrees with the full computation
+    #[test]
+    fn test_diagonal() {
+        check_triangular_diagonal(&Linear::new(vec![2.0, 3.0], 0.5), &points());
+        check_triangular_diagonal(&Polynomial::new(3, 1.0, 0.5), &points());
+    }
+
+    /// Passing invalid data will return an error
+    #[test]
+    fn test_mismatched() {
+        let kern = Linear::new(vec![1.0], 1.0);
+        let x = DMatrix::from_vec(2, 1, vec![1.0, 1.0]);
+
+        assert!(kern.call(&x, &x).is_err());
+        assert!(kern.call_diagonal(&x).is_err());
+    }
+
+    /// The jacobians match finite-difference approximations
+    #[test]
+    fn test_jacobian_finite_difference() {
+        check_jacobian(&Linear::new(vec![2.0, 3.0], 0.5), &points());
+        check_jacobian(&Polynomial::new(3, 1.0, 0.5), &points());
+    }
+
+    /// A degree 0 kernel is constant, even where the base is zero
+    #[test]
+    fn test_degree_zero() {
+        let kern = Polynomial::new(0, 0.0, 0.5);
+        let x = DMatrix::from_vec(2, 2, vec![0.0, 0.0, 1.0, 2.0]);
+
+        assert_eq!(kern.call(&x, &x).unwrap(), DMatrix::from_element(2, 2, 1.0));
+        assert_eq!(kern.jacobian(&x), DMatrix::zeros(4, 2));
+        check_jacobian(&kern, &points());
+    }
+}
@@ -0,0 +1,266 @@
+use nalgebra::DMatrix;
+
+use crate::{
+    linalg::errors::IncompatibleShapeError,
+    parameterized::{FromParams, Jacobian, Parameterized},
+};
+
+use super::{
+    eval::{
+        check_shapes, par_call_inplace, par_call_triangular_inplace, par_cross_jacobian,
+        par_diagonal_jacobian, par_jacobian,
+    },
+    kernel::{Kernel, KernelJacobian, TriangleSide},
+};
+
+/// Rational Quadratic kernel
+///
+/// `K = s^2 * (1 + q / (2 * a))^-a`, where `q = sum(((x - x') / l)^2)`
+///
+/// This is a scale mixture of RBF kernels with different length scales, where `a` controls the
+/// weighting of large and small scales. As `a` grows, the kernel approaches an RBF kernel.
+///
+/// The parameters are `[s^2, a, l...]`.
+///
+/// # Examples
+///
+/// ```rust
+/// use gprs::kernels::{Kernel, RationalQuadratic};
+/// use nalgebra::DMatrix;
+///
+/// // create a 2-d kernel with a length scale per dimension
+/// let kern = RationalQuadratic::new(vec![1.0, 2.0], 0.5, 1.0);
+///
+/// let x = DMatrix::from_vec(2, 1, vec![0.0, 0.0]);
+/// let y = DMatrix::from_vec(2, 1, vec![1.0, 2.0]);
+///
+/// // q = 2, so k = (1 + 2)^-0.5
+/// let k = kern.call(&x, &y).unwrap();
+/// assert!((k[0] - 3.0_f64.powf(-0.5)).abs() < 1e-12);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RationalQuadratic {
+    length_scale: Vec<f64>,
+    alpha: f64,
+    amplitude: f64,
+}
+
+impl RationalQuadratic {
+    /// Create a new kernel from a length scale per dimension, a shape parameter and a standard deviation
+    pub fn new<I>(length_scale: I, alpha: f64, sigma: f64) -> Self
+    where
+        I: IntoIterator<Item = f64>,
+    {
+        RationalQuadratic {
+            length_scale: length_scale.into_iter().collect(),
+            alpha,
+            amplitude: sigma * sigma,
+        }
+    }
+
+    /// Compute the squared scaled distance between 2 points
+    fn distance_squared(&self, x_point: &[f64], y_point: &[f64]) -> f64 {
+        self.length_scale
+            .iter()
+            .zip(x_point)
+            .zip(y_point)
+            .map(|((l, x), y)| {
+                let diff = (x - y) / l;
+                diff * diff
+            })
+            .sum::<f64>()
+    }
+
+    /// Compute the covariance between 2 points
+    fn call_point(&self, x_point: &[f64], y_point: &[f64]) -> f64 {
+        let base = 1.0 + self.distance_squared(x_point, y_point) / (2.0 * self.alpha);
+        self.amplitude * base.powf(-self.alpha)
+    }
+
+    /// Compute the gradient of the covariance between 2 points w.r.t. each parameter
+    fn jacobian_point(&self, x_point: &[f64], y_point: &[f64], grad: &mut [f64]) {
+        let q = self.distance_squared(x_point, y_point);
+        let base = 1.0 + q / (2.0 * self.alpha);
+        let unscaled = base.powf(-self.alpha);
+
+        grad[0] = unscaled;
+        grad[1] = self.amplitude * unscaled * (q / (2.0 * self.alpha * base) - base.ln());
+
+        let scale = self.amplitude * unscaled / base;
+        grad[2..]
+            .iter_mut()
+            .zip(&self.length_scale)
+            .zip(x_point.iter().zip(y_point))
+            .for_each(|((g, l), (x, y))| {
+                let diff = x - y;
+                *g = scale * diff * diff / (l * l * l);
+            });
+    }
+}
+
+impl Kernel for RationalQuadratic {
+    fn call_inplace(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+        into: &mut DMatrix<f64>,
+    ) -> Result<(), IncompatibleShapeError> {
+        check_shapes(self.length_scale.len(), x.shape(), y.shape(), into.shape())?;
+        par_call_inplace(x, y, into, |x_point, y_point| self.call_point(x_point, y_point));
+
+        Ok(())
+    }
+
+    fn call(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let mut value = DMatrix::<f64>::zeros(x.ncols(), y.ncols());
+
+        self.call_inplace(x, y, &mut value)?;
+
+        Ok(value)
+    }
+
+    fn call_triangular(
+        &self,
+        x: &DMatrix<f64>,
+        side: TriangleSide,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let x_shape = x.shape();
+        let mut value = DMatrix::<f64>::zeros(x_shape.1, x_shape.1);
+
+        check_shapes(self.length_scale.len(), x_shape, x_shape, value.shape())?;
+        par_call_triangular_inplace(x, side, &mut value, |x_point, y_point| {
+            self.call_point(x_point, y_point)
+        });
+
+        Ok(value)
+    }
+
+    fn call_diagonal(&self, x: &DMatrix<f64>) -> Result<Vec<f64>, IncompatibleShapeError> {
+        Ok(vec![self.amplitude; x.shape().1])
+    }
+}
+
+impl<'a> Parameterized<'a> for RationalQuadratic {
+    fn get_params(&'a self) -> Vec<f64> {
+        let mut params = Vec::with_capacity(2 + self.length_scale.len());
+        params.push(self.amplitude);
+        params.push(self.alpha);
+        params.extend(self.length_scale.iter());
+        params
+    }
+
+    fn set_params(&'a mut self, params: &[f64]) {
+        self.amplitude = params[0];
+        self.alpha = params[1];
+        params[2..].clone_into(&mut self.length_scale);
+    }
+}
+
+impl FromParams for RationalQuadratic {
+    fn from_params(params: &[f64]) -> Self {
+        RationalQuadratic {
+            length_scale: params[2..].to_vec(),
+            alpha: params[1],
+            amplitude: params[0],
+        }
+    }
+}
+
+impl<'a> Jacobian<'a> for RationalQuadratic {
+    /// Compute the derivative of the covariance of `x` with itself w.r.t. each parameter
+    ///
+    /// Column `p` of the result is `dK/dθp` flattened column-major, where `θ` is `get_params()`.
+    ///
+    /// With `b = 1 + q / (2a)`:
+    ///
+    /// `dK/da = K * (q / (2ab) - ln(b))`
+    ///
+    /// `dK/dl = s^2 * b^(-a-1) * (x - x')^2 / l^3`
+    ///
+    /// # Panics
+    /// If `x` does not have one row per length scale
+    fn jacobian(&'a self, x: &DMatrix<f64>) -> DMatrix<f64> {
+        let dims = self.length_scale.len();
+
+        par_jacobian(x, dims, 2 + dims, |x_point, y_point, grad| {
+            self.jacobian_point(x_point, y_point, grad)
+        })
+    }
+}
+
+impl KernelJacobian for RationalQuadratic {
+    fn cross_jacobian(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let dims = self.length_scale.len();
+        check_shapes(dims, x.shape(), y.shape(), (x.ncols(), y.ncols()))?;
+
+        Ok(par_cross_jacobian(x, y, 2 + dims, |x_point, y_point, grad| {
+            self.jacobian_point(x_point, y_point, grad)
+        }))
+    }
+
+    fn diagonal_jacobian(&self, x: &DMatrix<f64>) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let dims = self.length_scale.len();
+        check_shapes(dims, x.shape(), x.shape(), (x.ncols(), x.ncols()))?;
+
+        Ok(par_diagonal_jacobian(x, 2 + dims, |x_point, y_point, grad| {
+            self.jacobian_point(x_point, y_point, grad)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::DMatrix;
+
+    use crate::kernels::{
+        test_util::{check_jacobian, check_triangular_diagonal},
+        Kernel, RBF,
+    };
+
+    use super::RationalQuadratic;
+
+    fn points() -> DMatrix<f64> {
+        DMatrix::from_vec(2, 3, vec![0.0, 1.0, 0.5, -0.3, 1.2, 0.4])
+    }
+
+    /// A large shape parameter approaches the RBF kernel
+    #[test]
+    fn test_rbf_limit() {
+        let x = points();
+        let rq = RationalQuadratic::new(vec![0.8, 1.5], 1e8, 1.2)
+            .call(&x, &x)
+            .unwrap();
+        let rbf = RBF::new(vec![0.8, 1.5], 1.2).call(&x, &x).unwrap();
+
+        assert!((rq - rbf).amax() < 1e-6);
+    }
+
+    /// The triangular and diagonal paths agree with the full computation
+    #[test]
+    fn test_triangular_diagonal() {
+        check_triangular_diagonal(&RationalQuadratic::new(vec![0.8, 1.5], 0.7, 1.2), &points());
+    }
+
+    /// Passing invalid data will return an error
+    #[test]
+    fn test_mismatched() {
+        let kern = RationalQuadratic::new(vec![1.0], 1.0, 1.0);
+        let x = DMatrix::from_vec(2, 1, vec![1.0, 1.0]);
+
+        assert!(kern.call(&x, &x).is_err());
+    }
+
+    /// The jacobian matches a finite-difference approximation
+    #[test]
+    fn test_jacobian_finite_difference() {
+        check_jacobian(&RationalQuadratic::new(vec![0.8, 1.5], 0.7, 1.2), &points());
+    }
+}
@@ -1,12 +1,16 @@
 use crate::{
-    indexing::{index_to_2d, slice_indices},
     linalg::errors::IncompatibleShapeError,
-    parameterized::Parameterized,
+    parameterized::{FromParams, Jacobian, Parameterized},
 };
 
-use super::kernel::{Kernel, TriangleSide};
+use super::{
+    eval::{
+        check_hessian_shapes, check_shapes, par_call_inplace, par_call_triangular_inplace,
+        par_cross_jacobian, par_diagonal_jacobian, par_gradient, par_hessian, par_jacobian,
+    },
+    kernel::{Kernel, KernelGradient, KernelHessian, KernelJacobian, TriangleSide},
+};
 use nalgebra::DMatrix;
-use rayon::prelude::*;
 
 /// Radial Basis Function kernel
 ///
@@ -45,12 +49,12 @@ use rayon::prelude::*;
 ///
 /// ```rust
 /// use gprs::kernels::{RBF,Kernel};
-/// use gprs::parameterized::Parameterized;
+/// use gprs::parameterized::FromParams;
 /// use nalgebra::DVector;
 ///
 /// let kern = RBF::from_params(vec![1.0, -0.5, -0.125].as_slice());
 /// ```
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct RBF {
     gamma: Vec<f64>,
     amplitude: f64,
@@ -81,8 +85,12 @@ impl RBF {
 
     /// Compute the covariance between 2 points
     fn call_point(&self, x_point: &[f64], y_point: &[f64]) -> f64 {
-        let unscaled = self
-            .gamma
+        self.call_point_unscaled(x_point, y_point) * self.amplitude
+    }
+
+    /// Compute the covariance between 2 points, without the amplitude
+    fn call_point_unscaled(&self, x_point: &[f64], y_point: &[f64]) -> f64 {
+        self.gamma
             .iter()
             .zip(x_point)
             .zip(y_point)
@@ -91,67 +99,21 @@ impl RBF {
                 diff * diff * g
             })
             .sum::<f64>()
-            .exp();
-
-        unscaled * self.amplitude
-    }
-
-    fn check_shapes(
-        &self,
-        x_shape: (usize, usize),
-        y_shape: (usize, usize),
-        into_shape: (usize, usize),
-    ) -> Result<(), IncompatibleShapeError> {
-        if x_shape.0 != self.gamma.len()
-            || y_shape.0 != self.gamma.len()
-            || into_shape != (x_shape.1, y_shape.1)
-        {
-            return Err(IncompatibleShapeError {
-                shapes: vec![x_shape, y_shape, (1, self.gamma.len()), into_shape],
-            });
-        }
-
-        Ok(())
+            .exp()
     }
 
-    fn call_triangular_inplace(
-        &self,
-        x: &DMatrix<f64>,
-        side: TriangleSide,
-        into: &mut DMatrix<f64>,
-    ) -> Result<(), IncompatibleShapeError> {
-        let x_shape = x.shape();
-        let into_shape = into.shape();
-
-        self.check_shapes(x_shape, x_shape, into_shape)?;
-
-        let dims = x_shape.0;
-        let x_sl = x.as_slice();
-
-        into.as_mut_slice()
-            .into_par_iter()
-            .enumerate()
-            .map(|(index, v)| {
-                let (i, j) = index_to_2d(index, x_shape.1);
-                (i, j, v)
-            })
-            .filter(|(i, j, _v)| match side {
-                TriangleSide::LOWER => i <= j,
-                TriangleSide::UPPER => i >= j,
-            })
-            .for_each(|(i, j, v)| {
-                let (xs, xe) = slice_indices(i, dims);
-                let (ys, ye) = slice_indices(j, dims);
-
-                // SAFETY: the indices are valid because we checked them at the beginning of the function
-                unsafe {
-                    let x_point = &x_sl.get_unchecked(xs..xe);
-                    let y_point = &x_sl.get_unchecked(ys..ye);
-                    *v = self.call_point(x_point, y_point);
-                }
+    /// Compute the gradient of the covariance between 2 points w.r.t. each parameter
+    fn jacobian_point(&self, x_point: &[f64], y_point: &[f64], grad: &mut [f64]) {
+        let unscaled = self.call_point_unscaled(x_point, y_point);
+        grad[0] = unscaled;
+        grad[1..]
+            .iter_mut()
+            .zip(x_point)
+            .zip(y_point)
+            .for_each(|((g, x), y)| {
+                let diff = x - y;
+                *g = self.amplitude * unscaled * diff * diff;
             });
-
-        Ok(())
     }
 }
 
@@ -162,31 +124,8 @@ impl Kernel for RBF {
         y: &DMatrix<f64>,
         into: &mut DMatrix<f64>,
     ) -> Result<(), IncompatibleShapeError> {
-        let x_shape = x.shape();
-        let y_shape = y.shape();
-        let into_shape = into.shape();
-
-        self.check_shapes(x_shape, y_shape, into_shape)?;
-
-        let dims = x_shape.0;
-        let x_sl = x.as_slice();
-        let y_sl = y.as_slice();
-
-        into.as_mut_slice()
-            .into_par_iter()
-            .enumerate()
-            .for_each(|(index, v)| {
-                let (i, j) = index_to_2d(index, y_shape.1);
-                let (xs, xe) = slice_indices(i, dims);
-                let (ys, ye) = slice_indices(j, dims);
-
-                // SAFETY: the indices are valid because we checked them at the beginning of the function
-                unsafe {
-                    let x_point = &x_sl.get_unchecked(xs..xe);
-                    let y_point = &y_sl.get_unchecked(ys..ye);
-                    *v = self.call_point(x_point, y_point);
-                }
-            });
+        check_shapes(self.gamma.len(), x.shape(), y.shape(), into.shape())?;
+        par_call_inplace(x, y, into, |x_point, y_point| self.call_point(x_point, y_point));
 
         Ok(())
     }
@@ -213,7 +152,10 @@ impl Kernel for RBF {
         let x_shape = x.shape();
         let mut value = DMatrix::<f64>::zeros(x_shape.1, x_shape.1);
 
-        self.call_triangular_inplace(x, side, &mut value)?;
+        check_shapes(self.gamma.len(), x_shape, x_shape, value.shape())?;
+        par_call_triangular_inplace(x, side, &mut value, |x_point, y_point| {
+            self.call_point(x_point, y_point)
+        });
 
         Ok(value)
     }
@@ -223,6 +165,65 @@ impl Kernel for RBF {
     }
 }
 
+impl KernelGradient for RBF {
+    /// `dk/dy = -2 * g * (x - y) * k`
+    fn gradient(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<Vec<DMatrix<f64>>, IncompatibleShapeError> {
+        check_shapes(
+            self.gamma.len(),
+            x.shape(),
+            y.shape(),
+            (x.ncols(), y.ncols()),
+        )?;
+
+        Ok(par_gradient(x, y, |x_point, y_point, grad| {
+            let k = self.call_point(x_point, y_point);
+            grad.iter_mut()
+                .zip(&self.gamma)
+                .zip(x_point.iter().zip(y_point))
+                .for_each(|((d, g), (x, y))| *d = -2.0 * g * (x - y) * k);
+        }))
+    }
+
+    fn diagonal_gradient(&self, x: &DMatrix<f64>) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        check_shapes(
+            self.gamma.len(),
+            x.shape(),
+            x.shape(),
+            (x.ncols(), x.ncols()),
+        )?;
+        Ok(DMatrix::zeros(x.nrows(), x.ncols()))
+    }
+}
+
+impl KernelHessian for RBF {
+    /// `d2k/(dx_a dy_b) = k * (-2 * g_a * [a == b] - 4 * g_a * g_b * (x_a - y_a) * (x_b - y_b))`
+    fn hessian(
+        &self,
+        x: &DMatrix<f64>,
+        x_dims: &[usize],
+        y: &DMatrix<f64>,
+        y_dims: &[usize],
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        check_hessian_shapes(self.gamma.len(), x.shape(), x_dims, y.shape(), y_dims)?;
+
+        Ok(par_hessian(x, x_dims, y, y_dims, |x_point, a, y_point, b| {
+            let k = self.call_point(x_point, y_point);
+            let (ga, gb) = (self.gamma[a], self.gamma[b]);
+            let cross = -4.0 * ga * gb * (x_point[a] - y_point[a]) * (x_point[b] - y_point[b]);
+
+            if a == b {
+                k * (cross - 2.0 * ga)
+            } else {
+                k * cross
+            }
+        }))
+    }
+}
+
 impl<'a> Parameterized<'a> for RBF {
     fn get_params(&'a self) -> Vec<f64> {
         let mut params = Vec::with_capacity(1 + self.gamma.len());
@@ -235,7 +236,9 @@ impl<'a> Parameterized<'a> for RBF {
         self.amplitude = params[0];
         params[1..].clone_into(&mut self.gamma);
     }
+}
 
+impl FromParams for RBF {
     fn from_params(params: &[f64]) -> Self {
         RBF {
             gamma: params[1..].to_vec(),
@@ -244,9 +247,57 @@ impl<'a> Parameterized<'a> for RBF {
     }
 }
 
+impl<'a> Jacobian<'a> for RBF {
+    /// Compute the derivative of the covariance of `x` with itself w.r.t. each parameter
+    ///
+    /// Column `p` of the result is `dK/dθp` flattened column-major, where `θ` is `get_params()`.
+    ///
+    /// `dK/da = K / a`
+    ///
+    /// `dK/dg = K * (x - x')^2`
+    ///
+    /// # Panics
+    /// If `x` does not have one row per length scale
+    fn jacobian(&'a self, x: &DMatrix<f64>) -> DMatrix<f64> {
+        par_jacobian(x, self.gamma.len(), 1 + self.gamma.len(), |x_point, y_point, grad| {
+            self.jacobian_point(x_point, y_point, grad)
+        })
+    }
+}
+
+impl KernelJacobian for RBF {
+    fn cross_jacobian(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        check_shapes(
+            self.gamma.len(),
+            x.shape(),
+            y.shape(),
+            (x.ncols(), y.ncols()),
+        )?;
+        Ok(par_cross_jacobian(x, y, 1 + self.gamma.len(), |x_point, y_point, grad| {
+            self.jacobian_point(x_point, y_point, grad)
+        }))
+    }
+
+    fn diagonal_jacobian(&self, x: &DMatrix<f64>) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        check_shapes(
+            self.gamma.len(),
+            x.shape(),
+            x.shape(),
+            (x.ncols(), x.ncols()),
+        )?;
+        Ok(par_diagonal_jacobian(x, 1 + self.gamma.len(), |x_point, y_point, grad| {
+            self.jacobian_point(x_point, y_point, grad)
+        }))
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::kernels::{Kernel, RBF};
+    use crate::kernels::{test_util::check_jacobian, Kernel, KernelGradient, KernelHessian, RBF};
     use nalgebra::DMatrix;
 
     fn create(v: Vec<f64>) -> RBF {
@@ -261,7 +312,7 @@ mod tests {
         let x = DMatrix::from_vec(2, 1, vec![1.0, 1.0]);
         let y = DMatrix::from_vec(2, 1, vec![1.0, 1.0]);
         let err = kern.call(&x, &y).unwrap_err();
-        assert!(err.shapes.len() > 0);
+        assert!(!err.shapes.is_empty());
     }
 
     /// Passing a zero lengthscale will produce NaN
@@ -347,4 +398,88 @@ mod tests {
 
         assert_eq!(k[0], (-9.125_f64).exp());
     }
+
+    /// Rows of the result correspond to `x` and columns to `y`, even when not square
+    #[test]
+    fn test_non_square_layout() {
+        let kern = create(vec![1.0]);
+
+        let x = DMatrix::from_vec(1, 3, vec![0.0, 1.0, 2.0]);
+        let y = DMatrix::from_vec(1, 2, vec![0.0, 5.0]);
+        let k = kern.call(&x, &y).unwrap();
+
+        for i in 0..3 {
+            for j in 0..2 {
+                let diff = x[i] - y[j];
+                assert_eq!(k[(i, j)], (-0.5 * diff * diff).exp());
+            }
+        }
+    }
+
+    /// The jacobian matches a finite-difference approximation
+    #[test]
+    fn test_jacobian_finite_difference() {
+        let kern = RBF::new(vec![0.8, 1.5], 1.2);
+        let x = DMatrix::from_vec(2, 3, vec![0.0, 1.0, 0.5, -0.3, 1.2, 0.4]);
+
+        check_jacobian(&kern, &x);
+    }
+
+    /// The input gradient matches a finite-difference approximation in each dimension
+    #[test]
+    fn test_gradient_finite_difference() {
+        let kern = RBF::new(vec![0.8, 1.5], 1.2);
+        let x = DMatrix::from_vec(2, 3, vec![0.0, 1.0, 0.5, -0.3, 1.2, 0.4]);
+        let y = DMatrix::from_vec(2, 2, vec![0.2, 0.7, -1.0, 0.1]);
+
+        let grad = kern.gradient(&x, &y).unwrap();
+        assert_eq!(grad.len(), 2);
+
+        let eps = 1e-6;
+        for (d, grad) in grad.iter().enumerate() {
+            let mut up = y.clone();
+            up.row_mut(d).add_scalar_mut(eps);
+            let mut down = y.clone();
+            down.row_mut(d).add_scalar_mut(-eps);
+
+            let fd = (kern.call(&x, &up).unwrap() - kern.call(&x, &down).unwrap()) / (2.0 * eps);
+            assert_eq!(grad.shape(), (3, 2));
+
+            for (a, b) in grad.iter().zip(fd.iter()) {
+                assert!((a - b).abs() < 1e-6, "{} != {}", a, b);
+            }
+        }
+
+        assert!(kern.gradient(&y.rows(0, 1).into_owned(), &y).is_err());
+    }
+
+    /// The hessian matches a finite-difference approximation of the input gradient
+    #[test]
+    fn test_hessian_finite_difference() {
+        let kern = RBF::new(vec![0.8, 1.5], 1.2);
+        let x = DMatrix::from_vec(2, 3, vec![0.0, 1.0, 0.5, -0.3, 1.2, 0.4]);
+        let y = DMatrix::from_vec(2, 2, vec![0.2, 0.7, -1.0, 0.1]);
+
+        let eps = 1e-6;
+        for a in 0..2 {
+            for b in 0..2 {
+                let hessian = kern.hessian(&x, &[a; 3], &y, &[b; 2]).unwrap();
+
+                let mut up = x.clone();
+                up.row_mut(a).add_scalar_mut(eps);
+                let mut down = x.clone();
+                down.row_mut(a).add_scalar_mut(-eps);
+
+                let fd = (&kern.gradient(&up, &y).unwrap()[b]
+                    - &kern.gradient(&down, &y).unwrap()[b])
+                    / (2.0 * eps);
+
+                for (h, f) in hessian.iter().zip(fd.iter()) {
+                    assert!((h - f).abs() < 1e-6, "{} != {}", h, f);
+                }
+            }
+        }
+
+        assert!(kern.hessian(&x, &[0, 1, 2], &y, &[0, 0]).is_err());
+    }
 }
@@ -0,0 +1,72 @@
+//! Checks shared by the kernel tests
+
+use nalgebra::DMatrix;
+
+use crate::parameterized::{Jacobian, Parameterized};
+
+use super::{Kernel, KernelJacobian, TriangleSide};
+
+/// Check that the triangular and diagonal paths agree with the full computation
+pub(crate) fn check_triangular_diagonal<K: Kernel + ?Sized>(kern: &K, x: &DMatrix<f64>) {
+    let n = x.ncols();
+    let full = kern.call(x, x).unwrap();
+    let lower = kern.call_triangular(x, TriangleSide::LOWER).unwrap();
+    let diag = kern.call_diagonal(x).unwrap();
+
+    for j in 0..n {
+        for i in j..n {
+            assert!((full[(i, j)] - lower[(i, j)]).abs() < 1e-15);
+        }
+        assert!((full[(j, j)] - diag[j]).abs() < 1e-15);
+    }
+}
+
+/// Check that the jacobians match a finite-difference approximation
+///
+/// The cross jacobian is checked between `x` and `x` without its first column.
+pub(crate) fn check_jacobian<K>(kern: &K, x: &DMatrix<f64>)
+where
+    K: KernelJacobian + Clone + for<'a> Parameterized<'a> + for<'a> Jacobian<'a>,
+{
+    let y = x.columns(1, x.ncols() - 1).into_owned();
+    let jac = kern.jacobian(x);
+    let cross = kern.cross_jacobian(x, &y).unwrap();
+    let diag = kern.diagonal_jacobian(x).unwrap();
+    let params = kern.get_params();
+    assert_eq!(jac.shape(), (x.ncols() * x.ncols(), params.len()));
+    assert_eq!(cross.shape(), (x.ncols() * y.ncols(), params.len()));
+    assert_eq!(diag.shape(), (x.ncols(), params.len()));
+
+    let eps = 1e-6;
+    for p in 0..params.len() {
+        let mut up = kern.clone();
+        let mut v = params.clone();
+        v[p] += eps;
+        up.set_params(&v);
+
+        let mut down = kern.clone();
+        let mut v = params.clone();
+        v[p] -= eps;
+        down.set_params(&v);
+
+        let fd = (up.call(x, x).unwrap() - down.call(x, x).unwrap()) / (2.0 * eps);
+        let fd_cross = (up.call(x, &y).unwrap() - down.call(x, &y).unwrap()) / (2.0 * eps);
+        let fd_diag = up
+            .call_diagonal(x)
+            .unwrap()
+            .iter()
+            .zip(down.call_diagonal(x).unwrap())
+            .map(|(u, d)| (u - d) / (2.0 * eps))
+            .collect::<Vec<_>>();
+
+        let (jac_p, cross_p, diag_p) = (jac.column(p), cross.column(p), diag.column(p));
+        let pairs = jac_p
+            .iter()
+            .zip(fd.iter())
+            .chain(cross_p.iter().zip(fd_cross.iter()))
+            .chain(diag_p.iter().zip(&fd_diag));
+        for (a, b) in pairs {
+            assert!((a - b).abs() < 1e-6, "{} != {}", a, b);
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use nalgebra::DMatrix;
+use rayon::prelude::*;
+
+use crate::{
+    linalg::errors::IncompatibleShapeError,
+    parameterized::{FromParams, Jacobian, Parameterized},
+};
+
+use super::{
+    eval::{check_shapes, par_call_inplace},
+    kernel::{Kernel, KernelJacobian, TriangleSide},
+};
+
+/// White noise kernel
+///
+/// `K = s` where `x == x'`, and `0` otherwise
+///
+/// When compiling a GP, the noise is only added to the diagonal of the training covariance,
+/// so duplicate training points do not make the covariance singular.
+/// Likewise `call_disjoint` is zero, so observations added to a compiled GP get their own noise.
+/// The training covariance, and so the log marginal likelihood, is the same as for `GP::new(kernel, s)`,
+/// except that `s` is now a kernel parameter that can be optimized with the rest of the kernel.
+///
+/// Predictions are not the same, since `call` also adds `s` to the cross-covariance between
+/// a test point and an identical training point. `GP::new(kernel + WhiteNoise::new(s), 0.0)`
+/// predicts the same mean as `GP::new(kernel, s)` away from the training inputs, but at a training input
+/// the mean is pulled towards the observed value. Since `call` and `call_diagonal` include the noise,
+/// predicted variances and covariances are for noisy observations.
+///
+/// The parameters are `[s]`.
+///
+/// # Examples
+///
+/// ```rust
+/// use gprs::kernels::{Kernel, TriangleSide, WhiteNoise};
+/// use nalgebra::DMatrix;
+///
+/// let kern = WhiteNoise::new(0.5);
+/// let x = DMatrix::from_vec(1, 2, vec![1.0, 1.0]);
+///
+/// // identical points are perfectly correlated
+/// assert_eq!(kern.call(&x, &x).unwrap()[(1, 0)], 0.5);
+///
+/// // but only the diagonal of the training covariance is filled
+/// assert_eq!(kern.call_triangular(&x, TriangleSide::LOWER).unwrap()[(1, 0)], 0.0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct WhiteNoise {
+    variance: f64,
+}
+
+impl WhiteNoise {
+    /// Create a new kernel from a noise variance
+    pub fn new(variance: f64) -> Self {
+        WhiteNoise { variance }
+    }
+}
+
+impl Kernel for WhiteNoise {
+    fn call_inplace(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+        into: &mut DMatrix<f64>,
+    ) -> Result<(), IncompatibleShapeError> {
+        check_shapes(x.nrows(), x.shape(), y.shape(), into.shape())?;
+        par_call_inplace(x, y, into, |x_point, y_point| {
+            if x_point == y_point {
+                self.variance
+            } else {
+                0.0
+            }
+        });
+
+        Ok(())
+    }
+
+    fn call(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let mut value = DMatrix::<f64>::zeros(x.ncols(), y.ncols());
+
+        self.call_inplace(x, y, &mut value)?;
+
+        Ok(value)
+    }
+
+    fn call_triangular(
+        &self,
+        x: &DMatrix<f64>,
+        _side: TriangleSide,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let n = x.ncols();
+        let mut value = DMatrix::<f64>::zeros(n, n);
+
+        if n > 0 {
+            value
+                .as_mut_slice()
+                .par_chunks_exact_mut(n)
+                .enumerate()
+                .for_each(|(i, col)| col[i] = self.variance);
+        }
+
+        Ok(value)
+    }
+
+    fn call_diagonal(&self, x: &DMatrix<f64>) -> Result<Vec<f64>, IncompatibleShapeError> {
+        Ok(vec![self.variance; x.ncols()])
+    }
+
+    /// Separate observations are uncorrelated, even at identical points
+    fn call_disjoint(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let value = DMatrix::<f64>::zeros(x.ncols(), y.ncols());
+        check_shapes(x.nrows(), x.shape(), y.shape(), value.shape())?;
+
+        Ok(value)
+    }
+}
+
+impl<'a> Parameterized<'a> for WhiteNoise {
+    fn get_params(&'a self) -> Vec<f64> {
+        vec![self.variance]
+    }
+
+    fn set_params(&'a mut self, params: &[f64]) {
+        self.variance = params[0];
+    }
+}
+
+impl FromParams for WhiteNoise {
+    fn from_params(params: &[f64]) -> Self {
+        WhiteNoise {
+            variance: params[0],
+        }
+    }
+}
+
+impl<'a> Jacobian<'a> for WhiteNoise {
+    /// `dK/ds = I`, matching the training covariance from `call_triangular`
+    fn jacobian(&'a self, x: &DMatrix<f64>) -> DMatrix<f64> {
+        let n = x.ncols();
+        let identity = DMatrix::<f64>::identity(n, n);
+        DMatrix::from_column_slice(n * n, 1, identity.as_slice())
+    }
+}
+
+impl KernelJacobian for WhiteNoise {
+    /// `dK/ds = 1` between identical points, matching `call`
+    fn cross_jacobian(
+        &self,
+        x: &DMatrix<f64>,
+        y: &DMatrix<f64>,
+    ) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        let k = WhiteNoise::new(1.0).call(x, y)?;
+        Ok(DMatrix::from_column_slice(k.len(), 1, k.as_slice()))
+    }
+
+    fn diagonal_jacobian(&self, x: &DMatrix<f64>) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        Ok(DMatrix::from_element(x.ncols(), 1, 1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::DMatrix;
+
+    use crate::{
+        gp::GP,
+        kernels::{Kernel, TriangleSide, RBF},
+    };
+
+    use super::WhiteNoise;
+
+    /// Only identical points are correlated
+    #[test]
+    fn test_call() {
+        let kern = WhiteNoise::new(2.0);
+        let x = DMatrix::from_vec(2, 2, vec![0.0, 1.0, 0.0, 2.0]);
+        let y = DMatrix::from_vec(2, 3, vec![0.0, 2.0, 0.0, 1.0, 1.0, 1.0]);
+
+        let k = kern.call(&x, &y).unwrap();
+        let expected = DMatrix::from_vec(2, 3, vec![0.0, 2.0, 2.0, 0.0, 0.0, 0.0]);
+
+        assert_eq!(k, expected);
+    }
+
+    /// The training covariance is diagonal even with duplicate points
+    #[test]
+    fn test_triangular() {
+        let kern = WhiteNoise::new(2.0);
+        let x = DMatrix::from_vec(1, 3, vec![1.0, 1.0, 3.0]);
+
+        let k = kern.call_triangular(&x, TriangleSide::LOWER).unwrap();
+
+        assert_eq!(k, DMatrix::identity(3, 3) * 2.0);
+    }
+
+    /// A white noise kernel gives the same likelihood and predictions as the GP noise,
+    /// except at the training inputs
+    #[test]
+    fn test_gp_noise() {
+        let x = DMatrix::from_vec(1, 3, vec![0.0, 0.5, 2.0]);
+        let y = nalgebra::DVector::from_vec(vec![0.4, -0.2, 1.1]);
+        let xp = DMatrix::from_vec(1, 2, vec![0.25, 1.5]);
+
+        let with_kernel = GP::new(RBF::new(vec![1.0], 1.0) + WhiteNoise::new(0.3), 0.0)
+            .compile(x.clone(), &y)
+            .unwrap();
+        let with_noise = GP::new(RBF::new(vec![1.0], 1.0), 0.3)
+            .compile(x.clone(), &y)
+            .unwrap();
+
+        let lml = with_kernel.log_marginal_likelihood() - with_noise.log_marginal_likelihood();
+        assert!(lml.abs() < 1e-12);
+
+        let a = with_kernel.mean(&xp).unwrap();
+        let b = with_noise.mean(&xp).unwrap();
+        assert!((a - b).amax() < 1e-12);
+
+        // at a training input, the white noise cross-covariance pulls the mean towards the observation
+        let a = with_kernel.mean(&x).unwrap();
+        let b = with_noise.mean(&x).unwrap();
+        for i in 0..3 {
+            assert!((a[i] - y[i]).abs() < (b[i] - y[i]).abs());
+        }
+    }
+}
@@ -2,4 +2,7 @@ pub mod gp;
 pub mod indexing;
 pub mod kernels;
 pub mod linalg;
+pub mod means;
 pub mod parameterized;
+pub mod sparse;
+pub mod stats;
@@ -19,11 +19,29 @@ pub unsafe fn par_add_diagonal_mut_unchecked(mat: &mut DMatrix<f64>, f: &f64) {
         });
 }
 
+/// Add a different value to each entry of the matrix diagonal, in-place, in parallel
+///
+/// # Safety
+/// unsafe if `mat` is not square, or `values` does not have one entry per row of `mat`
+pub unsafe fn par_add_diagonal_vec_mut_unchecked(mat: &mut DMatrix<f64>, values: &[f64]) {
+    let size = mat.shape().0;
+    if size == 0 {
+        return;
+    }
+
+    mat.as_mut_slice()
+        .par_chunks_exact_mut(size)
+        .enumerate()
+        .for_each(|(i, slice)| {
+            *(slice.get_unchecked_mut(i)) += values.get_unchecked(i);
+        });
+}
+
 #[cfg(test)]
 mod tests {
     use nalgebra::DMatrix;
 
-    use super::par_add_diagonal_mut_unchecked;
+    use super::{par_add_diagonal_mut_unchecked, par_add_diagonal_vec_mut_unchecked};
 
     #[test]
     fn test_empty() {
@@ -46,4 +64,19 @@ mod tests {
         unsafe { par_add_diagonal_mut_unchecked(&mut mat, &10.0_f64) }
         assert_eq!(mat.as_slice(), expected.as_slice());
     }
+
+    #[test]
+    #[rustfmt::skip]
+    fn test_square_vec() {
+        let mut mat = DMatrix::from_vec(3, 3, vec![
+            1.0, 0.0, 0.0,
+            0.0, 1.0, 0.0,
+            0.0, 0.0, 1.0]);
+        let expected = vec![
+            2.0, 0.0, 0.0,
+            0.0, 3.0, 0.0,
+            0.0, 0.0, 4.0];
+        unsafe { par_add_diagonal_vec_mut_unchecked(&mut mat, &[1.0, 2.0, 3.0]) }
+        assert_eq!(mat.as_slice(), expected.as_slice());
+    }
 }
@@ -0,0 +1,76 @@
+use std::fmt::{self, Debug};
+
+use nalgebra::{DMatrix, DVector};
+
+use crate::{
+    linalg::errors::IncompatibleShapeError,
+    parameterized::{Jacobian, Parameterized},
+};
+
+use super::{eval::par_call_points, mean::MeanFunction};
+
+/// Mean function defined by a closure evaluated at each point
+///
+/// The closure receives one point (a column of `x`) at a time, and is evaluated in parallel.
+/// Anything the closure captures is fixed, so it has no trainable parameters.
+///
+/// # Examples
+///
+/// ```rust
+/// use gprs::means::{Closure, MeanFunction};
+/// use nalgebra::DMatrix;
+///
+/// let mean = Closure::new(|point: &[f64]| point[0] * point[0]);
+/// let x = DMatrix::from_vec(1, 2, vec![2.0, 3.0]);
+///
+/// assert_eq!(mean.call(&x).unwrap().as_slice(), &[4.0, 9.0]);
+/// ```
+#[derive(Clone)]
+pub struct Closure<F> {
+    f: F,
+}
+
+impl<F> Closure<F>
+where
+    F: Fn(&[f64]) -> f64 + Sync,
+{
+    /// Create a new mean function from a closure of a single point
+    pub fn new(f: F) -> Self {
+        Closure { f }
+    }
+}
+
+impl<F> Debug for Closure<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Closure").finish_non_exhaustive()
+    }
+}
+
+impl<F> MeanFunction for Closure<F>
+where
+    F: Fn(&[f64]) -> f64 + Sync,
+{
+    fn call(&self, x: &DMatrix<f64>) -> Result<DVector<f64>, IncompatibleShapeError> {
+        Ok(par_call_points(x, &self.f))
+    }
+}
+
+impl<'a, F> Parameterized<'a> for Closure<F>
+where
+    F: Fn(&[f64]) -> f64 + Sync,
+{
+    fn get_params(&'a self) -> Vec<f64> {
+        Vec::new()
+    }
+
+    fn set_params(&'a mut self, _params: &[f64]) {}
+}
+
+impl<'a, F> Jacobian<'a> for Closure<F>
+where
+    F: Fn(&[f64]) -> f64 + Sync,
+{
+    fn jacobian(&'a self, x: &DMatrix<f64>) -> DMatrix<f64> {
+        DMatrix::zeros(x.ncols(), 0)
+    }
+}
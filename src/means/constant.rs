@@ -0,0 +1,72 @@
+use nalgebra::{DMatrix, DVector};
+
+use crate::{
+    linalg::errors::IncompatibleShapeError,
+    parameterized::{FromParams, Jacobian, Parameterized},
+};
+
+use super::mean::{MeanFunction, MeanGradient};
+
+/// Constant mean
+///
+/// `m = c`
+///
+/// The parameters are `[c]`.
+///
+/// # Examples
+///
+/// ```rust
+/// use gprs::means::{Constant, MeanFunction};
+/// use nalgebra::DMatrix;
+///
+/// let mean = Constant::new(3.0);
+/// let x = DMatrix::from_vec(1, 2, vec![0.0, 1.0]);
+///
+/// assert_eq!(mean.call(&x).unwrap().as_slice(), &[3.0, 3.0]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Constant {
+    value: f64,
+}
+
+impl Constant {
+    /// Create a new mean function from a constant value
+    pub fn new(value: f64) -> Self {
+        Constant { value }
+    }
+}
+
+impl MeanFunction for Constant {
+    fn call(&self, x: &DMatrix<f64>) -> Result<DVector<f64>, IncompatibleShapeError> {
+        Ok(DVector::from_element(x.ncols(), self.value))
+    }
+}
+
+impl MeanGradient for Constant {
+    fn gradient(&self, x: &DMatrix<f64>) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        Ok(DMatrix::zeros(x.nrows(), x.ncols()))
+    }
+}
+
+impl<'a> Parameterized<'a> for Constant {
+    fn get_params(&'a self) -> Vec<f64> {
+        vec![self.value]
+    }
+
+    fn set_params(&'a mut self, params: &[f64]) {
+        self.value = params[0];
+    }
+}
+
+impl FromParams for Constant {
+    fn from_params(params: &[f64]) -> Self {
+        Constant { value: params[0] }
+    }
+}
+
+impl<'a> Jacobian<'a> for Constant {
+    /// Compute the derivative of the mean at each column of `x` w.r.t. `c`, which is always 1
+    fn jacobian(&'a self, x: &DMatrix<f64>) -> DMatrix<f64> {
+        DMatrix::from_element(x.ncols(), 1, 1.0)
+    }
+}
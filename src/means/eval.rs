@@ -0,0 +1,89 @@
+//! Parallel evaluation of mean functions defined on single points
+
+use nalgebra::{DMatrix, DVector};
+use rayon::prelude::*;
+
+use crate::{indexing::slice_indices, linalg::errors::IncompatibleShapeError};
+
+/// Check that `x` has `dims` rows
+pub(crate) fn check_dims(dims: usize, x_shape: (usize, usize)) -> Result<(), IncompatibleShapeError> {
+    if x_shape.0 != dims {
+        return Err(IncompatibleShapeError {
+            shapes: vec![x_shape, (1, dims)],
+        });
+    }
+
+    Ok(())
+}
+
+/// Evaluate `f` at every column of `x`, in parallel
+pub(crate) fn par_call_points<F>(x: &DMatrix<f64>, f: F) -> DVector<f64>
+where
+    F: Fn(&[f64]) -> f64 + Sync,
+{
+    let dims = x.nrows();
+    let x_sl = x.as_slice();
+
+    let values: Vec<f64> = (0..x.ncols())
+        .into_par_iter()
+        .map(|i| {
+            let (xs, xe) = slice_indices(i, dims);
+            f(&x_sl[xs..xe])
+        })
+        .collect();
+
+    DVector::from_vec(values)
+}
+
+/// Evaluate the parameter gradient of a mean function at every column of `x`
+///
+/// `f` writes the gradient w.r.t. each of the `nparams` parameters at a point.
+/// The result has one row per point and one column per parameter.
+///
+/// # Panics
+/// If `x` does not have `dims` rows
+pub(crate) fn par_jacobian<F>(x: &DMatrix<f64>, dims: usize, nparams: usize, f: F) -> DMatrix<f64>
+where
+    F: Fn(&[f64], &mut [f64]) + Sync,
+{
+    assert_eq!(x.nrows(), dims, "x must have one row per mean function dimension");
+
+    let x_sl = x.as_slice();
+    let mut grads = DMatrix::<f64>::zeros(nparams, x.ncols());
+
+    grads
+        .as_mut_slice()
+        .par_chunks_exact_mut(nparams.max(1))
+        .enumerate()
+        .for_each(|(i, grad)| {
+            let (xs, xe) = slice_indices(i, dims);
+            f(&x_sl[xs..xe], grad);
+        });
+
+    grads.transpose()
+}
+
+/// Evaluate the input gradient of a mean function at every column of `x`
+///
+/// `f` writes the derivative w.r.t. each dimension of a point. The result has the same shape as `x`.
+///
+/// Shapes must already be checked with `check_dims`.
+pub(crate) fn par_gradient<F>(x: &DMatrix<f64>, f: F) -> DMatrix<f64>
+where
+    F: Fn(&[f64], &mut [f64]) + Sync,
+{
+    let dims = x.nrows();
+    let x_sl = x.as_slice();
+    let mut grads = DMatrix::<f64>::zeros(dims, x.ncols());
+
+    grads
+        .as_mut_slice()
+        .par_chunks_exact_mut(dims.max(1))
+        .enumerate()
+        .for_each(|(i, grad)| {
+            let (xs, xe) = slice_indices(i, dims);
+            f(&x_sl[xs..xe], grad);
+        });
+
+    grads
+}
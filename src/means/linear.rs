@@ -0,0 +1,206 @@
+use nalgebra::{DMatrix, DVector};
+
+use crate::{
+    linalg::errors::IncompatibleShapeError,
+    parameterized::{FromParams, Jacobian, Parameterized},
+};
+
+use super::{
+    eval::{check_dims, par_call_points, par_gradient, par_jacobian},
+    mean::{MeanFunction, MeanGradient},
+};
+
+/// Linear mean
+///
+/// `m = b + sum(w * x)`
+///
+/// where `w` is the slope in each dimension, and `b` is the intercept.
+///
+/// The parameters are `[b, w...]`.
+///
+/// # Examples
+///
+/// ```rust
+/// use gprs::means::{Linear, MeanFunction};
+/// use nalgebra::DMatrix;
+///
+/// let mean = Linear::new(vec![2.0, -1.0], 0.5);
+/// let x = DMatrix::from_vec(2, 2, vec![
+///     1.0, 1.0,
+///     0.0, 3.0,
+/// ]);
+///
+/// assert_eq!(mean.call(&x).unwrap().as_slice(), &[1.5, -2.5]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Linear {
+    slope: Vec<f64>,
+    intercept: f64,
+}
+
+impl Linear {
+    /// Create a new mean function from a slope per dimension and an intercept
+    pub fn new<I>(slope: I, intercept: f64) -> Self
+    where
+        I: IntoIterator<Item = f64>,
+    {
+        Linear {
+            slope: slope.into_iter().collect(),
+            intercept,
+        }
+    }
+}
+
+impl MeanFunction for Linear {
+    fn call(&self, x: &DMatrix<f64>) -> Result<DVector<f64>, IncompatibleShapeError> {
+        check_dims(self.slope.len(), x.shape())?;
+
+        Ok(par_call_points(x, |point| {
+            self.intercept
+                + self
+                    .slope
+                    .iter()
+                    .zip(point)
+                    .map(|(w, x)| w * x)
+                    .sum::<f64>()
+        }))
+    }
+}
+
+impl MeanGradient for Linear {
+    /// `dm/dx = w`
+    fn gradient(&self, x: &DMatrix<f64>) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        check_dims(self.slope.len(), x.shape())?;
+        Ok(par_gradient(x, |_point, grad| {
+            grad.copy_from_slice(&self.slope)
+        }))
+    }
+}
+
+impl<'a> Parameterized<'a> for Linear {
+    fn get_params(&'a self) -> Vec<f64> {
+        let mut params = Vec::with_capacity(1 + self.slope.len());
+        params.push(self.intercept);
+        params.extend(self.slope.iter());
+        params
+    }
+
+    /// # Panics
+    /// If the number of slopes changes
+    fn set_params(&'a mut self, params: &[f64]) {
+        self.intercept = params[0];
+        self.slope.copy_from_slice(&params[1..]);
+    }
+}
+
+impl FromParams for Linear {
+    fn from_params(params: &[f64]) -> Self {
+        Linear {
+            slope: params[1..].to_vec(),
+            intercept: params[0],
+        }
+    }
+}
+
+impl<'a> Jacobian<'a> for Linear {
+    /// Compute the derivative of the mean at each column of `x` w.r.t. each parameter
+    ///
+    /// Row `i` of the result is the gradient at point `i`, in `get_params()` order.
+    ///
+    /// # Panics
+    /// If `x` does not have one row per slope
+    fn jacobian(&'a self, x: &DMatrix<f64>) -> DMatrix<f64> {
+        let dims = self.slope.len();
+
+        par_jacobian(x, dims, 1 + dims, |point, grad| {
+            grad[0] = 1.0;
+            grad[1..].copy_from_slice(point);
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::DMatrix;
+
+    use crate::{
+        means::{MeanFunction, MeanGradient},
+        parameterized::{Jacobian, Parameterized},
+    };
+
+    use super::Linear;
+
+    fn points() -> DMatrix<f64> {
+        DMatrix::from_vec(2, 3, vec![0.0, 1.0, 0.5, -0.3, 1.2, 0.4])
+    }
+
+    /// Passing data with the wrong number of dimensions will return an error
+    #[test]
+    fn test_mismatched() {
+        let mean = Linear::new(vec![1.0], 0.0);
+        let x = DMatrix::from_vec(2, 1, vec![1.0, 1.0]);
+
+        assert!(mean.call(&x).is_err());
+    }
+
+    /// Setting a different number of slopes would change the dimensions of the mean
+    #[test]
+    #[should_panic]
+    fn test_set_params_mismatched() {
+        let mut mean = Linear::new(vec![1.0, 2.0], 0.0);
+        mean.set_params(&[0.0, 1.0]);
+    }
+
+    /// The jacobian matches a finite-difference approximation
+    #[test]
+    fn test_jacobian_finite_difference() {
+        let mean = Linear::new(vec![2.0, -1.0], 0.5);
+        let x = points();
+
+        let jac = mean.jacobian(&x);
+        let params = mean.get_params();
+
+        let eps = 1e-6;
+        for p in 0..params.len() {
+            let mut up = mean.clone();
+            let mut v = params.clone();
+            v[p] += eps;
+            up.set_params(&v);
+
+            let mut down = mean.clone();
+            let mut v = params.clone();
+            v[p] -= eps;
+            down.set_params(&v);
+
+            let fd = (up.call(&x).unwrap() - down.call(&x).unwrap()) / (2.0 * eps);
+
+            for (a, b) in jac.column(p).iter().zip(fd.iter()) {
+                assert!((a - b).abs() < 1e-6, "{} != {}", a, b);
+            }
+        }
+    }
+
+    /// The input gradient matches a finite-difference approximation
+    #[test]
+    fn test_gradient_finite_difference() {
+        let mean = Linear::new(vec![2.0, -1.0], 0.5);
+        let x = points();
+
+        let grad = mean.gradient(&x).unwrap();
+        assert_eq!(grad.shape(), x.shape());
+
+        let eps = 1e-6;
+        for d in 0..2 {
+            let mut up = x.clone();
+            up.row_mut(d).add_scalar_mut(eps);
+            let mut down = x.clone();
+            down.row_mut(d).add_scalar_mut(-eps);
+
+            let fd = (mean.call(&up).unwrap() - mean.call(&down).unwrap()) / (2.0 * eps);
+
+            for (a, b) in grad.row(d).iter().zip(fd.iter()) {
+                assert!((a - b).abs() < 1e-6, "{} != {}", a, b);
+            }
+        }
+    }
+}
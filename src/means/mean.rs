@@ -0,0 +1,19 @@
+use nalgebra::{DMatrix, DVector};
+
+use crate::linalg::errors::IncompatibleShapeError;
+
+/// The prior mean of a gaussian process
+///
+/// The GP models the residuals `y - m(x)`, and predictions revert to `m(x)` far from the data.
+pub trait MeanFunction {
+    /// Evaluate the prior mean at each column of `x`
+    fn call(&self, x: &DMatrix<f64>) -> Result<DVector<f64>, IncompatibleShapeError>;
+}
+
+/// Derivatives of a mean function w.r.t. its input
+pub trait MeanGradient: MeanFunction {
+    /// Compute the derivative of the prior mean at each column of `x` w.r.t. that column
+    ///
+    /// The result has the same shape as `x`.
+    fn gradient(&self, x: &DMatrix<f64>) -> Result<DMatrix<f64>, IncompatibleShapeError>;
+}
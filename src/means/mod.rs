@@ -0,0 +1,14 @@
+mod closure;
+mod constant;
+mod eval;
+mod linear;
+mod mean;
+mod polynomial;
+mod zero;
+
+pub use closure::*;
+pub use constant::*;
+pub use linear::*;
+pub use mean::*;
+pub use polynomial::*;
+pub use zero::*;
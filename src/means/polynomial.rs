@@ -0,0 +1,241 @@
+use nalgebra::{DMatrix, DVector};
+
+use crate::{
+    linalg::errors::IncompatibleShapeError,
+    parameterized::{Jacobian, Parameterized},
+};
+
+use super::{
+    eval::{check_dims, par_call_points, par_gradient, par_jacobian},
+    mean::{MeanFunction, MeanGradient},
+};
+
+/// Polynomial trend mean
+///
+/// `m = b + sum_k sum(w_k * x^k)`, for `k = 1..=degree`
+///
+/// where `w_k` has a coefficient per dimension for the `k`th power, and `b` is the intercept.
+/// There are no cross terms between dimensions.
+///
+/// The parameters are `[b, w_1..., w_2..., ...]`.
+///
+/// # Examples
+///
+/// ```rust
+/// use gprs::means::{MeanFunction, Polynomial};
+/// use nalgebra::DMatrix;
+///
+/// // m = 1 + 2x - x^2
+/// let mean = Polynomial::new(vec![vec![2.0], vec![-1.0]], 1.0);
+/// let x = DMatrix::from_vec(1, 2, vec![0.0, 3.0]);
+///
+/// assert_eq!(mean.call(&x).unwrap().as_slice(), &[1.0, -2.0]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Polynomial {
+    /// Coefficients of each power, with all dimensions of a power next to each other
+    coefficients: Vec<f64>,
+    dims: usize,
+    intercept: f64,
+}
+
+impl Polynomial {
+    /// Create a new mean function from the coefficients of each power, starting at 1, and an intercept
+    ///
+    /// # Panics
+    /// If the powers do not all have the same number of coefficients
+    pub fn new<I>(coefficients: I, intercept: f64) -> Self
+    where
+        I: IntoIterator<Item = Vec<f64>>,
+    {
+        let powers: Vec<Vec<f64>> = coefficients.into_iter().collect();
+        let dims = powers.first().map_or(0, |w| w.len());
+
+        assert!(
+            powers.iter().all(|w| w.len() == dims),
+            "every power must have one coefficient per dimension"
+        );
+
+        Polynomial {
+            coefficients: powers.concat(),
+            dims,
+            intercept,
+        }
+    }
+
+    /// Create a polynomial with all coefficients set to zero, ready to be trained
+    pub fn zeros(degree: usize, dims: usize) -> Self {
+        Polynomial {
+            coefficients: vec![0.0; degree * dims],
+            dims,
+            intercept: 0.0,
+        }
+    }
+
+    /// The highest power of the polynomial
+    pub fn degree(&self) -> usize {
+        match self.dims {
+            0 => 0,
+            dims => self.coefficients.len() / dims,
+        }
+    }
+
+    /// Compute the mean at a point
+    fn call_point(&self, point: &[f64]) -> f64 {
+        let mut value = self.intercept;
+        let mut power = point.to_vec();
+
+        for w in self.coefficients.chunks_exact(self.dims.max(1)) {
+            value += w.iter().zip(&power).map(|(w, p)| w * p).sum::<f64>();
+            power.iter_mut().zip(point).for_each(|(p, x)| *p *= x);
+        }
+
+        value
+    }
+}
+
+impl MeanFunction for Polynomial {
+    fn call(&self, x: &DMatrix<f64>) -> Result<DVector<f64>, IncompatibleShapeError> {
+        check_dims(self.dims, x.shape())?;
+        Ok(par_call_points(x, |point| self.call_point(point)))
+    }
+}
+
+impl MeanGradient for Polynomial {
+    /// `dm/dx = sum_k k * w_k * x^(k - 1)`
+    fn gradient(&self, x: &DMatrix<f64>) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        check_dims(self.dims, x.shape())?;
+
+        Ok(par_gradient(x, |point, grad| {
+            let mut power = vec![1.0; point.len()];
+
+            for (k, w) in self.coefficients.chunks_exact(self.dims.max(1)).enumerate() {
+                grad.iter_mut()
+                    .zip(w.iter().zip(&power))
+                    .for_each(|(g, (w, p))| *g += (k + 1) as f64 * w * p);
+                power.iter_mut().zip(point).for_each(|(p, x)| *p *= x);
+            }
+        }))
+    }
+}
+
+impl<'a> Parameterized<'a> for Polynomial {
+    fn get_params(&'a self) -> Vec<f64> {
+        let mut params = Vec::with_capacity(1 + self.coefficients.len());
+        params.push(self.intercept);
+        params.extend(self.coefficients.iter());
+        params
+    }
+
+    /// # Panics
+    /// If the number of coefficients changes
+    fn set_params(&'a mut self, params: &[f64]) {
+        self.intercept = params[0];
+        self.coefficients.copy_from_slice(&params[1..]);
+    }
+}
+
+impl<'a> Jacobian<'a> for Polynomial {
+    /// Compute the derivative of the mean at each column of `x` w.r.t. each parameter
+    ///
+    /// Row `i` of the result is the gradient at point `i`, in `get_params()` order.
+    ///
+    /// `dm/db = 1`
+    ///
+    /// `dm/dw_k = x^k`
+    ///
+    /// # Panics
+    /// If `x` does not have one row per dimension
+    fn jacobian(&'a self, x: &DMatrix<f64>) -> DMatrix<f64> {
+        par_jacobian(
+            x,
+            self.dims,
+            1 + self.coefficients.len(),
+            |point, grad| {
+                grad[0] = 1.0;
+
+                let mut power = point.to_vec();
+                for g in grad[1..].chunks_exact_mut(self.dims.max(1)) {
+                    g.copy_from_slice(&power);
+                    power.iter_mut().zip(point).for_each(|(p, x)| *p *= x);
+                }
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::DMatrix;
+
+    use crate::{
+        means::{MeanFunction, MeanGradient},
+        parameterized::{Jacobian, Parameterized},
+    };
+
+    use super::Polynomial;
+
+    /// Each power is applied to every dimension separately
+    #[test]
+    fn test_correctness() {
+        let mean = Polynomial::new(vec![vec![1.0, 2.0], vec![0.5, -1.0]], 3.0);
+        let x = DMatrix::from_vec(2, 1, vec![2.0, 3.0]);
+
+        let expected = 3.0 + 2.0 + 6.0 + 0.5 * 4.0 - 9.0;
+        assert_eq!(mean.call(&x).unwrap()[0], expected);
+        assert_eq!(mean.degree(), 2);
+    }
+
+    /// The jacobian matches a finite-difference approximation
+    #[test]
+    fn test_jacobian_finite_difference() {
+        let mean = Polynomial::new(vec![vec![1.0, 2.0], vec![0.5, -1.0], vec![0.1, 0.2]], 3.0);
+        let x = DMatrix::from_vec(2, 3, vec![0.0, 1.0, 0.5, -0.3, 1.2, 0.4]);
+
+        let jac = mean.jacobian(&x);
+        let params = mean.get_params();
+
+        let eps = 1e-6;
+        for p in 0..params.len() {
+            let mut up = mean.clone();
+            let mut v = params.clone();
+            v[p] += eps;
+            up.set_params(&v);
+
+            let mut down = mean.clone();
+            let mut v = params.clone();
+            v[p] -= eps;
+            down.set_params(&v);
+
+            let fd = (up.call(&x).unwrap() - down.call(&x).unwrap()) / (2.0 * eps);
+
+            for (a, b) in jac.column(p).iter().zip(fd.iter()) {
+                assert!((a - b).abs() < 1e-6, "{} != {}", a, b);
+            }
+        }
+    }
+
+    /// The input gradient matches a finite-difference approximation
+    #[test]
+    fn test_gradient_finite_difference() {
+        let mean = Polynomial::new(vec![vec![1.0, 2.0], vec![0.5, -1.0], vec![0.1, 0.2]], 3.0);
+        let x = DMatrix::from_vec(2, 3, vec![0.0, 1.0, 0.5, -0.3, 1.2, 0.4]);
+
+        let grad = mean.gradient(&x).unwrap();
+        assert_eq!(grad.shape(), x.shape());
+
+        let eps = 1e-6;
+        for d in 0..2 {
+            let mut up = x.clone();
+            up.row_mut(d).add_scalar_mut(eps);
+            let mut down = x.clone();
+            down.row_mut(d).add_scalar_mut(-eps);
+
+            let fd = (mean.call(&up).unwrap() - mean.call(&down).unwrap()) / (2.0 * eps);
+
+            for (a, b) in grad.row(d).iter().zip(fd.iter()) {
+                assert!((a - b).abs() < 1e-6, "{} != {}", a, b);
+            }
+        }
+    }
+}
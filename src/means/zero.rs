@@ -0,0 +1,48 @@
+use nalgebra::{DMatrix, DVector};
+
+use crate::{
+    linalg::errors::IncompatibleShapeError,
+    parameterized::{FromParams, Jacobian, Parameterized},
+};
+
+use super::mean::{MeanFunction, MeanGradient};
+
+/// Zero mean
+///
+/// `m = 0`
+///
+/// This is the default prior mean of a GP. It has no parameters.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Zero;
+
+impl MeanFunction for Zero {
+    fn call(&self, x: &DMatrix<f64>) -> Result<DVector<f64>, IncompatibleShapeError> {
+        Ok(DVector::zeros(x.ncols()))
+    }
+}
+
+impl MeanGradient for Zero {
+    fn gradient(&self, x: &DMatrix<f64>) -> Result<DMatrix<f64>, IncompatibleShapeError> {
+        Ok(DMatrix::zeros(x.nrows(), x.ncols()))
+    }
+}
+
+impl<'a> Parameterized<'a> for Zero {
+    fn get_params(&'a self) -> Vec<f64> {
+        Vec::new()
+    }
+
+    fn set_params(&'a mut self, _params: &[f64]) {}
+}
+
+impl FromParams for Zero {
+    fn from_params(_params: &[f64]) -> Self {
+        Zero
+    }
+}
+
+impl<'a> Jacobian<'a> for Zero {
+    fn jacobian(&'a self, x: &DMatrix<f64>) -> DMatrix<f64> {
+        DMatrix::zeros(x.ncols(), 0)
+    }
+}
@@ -5,6 +5,13 @@ pub trait Parameterized<'a> {
     fn get_params(&'a self) -> Vec<f64>;
     /// Set the model parameters
     fn set_params(&'a mut self, params: &[f64]);
+}
+
+/// Models that can be created from their parameters alone
+///
+/// Models whose structure is not part of their parameters, like the degree of a polynomial or the split
+/// between the parameters of a sum, do not implement this. Use `set_params` on an existing model instead.
+pub trait FromParams: Sized {
     /// Create a new model from parameters
     fn from_params(params: &[f64]) -> Self;
 }
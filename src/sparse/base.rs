@@ -0,0 +1,788 @@
+use std::f64::consts::PI;
+
+use nalgebra::{Cholesky, DMatrix, DVector, Dynamic};
+
+use crate::{
+    gp::{
+        cholesky_with_jitter, errors::GPCompilationError, GPResult, JitterPolicy, Noise, Prediction,
+    },
+    kernels::{Kernel, KernelGradient, KernelJacobian, TriangleSide},
+    linalg::{
+        errors::IncompatibleShapeError, par_solve_lower_triangular_unchecked, par_tr_matmul,
+        par_tr_matmul_diag,
+    },
+    means::{MeanFunction, Zero},
+};
+
+/// How the inducing inputs approximate the covariance of the training data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Approximation {
+    /// Variational free energy (Titsias, 2009).
+    ///
+    /// The objective is a lower bound on the exact log marginal likelihood,
+    /// so adding or moving inducing inputs to improve it always brings the model closer to the exact GP.
+    #[default]
+    VFE,
+    /// Fully independent training conditional (Snelson & Ghahramani, 2006).
+    ///
+    /// The diagonal of the approximate covariance is corrected to match the kernel,
+    /// which gives wider predictions far from the inducing inputs but tends to underestimate the noise.
+    FITC,
+}
+
+/// Sparse Gaussian Process
+///
+/// Definition:
+///
+/// `f = m* + Q*f [Qff + L]^-1 (y - m)`
+///
+/// `cov = K** - Q** + Q*f [Qff + L]^-1 Qf*`
+///
+/// where `Qab = Kau Kuu^-1 Kub` is the covariance explained by the inducing inputs `u`,
+/// and `L` is the noise covariance, plus `diag(Kff - Qff)` for FITC.
+///
+/// Unlike `GP`, jitter from the default `JitterPolicy` is used unless set with `with_jitter`,
+/// since `Kuu` has no noise and is often close to singular.
+///
+/// # Examples
+/// ```rust
+/// use gprs::{kernels::RBF, sparse::SparseGP};
+/// use nalgebra::{DMatrix, DVector};
+///
+/// let x = DMatrix::from_fn(1, 1000, |_, j| j as f64 * 0.01);
+/// let y = DVector::from_iterator(x.ncols(), x.iter().map(|v| v.sin()));
+///
+/// // summarize 1000 points with 10
+/// let inducing = DMatrix::from_fn(1, 10, |_, j| j as f64 * 1.1);
+///
+/// let compiled = SparseGP::new(RBF::new(vec![1.0], 1.0), 0.01, inducing)
+///     .compile(x, &y)
+///     .unwrap();
+///
+/// let x_test = DMatrix::from_vec(1, 2, vec![2.5, 7.5]);
+/// let mean = compiled.mean(&x_test).unwrap();
+/// assert!((mean[0] - 2.5_f64.sin()).abs() < 0.05);
+/// ```
+#[derive(Debug)]
+pub struct SparseGP<K: Kernel, M: MeanFunction = Zero> {
+    kernel: K,
+    noise: Noise,
+    mean: M,
+    inducing: DMatrix<f64>,
+    approximation: Approximation,
+    jitter: Option<JitterPolicy>,
+}
+
+impl<K: Kernel> SparseGP<K> {
+    /// Create a sparse GP with the VFE approximation and inducing inputs in the columns of `inducing`
+    pub fn new(kernel: K, noise: f64, inducing: DMatrix<f64>) -> Self {
+        SparseGP {
+            kernel,
+            noise: Noise::Constant(noise),
+            mean: Zero,
+            inducing,
+            approximation: Approximation::default(),
+            jitter: Some(JitterPolicy::default()),
+        }
+    }
+}
+
+impl<K: Kernel, M: MeanFunction> SparseGP<K, M> {
+    /// Set the prior mean function
+    pub fn with_mean<N: MeanFunction>(self, mean: N) -> SparseGP<K, N> {
+        SparseGP {
+            kernel: self.kernel,
+            noise: self.noise,
+            mean,
+            inducing: self.inducing,
+            approximation: self.approximation,
+            jitter: self.jitter,
+        }
+    }
+
+    /// Set the noise variance, either constant or one per training point
+    pub fn with_noise<N: Into<Noise>>(self, noise: N) -> Self {
+        SparseGP {
+            noise: noise.into(),
+            ..self
+        }
+    }
+
+    /// Set the approximation of the training covariance
+    pub fn with_approximation(self, approximation: Approximation) -> Self {
+        SparseGP {
+            approximation,
+            ..self
+        }
+    }
+
+    /// Set the jitter policy used to factorize `Kuu`
+    pub fn with_jitter(self, policy: JitterPolicy) -> Self {
+        SparseGP {
+            jitter: Some(policy),
+            ..self
+        }
+    }
+
+    /// The covariance kernel
+    pub fn kernel(&self) -> &K {
+        &self.kernel
+    }
+
+    /// The noise variance of the training data
+    pub fn noise(&self) -> &Noise {
+        &self.noise
+    }
+
+    /// The prior mean function
+    pub fn mean_function(&self) -> &M {
+        &self.mean
+    }
+
+    /// The inducing inputs, one per column
+    pub fn inducing(&self) -> &DMatrix<f64> {
+        &self.inducing
+    }
+
+    /// The approximation of the training covariance
+    pub fn approximation(&self) -> Approximation {
+        self.approximation
+    }
+
+    /// The jitter policy used to factorize `Kuu`
+    pub fn jitter_policy(&self) -> Option<&JitterPolicy> {
+        self.jitter.as_ref()
+    }
+
+    /// Create a sparse GP with the same mean function, approximation and jitter policy
+    pub(crate) fn rebuild(&self, kernel: K, noise: Noise, inducing: DMatrix<f64>) -> Self
+    where
+        M: Clone,
+    {
+        SparseGP {
+            kernel,
+            noise,
+            mean: self.mean.clone(),
+            inducing,
+            approximation: self.approximation,
+            jitter: self.jitter,
+        }
+    }
+
+    /// Compile this sparse GP for training or estimation. Consumes `self` and `x`.
+    ///
+    /// With `A = Luu^-1 Kuf`, only the `m x m` matrices `Kuu` and `B = I + A L^-1 A'` are factorized.
+    pub fn compile(
+        self,
+        x: DMatrix<f64>,
+        y: &DVector<f64>,
+    ) -> Result<CompiledSparseGP<K, M>, GPCompilationError> {
+        if x.shape().1 != y.len() {
+            return Err(GPCompilationError::IncompatibleShapeError(
+                IncompatibleShapeError {
+                    shapes: vec![x.shape(), y.shape()],
+                },
+            ));
+        }
+        let n = y.len();
+
+        let kuu = self
+            .kernel
+            .call_triangular(&self.inducing, TriangleSide::LOWER)
+            .map_err(GPCompilationError::IncompatibleShapeError)?;
+        let (luu, jitter) = cholesky_with_jitter(kuu, self.jitter.as_ref())?;
+
+        let kuf = self
+            .kernel
+            .call(&self.inducing, &x)
+            .map_err(GPCompilationError::IncompatibleShapeError)?;
+        let a = par_solve_lower_triangular_unchecked(luu.l_dirty(), &kuf);
+
+        let kff = self
+            .kernel
+            .call_diagonal(&x)
+            .map(DVector::from_vec)
+            .map_err(GPCompilationError::IncompatibleShapeError)?;
+        let qff = DVector::from_iterator(n, a.column_iter().map(|col| col.norm_squared()));
+
+        let mut noise_var = DVector::zeros(n);
+        self.noise
+            .add_to(&mut noise_var)
+            .map_err(GPCompilationError::IncompatibleShapeError)?;
+
+        let lambda = match self.approximation {
+            Approximation::VFE => noise_var.clone(),
+            Approximation::FITC => &noise_var + &kff - &qff,
+        };
+        if lambda.iter().any(|l| *l <= 0.0) {
+            return Err(GPCompilationError::NonPositiveDefiniteError);
+        }
+
+        let mut scaled = a.clone();
+        scaled
+            .column_iter_mut()
+            .zip(lambda.iter())
+            .for_each(|(mut col, l)| col /= l.sqrt());
+        let b = outer(&scaled, &scaled).map_err(GPCompilationError::IncompatibleShapeError)?
+            + DMatrix::identity(a.nrows(), a.nrows());
+        let lb = b
+            .cholesky()
+            .ok_or(GPCompilationError::NonPositiveDefiniteError)?;
+
+        let residual = y - self
+            .mean
+            .call(&x)
+            .map_err(GPCompilationError::IncompatibleShapeError)?;
+        let c = lb
+            .l_dirty()
+            .solve_lower_triangular_unchecked(&(&a * residual.component_div(&lambda)));
+
+        Ok(CompiledSparseGP {
+            luu,
+            lb,
+            a,
+            c,
+            lambda,
+            noise_var,
+            kff,
+            qff,
+            kernel: self.kernel,
+            noise: self.noise,
+            jitter,
+            mean: self.mean,
+            inducing: self.inducing,
+            approximation: self.approximation,
+            x,
+            y: residual,
+        })
+    }
+}
+
+/// Compute `lhs rhs'`
+pub(super) fn outer(lhs: &DMatrix<f64>, rhs: &DMatrix<f64>) -> GPResult<DMatrix<f64>> {
+    // the transposes have contiguous columns of length n, which par_tr_matmul reduces over
+    let res = par_tr_matmul(&lhs.transpose(), &rhs.transpose())?;
+    Ok(DMatrix::from_vec(lhs.nrows(), rhs.nrows(), res))
+}
+
+#[derive(Debug)]
+pub struct CompiledSparseGP<K: Kernel, M: MeanFunction = Zero> {
+    /// The cholesky decomposition of (Kuu + jitter * I)
+    luu: Cholesky<f64, Dynamic>,
+    /// The cholesky decomposition of (I + A L^-1 A')
+    lb: Cholesky<f64, Dynamic>,
+    /// Luu^-1 Kuf
+    a: DMatrix<f64>,
+    /// Factor to compute mean, Lb^-1 A L^-1 (y - m)
+    c: DVector<f64>,
+    /// The diagonal of L
+    lambda: DVector<f64>,
+    /// The noise variance of each training point
+    noise_var: DVector<f64>,
+    /// The diagonal of Kff
+    kff: DVector<f64>,
+    /// The diagonal of Qff
+    qff: DVector<f64>,
+    /// The original kernel
+    kernel: K,
+    /// The noise variance
+    noise: Noise,
+    /// The jitter added to the diagonal of Kuu to make it positive definite
+    jitter: f64,
+    /// The prior mean function
+    mean: M,
+    /// The inducing inputs
+    inducing: DMatrix<f64>,
+    approximation: Approximation,
+    /// The input data set
+    x: DMatrix<f64>,
+    /// The output data set, less the prior mean
+    y: DVector<f64>,
+}
+
+impl<K: Kernel, M: MeanFunction> CompiledSparseGP<K, M> {
+    /// The covariance kernel
+    pub fn kernel(&self) -> &K {
+        &self.kernel
+    }
+
+    /// The noise variance of the training data
+    pub fn noise(&self) -> &Noise {
+        &self.noise
+    }
+
+    /// The prior mean function
+    pub fn mean_function(&self) -> &M {
+        &self.mean
+    }
+
+    /// The inducing inputs, one per column
+    pub fn inducing(&self) -> &DMatrix<f64> {
+        &self.inducing
+    }
+
+    /// The approximation of the training covariance
+    pub fn approximation(&self) -> Approximation {
+        self.approximation
+    }
+
+    /// The jitter that was added to the diagonal of `Kuu` during compilation
+    pub fn jitter(&self) -> f64 {
+        self.jitter
+    }
+
+    /// Compute the objective maximized by training: the VFE lower bound on the log marginal likelihood,
+    /// or the FITC approximate log marginal likelihood.
+    ///
+    /// `log p(y) = -0.5 (y - m)' [Qff + L]^-1 (y - m) - 0.5 log|Qff + L| - 0.5 n log(2 pi)`,
+    /// less `0.5 tr(S^-1 (Kff - Qff))` for VFE, where `S` is the noise covariance.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use gprs::{gp::GP, kernels::RBF, sparse::SparseGP};
+    /// use nalgebra::{DMatrix, DVector};
+    ///
+    /// let x = DMatrix::from_fn(1, 50, |_, j| j as f64 * 0.2);
+    /// let y = DVector::from_iterator(x.ncols(), x.iter().map(|v| v.sin()));
+    /// let kernel = RBF::new(vec![1.0], 1.0);
+    ///
+    /// let exact = GP::new(kernel.clone(), 0.01).compile(x.clone(), &y).unwrap();
+    ///
+    /// let few = DMatrix::from_fn(1, 3, |_, j| j as f64 * 4.0);
+    /// let many = DMatrix::from_fn(1, 10, |_, j| j as f64 * 1.1);
+    /// let few = SparseGP::new(kernel.clone(), 0.01, few).compile(x.clone(), &y).unwrap();
+    /// let many = SparseGP::new(kernel, 0.01, many).compile(x, &y).unwrap();
+    ///
+    /// assert!(few.log_marginal_likelihood() < many.log_marginal_likelihood());
+    /// assert!(many.log_marginal_likelihood() <= exact.log_marginal_likelihood());
+    /// ```
+    pub fn log_marginal_likelihood(&self) -> f64 {
+        let n = self.y.len() as f64;
+        // by the matrix determinant lemma, log|Qff + L| = log|L| + log|B|
+        let half_log_det = self
+            .lb
+            .l_dirty()
+            .diagonal()
+            .iter()
+            .map(|v| v.ln())
+            .sum::<f64>()
+            + 0.5 * self.lambda.iter().map(|v| v.ln()).sum::<f64>();
+        // by the woodbury identity, r' [Qff + L]^-1 r = r' L^-1 r - c'c
+        let data_fit = self
+            .y
+            .iter()
+            .zip(self.lambda.iter())
+            .map(|(r, l)| r * r / l)
+            .sum::<f64>()
+            - self.c.norm_squared();
+
+        let lml = -0.5 * data_fit - half_log_det - 0.5 * n * (2.0 * PI).ln();
+
+        match self.approximation {
+            Approximation::VFE => lml - 0.5 * self.trace_correction().sum(),
+            Approximation::FITC => lml,
+        }
+    }
+
+    /// Compute the gradient of the objective w.r.t. the noise variance
+    ///
+    /// With per-point noise, this is the gradient w.r.t. a constant added to every noise variance.
+    pub fn log_marginal_likelihood_noise_gradient(&self) -> f64 {
+        let (_, g) = self.diagonal_weights();
+        let grad = g.sum();
+
+        match self.approximation {
+            Approximation::VFE => {
+                grad + 0.5 * self.trace_correction().component_div(&self.noise_var).sum()
+            }
+            Approximation::FITC => grad,
+        }
+    }
+
+    /// Compute `(Kff_ii - Qff_ii) / s_i`, the terms of the VFE trace correction
+    fn trace_correction(&self) -> DVector<f64> {
+        (&self.kff - &self.qff).component_div(&self.noise_var)
+    }
+
+    /// Compute `b = [Qff + L]^-1 (y - m)` and `g = 0.5 (b^2 - diag([Qff + L]^-1))`,
+    /// the gradient of the objective w.r.t. the diagonal of `L`
+    fn diagonal_weights(&self) -> (DVector<f64>, DVector<f64>) {
+        // B^-1 A L^-1 r = Lb^-T c
+        let b_inv_c = self
+            .lb
+            .l_dirty()
+            .tr_solve_lower_triangular_unchecked(&self.c);
+        let beta = (&self.y - self.a.tr_mul(&b_inv_c)).component_div(&self.lambda);
+
+        // diag([Qff + L]^-1)_i = 1 / l_i - |Lb^-1 a_i|^2 / l_i^2
+        let v = par_solve_lower_triangular_unchecked(self.lb.l_dirty(), &self.a);
+        let g = DVector::from_iterator(
+            beta.len(),
+            v.column_iter()
+                .zip(beta.iter())
+                .zip(self.lambda.iter())
+                .map(|((v, b), l)| 0.5 * (b * b - 1.0 / l + v.norm_squared() / (l * l))),
+        );
+
+        (beta, g)
+    }
+
+    /// Compute the mean and variance from input data
+    pub fn call(&self, x: &DMatrix<f64>) -> GPResult<(DVector<f64>, DVector<f64>)> {
+        let (tmp1, tmp2) = self.project(x)?;
+
+        let mean = self.mean_precomputed(x, &tmp2)?;
+        let var = self.var_precomputed(x, &tmp1, &tmp2)?;
+
+        Ok((mean, var))
+    }
+
+    /// Predict the latent function at `x`, without observation noise
+    pub fn predict(&self, x: &DMatrix<f64>) -> GPResult<Prediction> {
+        let (mean, var) = self.call(x)?;
+        Ok(Prediction { mean, var })
+    }
+
+    /// Compute the mean from input data
+    ///
+    /// `f = m* + K*u Luu^-T Lb^-T c`
+    pub fn mean(&self, x: &DMatrix<f64>) -> GPResult<DVector<f64>> {
+        let (_, tmp2) = self.project(x)?;
+        self.mean_precomputed(x, &tmp2)
+    }
+
+    /// Compute just the diagonal variance
+    pub fn var(&self, x: &DMatrix<f64>) -> GPResult<DVector<f64>> {
+        let (tmp1, tmp2) = self.project(x)?;
+        self.var_precomputed(x, &tmp1, &tmp2)
+    }
+
+    /// Compute the full covariance matrix from input data
+    ///
+    /// `V = K** - T1' T1 + T2' T2`, where `T1 = Luu^-1 Ku*` and `T2 = Lb^-1 T1`
+    pub fn cov(&self, x: &DMatrix<f64>) -> GPResult<DMatrix<f64>> {
+        let (tmp1, tmp2) = self.project(x)?;
+
+        let mut k_xp_xp = self.kernel.call(x, x)?;
+        let explained = par_tr_matmul(&tmp1, &tmp1)?;
+        let unexplained = par_tr_matmul(&tmp2, &tmp2)?;
+
+        k_xp_xp
+            .iter_mut()
+            .zip(explained)
+            .zip(unexplained)
+            .for_each(|((k, e), u)| *k += u - e);
+
+        Ok(k_xp_xp)
+    }
+
+    /// Compute `T1 = Luu^-1 Ku*` and `T2 = Lb^-1 T1`
+    fn project(&self, x: &DMatrix<f64>) -> GPResult<(DMatrix<f64>, DMatrix<f64>)> {
+        let k_u_xp = self.kernel.call(&self.inducing, x)?;
+        let tmp1 = par_solve_lower_triangular_unchecked(self.luu.l_dirty(), &k_u_xp);
+        let tmp2 = par_solve_lower_triangular_unchecked(self.lb.l_dirty(), &tmp1);
+        Ok((tmp1, tmp2))
+    }
+
+    /// Find the mean given a precomputed T2
+    fn mean_precomputed(&self, x: &DMatrix<f64>, tmp2: &DMatrix<f64>) -> GPResult<DVector<f64>> {
+        let res = par_tr_matmul(tmp2, &self.c)?;
+        Ok(self.mean.call(x)? + DVector::from_vec(res))
+    }
+
+    /// Find the variance given precomputed T1 and T2
+    fn var_precomputed(
+        &self,
+        x: &DMatrix<f64>,
+        tmp1: &DMatrix<f64>,
+        tmp2: &DMatrix<f64>,
+    ) -> GPResult<DVector<f64>> {
+        let k_xp_xp = self.kernel.call_diagonal(x)?;
+        let explained = par_tr_matmul_diag(tmp1, tmp1)?;
+        let unexplained = par_tr_matmul_diag(tmp2, tmp2)?;
+
+        Ok(DVector::from_iterator(
+            k_xp_xp.len(),
+            k_xp_xp
+                .into_iter()
+                .zip(explained)
+                .zip(unexplained)
+                // rounding error can make the variance slightly negative near the inducing inputs
+                .map(|((k, e), u)| (k - e + u).max(0.0)),
+        ))
+    }
+
+    /// Compute the gradient of the objective w.r.t. `W = Kuf`, `U = Kuu` and the diagonal of `Kff`
+    ///
+    /// With `h` the weight of `diag(Qff)` in the objective,
+    ///
+    /// `dL/dW = Luu^-T [A b b' - B^-1 A L^-1 + 2 A diag(h)]`
+    ///
+    /// `dL/dU = Luu^-T [0.5 (I - B^-1 - A b b' A') - A diag(h) A'] Luu^-1`
+    ///
+    /// `dL/dKff_ii = -h_i`
+    fn covariance_gradients(&self) -> GPResult<(DMatrix<f64>, DMatrix<f64>, DVector<f64>)> {
+        let (beta, g) = self.diagonal_weights();
+        let h = match self.approximation {
+            Approximation::VFE => self.noise_var.map(|s| 0.5 / s),
+            Approximation::FITC => -g,
+        };
+
+        let a_beta = &self.a * &beta;
+        let b_inv = self.lb.inverse();
+
+        let mut dw = &b_inv * &self.a;
+        dw.column_iter_mut()
+            .zip(self.a.column_iter())
+            .zip(self.lambda.iter().zip(h.iter()))
+            .for_each(|((mut col, a), (l, h))| {
+                col /= -l;
+                col.axpy(2.0 * h, &a, 1.0);
+            });
+        dw.ger(1.0, &a_beta, &beta, 1.0);
+        let dw = self.luu.l_dirty().tr_solve_lower_triangular_unchecked(&dw);
+
+        let mut ah = self.a.clone();
+        ah.column_iter_mut()
+            .zip(h.iter())
+            .for_each(|(mut col, h)| col *= *h);
+        let mut du = -outer(&ah, &self.a)? - 0.5 * b_inv;
+        du.ger(-0.5, &a_beta, &a_beta, 1.0);
+        for i in 0..du.nrows() {
+            du[(i, i)] += 0.5;
+        }
+        let du = self.luu.l_dirty().tr_solve_lower_triangular_unchecked(&du);
+        let du = self
+            .luu
+            .l_dirty()
+            .tr_solve_lower_triangular_unchecked(&du.transpose())
+            .transpose();
+
+        Ok((dw, du, -h))
+    }
+}
+
+impl<K, M> CompiledSparseGP<K, M>
+where
+    K: KernelGradient,
+    M: MeanFunction,
+{
+    /// Compute the gradient of the objective w.r.t. the inducing inputs, in the same shape as `inducing`
+    ///
+    /// The gradients w.r.t. `Kuf` and `Kuu` are chained through the kernel gradient w.r.t. its inputs.
+    pub fn log_marginal_likelihood_inducing_gradient(&self) -> GPResult<DMatrix<f64>> {
+        let (dw, du, _) = self.covariance_gradients()?;
+
+        // dk(z_k, x_j)/dz_kd is entry (j, k) of the kernel gradient of (X, Z),
+        // and Kuu depends on z_k through both row and column k
+        let grad_xz = self.kernel.gradient(&self.x, &self.inducing)?;
+        let grad_zz = self.kernel.gradient(&self.inducing, &self.inducing)?;
+
+        let mut grad = DMatrix::zeros(self.inducing.nrows(), self.inducing.ncols());
+        for (d, (gx, gz)) in grad_xz.iter().zip(&grad_zz).enumerate() {
+            let row = dw.transpose().component_mul(gx).row_sum()
+                + 2.0 * du.transpose().component_mul(gz).row_sum();
+            grad.row_mut(d).copy_from(&row);
+        }
+
+        Ok(grad)
+    }
+}
+
+impl<K, M> CompiledSparseGP<K, M>
+where
+    K: KernelJacobian,
+    M: MeanFunction,
+{
+    /// Compute the gradient of the objective w.r.t. the kernel parameters, in the order of `get_params()`
+    ///
+    /// The gradients w.r.t. `Kuf`, `Kuu` and the diagonal of `Kff` are chained through the kernel jacobians,
+    /// so this costs about as much as one compilation per parameter, without any factorization.
+    pub fn log_marginal_likelihood_kernel_gradient(&self) -> GPResult<Vec<f64>> {
+        let (dw, du, dkff) = self.covariance_gradients()?;
+
+        let jac_uf = self.kernel.cross_jacobian(&self.inducing, &self.x)?;
+        let jac_uu = self.kernel.jacobian(&self.inducing);
+        let jac_ff = self.kernel.diagonal_jacobian(&self.x)?;
+
+        let grad = jac_uf.tr_mul(&DVector::from_column_slice(dw.as_slice()))
+            + jac_uu.tr_mul(&DVector::from_column_slice(du.as_slice()))
+            + jac_ff.tr_mul(&dkff);
+
+        Ok(grad.iter().copied().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DMatrix, DVector};
+
+    use crate::{
+        gp::{errors::GPCompilationError, GP},
+        kernels::RBF,
+        parameterized::{FromParams, Parameterized},
+    };
+
+    use super::{Approximation, SparseGP};
+
+    fn data() -> (DMatrix<f64>, DVector<f64>) {
+        let x = DMatrix::from_fn(2, 12, |i, j| ((i + 1) * j) as f64 * 0.37 % 3.0);
+        let y = DVector::from_fn(12, |i, _| (i as f64 * 0.8).sin());
+        (x, y)
+    }
+
+    /// With the training inputs as inducing inputs, both approximations are exact
+    #[test]
+    fn test_matches_exact() {
+        let (x, y) = data();
+        let kernel = RBF::new(vec![1.0, 0.7], 1.2);
+        let x_test = DMatrix::from_fn(2, 4, |i, j| (i + j) as f64 * 0.4);
+
+        let exact = GP::new(kernel.clone(), 0.05)
+            .compile(x.clone(), &y)
+            .unwrap();
+
+        for approximation in [Approximation::VFE, Approximation::FITC] {
+            let sparse = SparseGP::new(kernel.clone(), 0.05, x.clone())
+                .with_approximation(approximation)
+                .compile(x.clone(), &y)
+                .unwrap();
+            assert_eq!(sparse.jitter(), 0.0);
+
+            assert!(
+                (sparse.log_marginal_likelihood() - exact.log_marginal_likelihood()).abs() < 1e-8
+            );
+
+            let (mean, var) = sparse.call(&x_test).unwrap();
+            let (exact_mean, exact_var) = exact.call(&x_test).unwrap();
+            assert!((mean - exact_mean).amax() < 1e-8);
+            assert!((var - exact_var).amax() < 1e-8);
+            assert!((sparse.cov(&x_test).unwrap() - exact.cov(&x_test).unwrap()).amax() < 1e-8);
+        }
+    }
+
+    /// Fewer inducing inputs lower the VFE bound, and FITC keeps the prior variance
+    #[test]
+    fn test_approximations() {
+        let (x, y) = data();
+        let kernel = RBF::new(vec![1.0, 0.7], 1.2);
+        let inducing = x.columns(0, 3).into_owned();
+
+        let exact = GP::new(kernel.clone(), 0.05)
+            .compile(x.clone(), &y)
+            .unwrap();
+        let vfe = SparseGP::new(kernel.clone(), 0.05, inducing.clone())
+            .compile(x.clone(), &y)
+            .unwrap();
+        assert!(vfe.log_marginal_likelihood() < exact.log_marginal_likelihood());
+
+        // far from every inducing input, the prediction reverts to the prior
+        let far = DMatrix::from_vec(2, 1, vec![50.0, 50.0]);
+        let fitc = SparseGP::new(kernel, 0.05, inducing)
+            .with_approximation(Approximation::FITC)
+            .compile(x, &y)
+            .unwrap();
+        assert!((fitc.var(&far).unwrap()[0] - 1.44).abs() < 1e-12);
+        assert!(fitc.mean(&far).unwrap()[0].abs() < 1e-12);
+    }
+
+    /// The analytic gradients match central finite differences
+    #[test]
+    fn test_gradients_finite_difference() {
+        let (x, y) = data();
+        let kernel = RBF::new(vec![1.0, 0.7], 1.2);
+        let inducing = DMatrix::from_vec(2, 3, vec![0.2, 0.5, 1.4, 2.1, 2.5, 0.9]);
+        let step = 1e-6;
+
+        for approximation in [Approximation::VFE, Approximation::FITC] {
+            let lml = |inducing: &DMatrix<f64>, noise: f64| {
+                SparseGP::new(kernel.clone(), noise, inducing.clone())
+                    .with_approximation(approximation)
+                    .compile(x.clone(), &y)
+                    .unwrap()
+                    .log_marginal_likelihood()
+            };
+            let compiled = SparseGP::new(kernel.clone(), 0.1, inducing.clone())
+                .with_approximation(approximation)
+                .compile(x.clone(), &y)
+                .unwrap();
+
+            let grad = compiled
+                .log_marginal_likelihood_inducing_gradient()
+                .unwrap();
+            assert_eq!(grad.shape(), inducing.shape());
+            for i in 0..inducing.len() {
+                let (mut upper, mut lower) = (inducing.clone(), inducing.clone());
+                upper[i] += step;
+                lower[i] -= step;
+                let fd = (lml(&upper, 0.1) - lml(&lower, 0.1)) / (2.0 * step);
+                assert!(
+                    (grad[i] - fd).abs() < 1e-5,
+                    "{:?} inducing {}: {} != {}",
+                    approximation,
+                    i,
+                    grad[i],
+                    fd
+                );
+            }
+
+            let fd = (lml(&inducing, 0.1 + step) - lml(&inducing, 0.1 - step)) / (2.0 * step);
+            let grad = compiled.log_marginal_likelihood_noise_gradient();
+            assert!(
+                (grad - fd).abs() < 1e-5,
+                "{:?} noise: {} != {}",
+                approximation,
+                grad,
+                fd
+            );
+
+            let grad = compiled.log_marginal_likelihood_kernel_gradient().unwrap();
+            let params = kernel.get_params();
+            assert_eq!(grad.len(), params.len());
+            for p in 0..params.len() {
+                let with_param = |delta: f64| {
+                    let mut v = params.clone();
+                    v[p] += delta;
+                    SparseGP::new(RBF::from_params(&v), 0.1, inducing.clone())
+                        .with_approximation(approximation)
+                        .compile(x.clone(), &y)
+                        .unwrap()
+                        .log_marginal_likelihood()
+                };
+                let fd = (with_param(step) - with_param(-step)) / (2.0 * step);
+                assert!(
+                    (grad[p] - fd).abs() < 1e-5,
+                    "{:?} kernel {}: {} != {}",
+                    approximation,
+                    p,
+                    grad[p],
+                    fd
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_shapes() {
+        let (x, y) = data();
+        let kernel = RBF::new(vec![1.0, 0.7], 1.2);
+
+        let short = DVector::from_vec(vec![1.0, 2.0]);
+        let err = SparseGP::new(kernel.clone(), 0.1, x.clone())
+            .compile(x.clone(), &short)
+            .unwrap_err();
+        assert!(matches!(err, GPCompilationError::IncompatibleShapeError(_)));
+
+        let inducing = DMatrix::from_vec(1, 2, vec![0.0, 1.0]);
+        let err = SparseGP::new(kernel.clone(), 0.1, inducing)
+            .compile(x.clone(), &y)
+            .unwrap_err();
+        assert!(matches!(err, GPCompilationError::IncompatibleShapeError(_)));
+
+        // the VFE noise must be positive
+        let err = SparseGP::new(kernel, 0.0, x.clone())
+            .compile(x, &y)
+            .unwrap_err();
+        assert_eq!(err, GPCompilationError::NonPositiveDefiniteError);
+    }
+}
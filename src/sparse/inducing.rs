@@ -0,0 +1,312 @@
+//! Initial inducing inputs chosen from the training inputs
+//!
+//! Each initializer takes the training inputs one per column, and returns `m` inducing inputs in the same layout.
+//! The randomized initializers are deterministic for a seeded `rng`.
+
+use nalgebra::{DMatrix, DVector};
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    seq::index,
+    Rng,
+};
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+
+use crate::{gp::GPResult, kernels::Kernel, linalg::errors::IncompatibleShapeError};
+
+/// Check that `m` inducing inputs can be chosen from the columns of `x`
+fn check_count(x: &DMatrix<f64>, m: usize) -> GPResult<()> {
+    if m > x.ncols() {
+        return Err(IncompatibleShapeError {
+            shapes: vec![x.shape(), (x.nrows(), m)],
+        });
+    }
+    Ok(())
+}
+
+/// Choose `m` distinct columns of `x` uniformly at random
+///
+/// # Examples
+/// ```rust
+/// use gprs::sparse::inducing::uniform_subset;
+/// use nalgebra::DMatrix;
+/// use rand::{rngs::StdRng, SeedableRng};
+///
+/// let x = DMatrix::from_fn(2, 100, |i, j| (i * 100 + j) as f64);
+///
+/// let first = uniform_subset(&x, 10, &mut StdRng::seed_from_u64(0)).unwrap();
+/// let second = uniform_subset(&x, 10, &mut StdRng::seed_from_u64(0)).unwrap();
+/// assert_eq!(first, second);
+/// assert_eq!(first.shape(), (2, 10));
+/// ```
+pub fn uniform_subset<R>(x: &DMatrix<f64>, m: usize, rng: &mut R) -> GPResult<DMatrix<f64>>
+where
+    R: Rng + ?Sized,
+{
+    check_count(x, m)?;
+
+    let indices = index::sample(rng, x.ncols(), m).into_vec();
+    Ok(x.select_columns(&indices))
+}
+
+/// Cluster the columns of `x` into `m` groups with k-means, returning the cluster centers
+///
+/// The centers are seeded with k-means++, which picks each new center with probability proportional
+/// to its squared distance from the nearest existing center. They are then refined with up to `iterations`
+/// rounds of Lloyd's algorithm, stopping early when no point changes cluster.
+/// A cluster that loses all its points keeps its previous center.
+///
+/// # Examples
+/// ```rust
+/// use gprs::sparse::inducing::kmeans;
+/// use nalgebra::DMatrix;
+/// use rand::{rngs::StdRng, SeedableRng};
+///
+/// // two tight clusters, around 0 and 10
+/// let x = DMatrix::from_fn(1, 40, |_, j| (j % 2) as f64 * 10.0 + (j / 2) as f64 * 0.01);
+///
+/// let centers = kmeans(&x, 2, 20, &mut StdRng::seed_from_u64(0)).unwrap();
+/// let (lo, hi) = (centers.min(), centers.max());
+/// assert!((lo - 0.095).abs() < 1e-9 && (hi - 10.095).abs() < 1e-9);
+/// ```
+pub fn kmeans<R>(
+    x: &DMatrix<f64>,
+    m: usize,
+    iterations: usize,
+    rng: &mut R,
+) -> GPResult<DMatrix<f64>>
+where
+    R: Rng + ?Sized,
+{
+    check_count(x, m)?;
+    let n = x.ncols();
+    if m == 0 {
+        return Ok(DMatrix::zeros(x.nrows(), 0));
+    }
+
+    // k-means++ seeding
+    let mut centers = DMatrix::zeros(x.nrows(), m);
+    centers.set_column(0, &x.column(rng.gen_range(0..n)));
+    let mut distances = nearest_distances(x, &centers.columns(0, 1).into_owned());
+
+    for k in 1..m {
+        let next = match WeightedIndex::new(&distances) {
+            Ok(weights) => weights.sample(rng),
+            // every point is already a center, so any choice is as good
+            Err(_) => rng.gen_range(0..n),
+        };
+        centers.set_column(k, &x.column(next));
+
+        let added = nearest_distances(x, &centers.columns(k, 1).into_owned());
+        distances
+            .iter_mut()
+            .zip(added)
+            .for_each(|(d, a)| *d = d.min(a));
+    }
+
+    // Lloyd's algorithm
+    let mut assignments: Option<Vec<usize>> = None;
+    for _ in 0..iterations {
+        let nearest = nearest_centers(x, &centers);
+        if assignments.as_ref() == Some(&nearest) {
+            break;
+        }
+
+        let mut sums = DMatrix::zeros(x.nrows(), m);
+        let mut counts = vec![0usize; m];
+        for (point, cluster) in x.column_iter().zip(&nearest) {
+            let mut sum = sums.column_mut(*cluster);
+            sum += point;
+            counts[*cluster] += 1;
+        }
+        for (k, count) in counts.iter().enumerate() {
+            if *count > 0 {
+                centers.set_column(k, &(sums.column(k) / *count as f64));
+            }
+        }
+
+        assignments = Some(nearest);
+    }
+
+    Ok(centers)
+}
+
+/// The squared distance from each column of `x` to its nearest column of `centers`
+fn nearest_distances(x: &DMatrix<f64>, centers: &DMatrix<f64>) -> Vec<f64> {
+    (0..x.ncols())
+        .into_par_iter()
+        .map(|j| {
+            centers
+                .column_iter()
+                .map(|c| (x.column(j) - c).norm_squared())
+                .fold(f64::INFINITY, f64::min)
+        })
+        .collect()
+}
+
+/// The index of the nearest column of `centers` to each column of `x`, choosing the first on ties
+fn nearest_centers(x: &DMatrix<f64>, centers: &DMatrix<f64>) -> Vec<usize> {
+    (0..x.ncols())
+        .into_par_iter()
+        .map(|j| {
+            centers
+                .column_iter()
+                .map(|c| (x.column(j) - c).norm_squared())
+                .enumerate()
+                .fold(
+                    (0, f64::INFINITY),
+                    |best, (k, d)| {
+                        if d < best.1 {
+                            (k, d)
+                        } else {
+                            best
+                        }
+                    },
+                )
+                .0
+        })
+        .collect()
+}
+
+/// Choose `m` columns of `x` by greedy variance reduction
+///
+/// This is a pivoted cholesky decomposition of `K(x, x)`: each step picks the point whose
+/// prior variance is least explained by the points already chosen, then conditions on it.
+/// It costs `O(n m^2)` time and `O(n m)` memory, and never forms the full kernel matrix.
+/// There is no randomness, and ties go to the first column.
+///
+/// # Examples
+/// ```rust
+/// use gprs::{kernels::RBF, sparse::{inducing::greedy_variance, SparseGP}};
+/// use nalgebra::{DMatrix, DVector};
+///
+/// let x = DMatrix::from_fn(1, 500, |_, j| j as f64 * 0.02);
+/// let y = DVector::from_iterator(500, x.iter().map(|v| v.sin()));
+/// let kernel = RBF::new(vec![1.0], 1.0);
+///
+/// let inducing = greedy_variance(&kernel, &x, 10).unwrap();
+/// let compiled = SparseGP::new(kernel, 0.01, inducing).compile(x, &y).unwrap();
+/// ```
+pub fn greedy_variance<K: Kernel>(
+    kernel: &K,
+    x: &DMatrix<f64>,
+    m: usize,
+) -> GPResult<DMatrix<f64>> {
+    check_count(x, m)?;
+    let n = x.ncols();
+
+    // the variance of each point, given the points chosen so far
+    let mut variance = DVector::from_vec(kernel.call_diagonal(x)?);
+    // row k holds the k-th column of the partial cholesky factor
+    let mut factor = DMatrix::zeros(m, n);
+    let mut chosen = vec![false; n];
+    let mut pivots = Vec::with_capacity(m);
+
+    for k in 0..m {
+        let pivot = (0..n)
+            .filter(|j| !chosen[*j])
+            .fold(None, |best: Option<usize>, j| match best {
+                Some(b) if variance[b] >= variance[j] => Some(b),
+                _ => Some(j),
+            })
+            .unwrap();
+        chosen[pivot] = true;
+        pivots.push(pivot);
+
+        let pivot_variance = variance[pivot];
+        if pivot_variance <= 0.0 {
+            // the remaining points are fully explained, so the factor gains nothing
+            continue;
+        }
+
+        // the covariance with the pivot, less the part explained by the previous pivots
+        let cov = kernel.call(x, &x.columns(pivot, 1).into_owned())?;
+        let previous = factor.rows(0, k);
+        let mut row = cov.column(0) - previous.tr_mul(&previous.column(pivot));
+        row /= pivot_variance.sqrt();
+
+        variance
+            .iter_mut()
+            .zip(row.iter())
+            .for_each(|(v, r)| *v -= r * r);
+        factor.set_row(k, &row.transpose());
+    }
+
+    Ok(x.select_columns(&pivots))
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DMatrix, DVector};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{kernels::RBF, sparse::SparseGP};
+
+    use super::{greedy_variance, kmeans, uniform_subset};
+
+    fn grid() -> DMatrix<f64> {
+        DMatrix::from_fn(2, 200, |i, j| if i == 0 { (j % 20) as f64 } else { (j / 20) as f64 } * 0.5)
+    }
+
+    /// Every initializer returns distinct training inputs, or errors when there are too few
+    #[test]
+    fn test_subsets() {
+        let x = grid();
+        let kernel = RBF::new(vec![1.0, 1.0], 1.0);
+        let mut rng = StdRng::seed_from_u64(3);
+
+        for inducing in [
+            uniform_subset(&x, 15, &mut rng).unwrap(),
+            greedy_variance(&kernel, &x, 15).unwrap(),
+        ] {
+            assert_eq!(inducing.shape(), (2, 15));
+            for (k, z) in inducing.column_iter().enumerate() {
+                assert!(x.column_iter().any(|col| col == z));
+                assert!(inducing.column_iter().skip(k + 1).all(|other| other != z));
+            }
+        }
+
+        assert!(uniform_subset(&x, 201, &mut rng).is_err());
+        assert!(greedy_variance(&kernel, &x, 201).is_err());
+        assert!(kmeans(&x, 201, 10, &mut rng).is_err());
+    }
+
+    /// The same seed gives the same inducing inputs
+    #[test]
+    fn test_seeded() {
+        let x = grid();
+        let run = |seed| kmeans(&x, 8, 10, &mut StdRng::seed_from_u64(seed)).unwrap();
+
+        assert_eq!(run(1), run(1));
+        assert_ne!(run(1), run(2));
+    }
+
+    /// Greedy variance reduction spreads the points out, and explains the data better than a random subset
+    #[test]
+    fn test_greedy_variance() {
+        let x = grid();
+        let kernel = RBF::new(vec![5.0, 5.0], 1.0);
+
+        let inducing = greedy_variance(&kernel, &x, 4).unwrap();
+        // the first pivot is the first point, and the next is the far corner
+        assert_eq!(inducing.column(0), x.column(0));
+        assert_eq!(inducing.column(1), x.column(199));
+
+        // the largest posterior variance at the training inputs
+        let worst_variance = |inducing: DMatrix<f64>| {
+            SparseGP::new(kernel.clone(), 1e-6, inducing)
+                .compile(x.clone(), &DVector::zeros(200))
+                .unwrap()
+                .var(&x)
+                .unwrap()
+                .max()
+        };
+        let greedy = worst_variance(greedy_variance(&kernel, &x, 12).unwrap());
+        let random = worst_variance(uniform_subset(&x, 12, &mut StdRng::seed_from_u64(0)).unwrap());
+        assert!(greedy < random, "{} >= {}", greedy, random);
+
+        // duplicates leave no variance to explain, and are not chosen while others remain
+        let duplicated = DMatrix::from_vec(1, 4, vec![0.0, 0.0, 0.0, 5.0]);
+        let inducing = greedy_variance(&RBF::new(vec![1.0], 1.0), &duplicated, 2).unwrap();
+        assert_eq!(inducing, DMatrix::from_vec(1, 2, vec![0.0, 5.0]));
+    }
+}
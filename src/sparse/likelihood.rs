@@ -0,0 +1,292 @@
+use std::{f64::consts::PI, sync::OnceLock};
+
+use nalgebra::{DMatrix, SymmetricEigen};
+
+use crate::{
+    parameterized::Parameterized,
+    stats::{erfc, ln_gamma, normal_cdf},
+};
+
+/// The number of Gauss-Hermite points used for expectations without a closed form
+const QUADRATURE_POINTS: usize = 20;
+
+/// Below this, `log Phi(z)` uses its asymptotic series, because `erfc` underflows near `z = -38`
+const PROBIT_TAIL: f64 = -30.0;
+
+/// The distribution of an observation `y` given the latent function value `f`
+pub trait Likelihood {
+    /// Compute `log p(y | f)`, and its first and second derivatives w.r.t. `f`
+    fn log_density(&self, y: f64, f: f64) -> (f64, f64, f64);
+
+    /// Compute `E[log p(y | f)]` for `f ~ N(mean, var)`, and its derivatives w.r.t. `mean` and `var`
+    ///
+    /// The default uses Gauss-Hermite quadrature, with `dE/dmean = E[d log p / df]`
+    /// and `dE/dvar = 0.5 E[d^2 log p / df^2]`.
+    fn variational_expectation(&self, y: f64, mean: f64, var: f64) -> (f64, f64, f64) {
+        let (nodes, weights) = gauss_hermite();
+        let scale = (2.0 * var).sqrt();
+
+        nodes
+            .iter()
+            .zip(weights)
+            .fold((0.0, 0.0, 0.0), |(e, dmean, dvar), (t, w)| {
+                let (value, d1, d2) = self.log_density(y, mean + scale * t);
+                (e + w * value, dmean + w * d1, dvar + 0.5 * w * d2)
+            })
+    }
+
+    /// Compute the mean and variance of `y` for `f ~ N(mean, var)`
+    fn predict(&self, mean: f64, var: f64) -> (f64, f64);
+}
+
+/// Derivatives of the expected log likelihood w.r.t. the likelihood parameters
+pub trait LikelihoodGradient: Likelihood + for<'a> Parameterized<'a> {
+    /// Compute the derivative of `E[log p(y | f)]` for `f ~ N(mean, var)` w.r.t. each parameter,
+    /// in the order of `get_params()`
+    fn variational_expectation_gradient(&self, y: f64, mean: f64, var: f64) -> Vec<f64>;
+}
+
+/// Gauss-Hermite nodes and weights, normalized so the weights sum to one
+///
+/// With `f = mean + sqrt(2 var) t`, `E[g(f)] ~ sum_k w_k g(f_k)`.
+fn gauss_hermite() -> &'static (Vec<f64>, Vec<f64>) {
+    static QUADRATURE: OnceLock<(Vec<f64>, Vec<f64>)> = OnceLock::new();
+
+    QUADRATURE.get_or_init(|| {
+        // Golub-Welsch: the nodes are the eigenvalues of the Jacobi matrix of the Hermite polynomials
+        let n = QUADRATURE_POINTS;
+        let jacobi = DMatrix::from_fn(n, n, |i, j| {
+            if i.abs_diff(j) == 1 {
+                (i.max(j) as f64 / 2.0).sqrt()
+            } else {
+                0.0
+            }
+        });
+        let eigen = SymmetricEigen::new(jacobi);
+
+        let mut points: Vec<(f64, f64)> = eigen
+            .eigenvalues
+            .iter()
+            .zip(eigen.eigenvectors.row(0).iter())
+            .map(|(t, v)| (*t, v * v))
+            .collect();
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        points.into_iter().unzip()
+    })
+}
+
+/// Normal observation noise with variance `noise`, `y ~ N(f, noise)`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gaussian {
+    /// The variance of the observation noise
+    pub noise: f64,
+}
+
+impl Gaussian {
+    /// Create a likelihood with noise variance `noise`
+    pub fn new(noise: f64) -> Self {
+        Gaussian { noise }
+    }
+}
+
+impl Likelihood for Gaussian {
+    fn log_density(&self, y: f64, f: f64) -> (f64, f64, f64) {
+        let error = y - f;
+        (
+            -0.5 * (error * error / self.noise + (2.0 * PI * self.noise).ln()),
+            error / self.noise,
+            -1.0 / self.noise,
+        )
+    }
+
+    /// `E = -0.5 ((y - mean)^2 + var) / noise - 0.5 log(2 pi noise)`
+    fn variational_expectation(&self, y: f64, mean: f64, var: f64) -> (f64, f64, f64) {
+        let error = y - mean;
+        (
+            -0.5 * ((error * error + var) / self.noise + (2.0 * PI * self.noise).ln()),
+            error / self.noise,
+            -0.5 / self.noise,
+        )
+    }
+
+    fn predict(&self, mean: f64, var: f64) -> (f64, f64) {
+        (mean, var + self.noise)
+    }
+}
+
+impl<'a> Parameterized<'a> for Gaussian {
+    fn get_params(&'a self) -> Vec<f64> {
+        vec![self.noise]
+    }
+
+    fn set_params(&'a mut self, params: &[f64]) {
+        self.noise = params[0];
+    }
+}
+
+impl LikelihoodGradient for Gaussian {
+    /// `dE/dnoise = 0.5 ((y - mean)^2 + var) / noise^2 - 0.5 / noise`
+    fn variational_expectation_gradient(&self, y: f64, mean: f64, var: f64) -> Vec<f64> {
+        let error = y - mean;
+        vec![0.5 * ((error * error + var) / self.noise - 1.0) / self.noise]
+    }
+}
+
+/// Binary classification with a probit link, `p(y = 1 | f) = Phi(f)` for `y` in `{0, 1}`
+///
+/// Predictions are the probability that `y = 1`, and its Bernoulli variance.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Bernoulli;
+
+impl Likelihood for Bernoulli {
+    fn log_density(&self, y: f64, f: f64) -> (f64, f64, f64) {
+        let sign = if y > 0.5 { 1.0 } else { -1.0 };
+        let z = sign * f;
+
+        let log_pdf = -0.5 * z * z - 0.5 * (2.0 * PI).ln();
+        let (log_cdf, ratio) = if z < PROBIT_TAIL {
+            // Phi(z) = phi(z) / -z * (1 - 1/z^2 + 3/z^4 - 15/z^6 + ...)
+            let w = 1.0 / (z * z);
+            let series = 1.0 - w * (1.0 - w * (3.0 - 15.0 * w));
+            (log_pdf - (-z).ln() + series.ln(), -z / series)
+        } else {
+            // computed through erfc to keep its precision for negative z
+            let log_cdf = (0.5 * erfc(-z / 2.0_f64.sqrt())).ln();
+            // the inverse mills ratio phi(z) / Phi(z)
+            (log_cdf, (log_pdf - log_cdf).exp())
+        };
+
+        (log_cdf, sign * ratio, -ratio * (z + ratio))
+    }
+
+    /// `p(y = 1) = Phi(mean / sqrt(1 + var))`
+    fn predict(&self, mean: f64, var: f64) -> (f64, f64) {
+        let p = normal_cdf(mean / (1.0 + var).sqrt());
+        (p, p * (1.0 - p))
+    }
+}
+
+impl<'a> Parameterized<'a> for Bernoulli {
+    fn get_params(&'a self) -> Vec<f64> {
+        Vec::new()
+    }
+
+    fn set_params(&'a mut self, _params: &[f64]) {}
+}
+
+impl LikelihoodGradient for Bernoulli {
+    fn variational_expectation_gradient(&self, _y: f64, _mean: f64, _var: f64) -> Vec<f64> {
+        Vec::new()
+    }
+}
+
+/// Counts with a log link, `y ~ Poisson(exp(f))`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Poisson;
+
+impl Likelihood for Poisson {
+    fn log_density(&self, y: f64, f: f64) -> (f64, f64, f64) {
+        let rate = f.exp();
+        (y * f - rate - ln_gamma(y + 1.0), y - rate, -rate)
+    }
+
+    /// The rate is log-normal, so `E[y] = exp(mean + var / 2)` and `V[y] = E[y] + (exp(var) - 1) E[y]^2`
+    fn predict(&self, mean: f64, var: f64) -> (f64, f64) {
+        let rate = (mean + 0.5 * var).exp();
+        (rate, rate + var.exp_m1() * rate * rate)
+    }
+}
+
+impl<'a> Parameterized<'a> for Poisson {
+    fn get_params(&'a self) -> Vec<f64> {
+        Vec::new()
+    }
+
+    fn set_params(&'a mut self, _params: &[f64]) {}
+}
+
+impl LikelihoodGradient for Poisson {
+    fn variational_expectation_gradient(&self, _y: f64, _mean: f64, _var: f64) -> Vec<f64> {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stats::{ln_gamma, normal_cdf};
+
+    use super::{gauss_hermite, Bernoulli, Gaussian, Likelihood, Poisson};
+
+    /// The quadrature integrates polynomials exactly
+    #[test]
+    fn test_quadrature() {
+        let (nodes, weights) = gauss_hermite();
+
+        let moment =
+            |k: i32| -> f64 { nodes.iter().zip(weights).map(|(t, w)| w * t.powi(k)).sum() };
+
+        // t = z / sqrt(2) for a standard normal z
+        assert!((moment(0) - 1.0).abs() < 1e-12);
+        assert!(moment(1).abs() < 1e-12);
+        assert!((moment(2) - 0.5).abs() < 1e-12);
+        assert!((moment(4) - 0.75).abs() < 1e-12);
+    }
+
+    /// The quadrature matches the closed form expectations
+    #[test]
+    fn test_expectations() {
+        struct Quadrature<L: Likelihood>(L);
+
+        impl<L: Likelihood> Likelihood for Quadrature<L> {
+            fn log_density(&self, y: f64, f: f64) -> (f64, f64, f64) {
+                self.0.log_density(y, f)
+            }
+
+            fn predict(&self, mean: f64, var: f64) -> (f64, f64) {
+                self.0.predict(mean, var)
+            }
+        }
+
+        let gaussian = Gaussian::new(0.3);
+        let quadrature = Quadrature(gaussian).variational_expectation(1.2, 0.4, 0.8);
+        let closed = gaussian.variational_expectation(1.2, 0.4, 0.8);
+        assert!((quadrature.0 - closed.0).abs() < 1e-10);
+        assert!((quadrature.1 - closed.1).abs() < 1e-10);
+        assert!((quadrature.2 - closed.2).abs() < 1e-10);
+
+        // E[y f - exp(f)] = y mean - exp(mean + var / 2)
+        let (y, mean, var) = (3.0, 0.7, 0.2);
+        let (e, dmean, dvar) = Poisson.variational_expectation(y, mean, var);
+        let rate = (mean + 0.5 * var).exp();
+        assert!((e - (y * mean - rate - ln_gamma(y + 1.0))).abs() < 1e-10);
+        assert!((dmean - (y - rate)).abs() < 1e-10);
+        assert!((dvar + 0.5 * rate).abs() < 1e-10);
+    }
+
+    /// The probit derivatives match finite differences, including far into the tail
+    #[test]
+    fn test_bernoulli() {
+        let step = 1e-6;
+        for y in [0.0, 1.0] {
+            for f in [-100.0, -45.0, -40.0, -30.5, -29.5, -3.0, 0.0, 2.0, 25.0] {
+                let (value, d1, d2) = Bernoulli.log_density(y, f);
+                let (upper, du, _) = Bernoulli.log_density(y, f + step);
+                let (lower, dl, _) = Bernoulli.log_density(y, f - step);
+
+                assert!(value.is_finite());
+                assert!((d1 - (upper - lower) / (2.0 * step)).abs() < 1e-5 * d1.abs().max(1.0));
+                assert!((d2 - (du - dl) / (2.0 * step)).abs() < 1e-5 * d2.abs().max(1.0));
+            }
+        }
+
+        // the asymptotic series agrees with erfc where erfc is still representable
+        for z in [-30.5, -35.0] {
+            let (value, _, _) = Bernoulli.log_density(1.0, z);
+            assert!((value / normal_cdf(z).ln() - 1.0).abs() < 1e-12);
+        }
+
+        let (p, _) = Bernoulli.predict(0.0, 1.0);
+        assert_eq!(p, 0.5);
+    }
+}
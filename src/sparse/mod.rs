@@ -0,0 +1,14 @@
+//! Sparse gaussian processes, which summarize the training data with `m` inducing inputs
+//!
+//! Compiling a `SparseGP` costs `O(n m^2)` time and `O(n m)` memory rather than `O(n^3)` and `O(n^2)`.
+//! An `SVGP` is trained from minibatches, so each step is independent of the size of the data,
+//! and supports non-gaussian likelihoods.
+
+mod base;
+pub mod inducing;
+mod likelihood;
+pub mod optimize;
+mod svgp;
+pub use base::*;
+pub use likelihood::*;
+pub use svgp::*;
@@ -0,0 +1,327 @@
+//! Optimization of sparse gaussian processes
+//!
+//! The inducing inputs, kernel parameters and noise are fit together by maximizing the VFE bound
+//! or the FITC approximate log marginal likelihood with L-BFGS, using analytic gradients.
+//! Kernel parameters and the noise are searched in log space, like the exact GP optimizer.
+
+use nalgebra::{DMatrix, DVector};
+
+use crate::{
+    gp::{
+        errors::GPCompilationError,
+        optimize::{
+            lbfgs::{minimize, LbfgsOptions},
+            LogMagnitudes,
+        },
+        Noise,
+    },
+    kernels::{KernelGradient, KernelJacobian},
+    means::MeanFunction,
+    parameterized::Parameterized,
+};
+
+use super::{CompiledSparseGP, SparseGP};
+
+/// Settings for sparse GP optimization
+#[derive(Debug, Clone)]
+pub struct SparseOptimizeOptions {
+    /// Bounds `(lower, upper)` on the magnitude of each kernel parameter, in `get_params` order.
+    ///
+    /// `None` applies `default_bounds` to every parameter.
+    pub bounds: Option<Vec<(f64, f64)>>,
+    /// Bounds on the magnitude of kernel parameters when `bounds` is `None`
+    pub default_bounds: (f64, f64),
+    /// Bounds on the noise variance
+    pub noise_bounds: (f64, f64),
+    /// Whether to optimize the kernel parameters, or keep them fixed
+    pub optimize_kernel: bool,
+    /// Whether to optimize the noise variance, or keep it fixed.
+    ///
+    /// Per-point noise is always kept fixed.
+    pub optimize_noise: bool,
+    /// Whether to move the inducing inputs, or keep them fixed
+    pub optimize_inducing: bool,
+    /// Settings for the L-BFGS minimizer
+    pub lbfgs: LbfgsOptions,
+}
+
+impl Default for SparseOptimizeOptions {
+    fn default() -> Self {
+        SparseOptimizeOptions {
+            bounds: None,
+            default_bounds: (1e-5, 1e5),
+            noise_bounds: (1e-8, 1e5),
+            optimize_kernel: true,
+            optimize_noise: true,
+            optimize_inducing: true,
+            lbfgs: LbfgsOptions::default(),
+        }
+    }
+}
+
+/// Fit the inducing inputs, kernel parameters and noise of a sparse GP by maximizing its objective
+///
+/// The mean function is kept fixed. The sparse GP's current parameters are the starting point.
+///
+/// # Examples
+/// ```rust
+/// use gprs::{
+///     kernels::RBF,
+///     sparse::{optimize::{fit, SparseOptimizeOptions}, SparseGP},
+/// };
+/// use nalgebra::{DMatrix, DVector};
+///
+/// let x = DMatrix::from_fn(1, 200, |_, j| j as f64 * 0.05);
+/// let y = DVector::from_iterator(x.ncols(), x.iter().map(|v| v.sin()));
+///
+/// // start with the inducing inputs bunched together
+/// let inducing = DMatrix::from_fn(1, 6, |_, j| 4.0 + j as f64 * 0.2);
+/// let gp = SparseGP::new(RBF::new(vec![1.0], 1.0), 0.1, inducing.clone());
+///
+/// let initial = SparseGP::new(RBF::new(vec![1.0], 1.0), 0.1, inducing)
+///     .compile(x.clone(), &y)
+///     .unwrap()
+///     .log_marginal_likelihood();
+///
+/// let fitted = fit(gp, x, &y, &SparseOptimizeOptions::default()).unwrap();
+///
+/// assert!(fitted.log_marginal_likelihood() > initial);
+/// ```
+pub fn fit<K, M>(
+    gp: SparseGP<K, M>,
+    x: DMatrix<f64>,
+    y: &DVector<f64>,
+    options: &SparseOptimizeOptions,
+) -> Result<CompiledSparseGP<K, M>, GPCompilationError>
+where
+    K: KernelGradient + KernelJacobian + Clone + for<'a> Parameterized<'a>,
+    M: MeanFunction + Clone,
+{
+    let space = SearchSpace::new(&gp, options)?;
+    let u0 = space.initial(&gp);
+
+    let objective = |u: &[f64]| {
+        let compiled = space.to_gp(&gp, u).compile(x.clone(), y).ok()?;
+        let value = compiled.log_marginal_likelihood();
+        let grad = space.gradient(u, &compiled)?;
+        // minimize the negative objective
+        Some((-value, grad.iter().map(|g| -g).collect()))
+    };
+
+    let u = minimize(objective, &u0, &space.lower, &space.upper, &options.lbfgs)
+        .map(|result| result.x)
+        .unwrap_or(u0);
+
+    space.to_gp(&gp, &u).compile(x, y)
+}
+
+/// Mapping between sparse GP parameters and the search variables of L-BFGS
+///
+/// The search variables are the log kernel parameters, then the log noise, then the inducing inputs
+/// in column-major order. Each group is only present if it is optimized.
+struct SearchSpace {
+    /// The kernel parameters if they are optimized, followed by the noise if it is optimized
+    magnitudes: LogMagnitudes,
+    /// The number of kernel parameters, or zero if the kernel is fixed
+    nkernel: usize,
+    /// Whether the noise follows the kernel parameters
+    optimize_noise: bool,
+    /// Whether the inducing inputs are at the end
+    optimize_inducing: bool,
+    lower: Vec<f64>,
+    upper: Vec<f64>,
+}
+
+impl SearchSpace {
+    fn new<K, M>(
+        gp: &SparseGP<K, M>,
+        options: &SparseOptimizeOptions,
+    ) -> Result<Self, GPCompilationError>
+    where
+        K: KernelGradient + for<'a> Parameterized<'a>,
+        M: MeanFunction,
+    {
+        let mut magnitudes = if options.optimize_kernel {
+            LogMagnitudes::new(
+                &gp.kernel().get_params(),
+                options.bounds.as_deref(),
+                options.default_bounds,
+            )?
+        } else {
+            LogMagnitudes::new(&[], None, options.default_bounds)?
+        };
+        let nkernel = magnitudes.len();
+
+        let optimize_noise = options.optimize_noise && gp.noise().constant().is_some();
+        if optimize_noise {
+            magnitudes.push_positive(options.noise_bounds)?;
+        }
+
+        let mut lower = magnitudes.lower.clone();
+        let mut upper = magnitudes.upper.clone();
+        if options.optimize_inducing {
+            lower.extend(std::iter::repeat_n(f64::NEG_INFINITY, gp.inducing().len()));
+            upper.extend(std::iter::repeat_n(f64::INFINITY, gp.inducing().len()));
+        }
+
+        Ok(SearchSpace {
+            magnitudes,
+            nkernel,
+            optimize_noise,
+            optimize_inducing: options.optimize_inducing,
+            lower,
+            upper,
+        })
+    }
+
+    /// The search variables for the sparse GP's current parameters
+    fn initial<K, M>(&self, gp: &SparseGP<K, M>) -> Vec<f64>
+    where
+        K: KernelGradient + for<'a> Parameterized<'a>,
+        M: MeanFunction,
+    {
+        let mut params = if self.nkernel > 0 {
+            gp.kernel().get_params()
+        } else {
+            Vec::new()
+        };
+        if self.optimize_noise {
+            params.extend(gp.noise().constant());
+        }
+
+        let mut u = self.magnitudes.to_search(&params);
+
+        if self.optimize_inducing {
+            u.extend(gp.inducing().iter());
+        }
+
+        u
+    }
+
+    /// The index of the first inducing input coordinate in the search variables
+    fn inducing_offset(&self) -> usize {
+        self.magnitudes.len()
+    }
+
+    /// Create a sparse GP from search variables
+    fn to_gp<K, M>(&self, gp: &SparseGP<K, M>, u: &[f64]) -> SparseGP<K, M>
+    where
+        K: KernelGradient + Clone + for<'a> Parameterized<'a>,
+        M: MeanFunction + Clone,
+    {
+        let params = self.magnitudes.to_params(u);
+
+        let mut kernel = gp.kernel().clone();
+        if self.nkernel > 0 {
+            kernel.set_params(&params[..self.nkernel]);
+        }
+
+        let noise = if self.optimize_noise {
+            Noise::Constant(params[self.nkernel])
+        } else {
+            gp.noise().clone()
+        };
+
+        let inducing = if self.optimize_inducing {
+            let shape = gp.inducing().shape();
+            DMatrix::from_column_slice(shape.0, shape.1, &u[self.inducing_offset()..])
+        } else {
+            gp.inducing().clone()
+        };
+
+        gp.rebuild(kernel, noise, inducing)
+    }
+
+    /// The gradient of the objective w.r.t. the search variables
+    fn gradient<K, M>(&self, u: &[f64], compiled: &CompiledSparseGP<K, M>) -> Option<Vec<f64>>
+    where
+        K: KernelGradient + KernelJacobian,
+        M: MeanFunction,
+    {
+        let mut params_grad = if self.nkernel > 0 {
+            compiled.log_marginal_likelihood_kernel_gradient().ok()?
+        } else {
+            Vec::new()
+        };
+        if self.optimize_noise {
+            params_grad.push(compiled.log_marginal_likelihood_noise_gradient());
+        }
+
+        let mut grad = self.magnitudes.to_search_gradient(u, &params_grad);
+
+        if self.optimize_inducing {
+            grad.extend(
+                compiled
+                    .log_marginal_likelihood_inducing_gradient()
+                    .ok()?
+                    .iter(),
+            );
+        }
+
+        Some(grad)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DMatrix, DVector};
+
+    use crate::{
+        kernels::RBF,
+        parameterized::Parameterized,
+        sparse::{Approximation, SparseGP},
+    };
+
+    use super::{fit, SparseOptimizeOptions};
+
+    fn data() -> (DMatrix<f64>, DVector<f64>) {
+        let x = DMatrix::from_fn(1, 100, |_, j| j as f64 * 0.1);
+        let y = DVector::from_fn(100, |i, _| (i as f64 * 0.1).sin());
+        (x, y)
+    }
+
+    /// Bunched inducing inputs spread out over the data, for both approximations
+    #[test]
+    fn test_fit_inducing() {
+        let (x, y) = data();
+        let inducing = DMatrix::from_fn(1, 5, |_, j| 4.0 + j as f64 * 0.1);
+
+        for approximation in [Approximation::VFE, Approximation::FITC] {
+            let gp = SparseGP::new(RBF::new(vec![1.0], 1.0), 0.1, inducing.clone())
+                .with_approximation(approximation);
+            let initial = SparseGP::new(RBF::new(vec![1.0], 1.0), 0.1, inducing.clone())
+                .with_approximation(approximation)
+                .compile(x.clone(), &y)
+                .unwrap()
+                .log_marginal_likelihood();
+
+            let fitted = fit(gp, x.clone(), &y, &SparseOptimizeOptions::default()).unwrap();
+
+            assert!(fitted.log_marginal_likelihood() > initial + 10.0);
+            let spread = fitted.inducing().max() - fitted.inducing().min();
+            assert!(spread > 2.0, "{:?}: {}", approximation, spread);
+        }
+    }
+
+    /// Fixed groups keep their values
+    #[test]
+    fn test_fixed() {
+        let (x, y) = data();
+        let inducing = DMatrix::from_fn(1, 5, |_, j| j as f64 * 2.0);
+        let gp = SparseGP::new(RBF::new(vec![0.5], 1.0), 0.3, inducing.clone());
+
+        let options = SparseOptimizeOptions {
+            optimize_noise: false,
+            optimize_inducing: false,
+            ..Default::default()
+        };
+        let fitted = fit(gp, x, &y, &options).unwrap();
+
+        assert_eq!(fitted.noise().constant(), Some(0.3));
+        assert_eq!(fitted.inducing(), &inducing);
+        assert_ne!(
+            fitted.kernel().get_params(),
+            RBF::new(vec![0.5], 1.0).get_params()
+        );
+    }
+}
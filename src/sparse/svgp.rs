@@ -0,0 +1,1030 @@
+use nalgebra::{Cholesky, DMatrix, DVector, Dynamic};
+use rand::{seq::index, Rng};
+
+use crate::{
+    gp::{
+        cholesky_with_jitter,
+        errors::GPCompilationError,
+        optimize::{
+            adam::{Adam, AdamOptions},
+            LogMagnitudes,
+        },
+        GPResult, JitterPolicy, Prediction,
+    },
+    kernels::{Kernel, KernelGradient, KernelJacobian, TriangleSide},
+    linalg::{
+        errors::IncompatibleShapeError, par_matmul, par_solve_lower_triangular_unchecked,
+        par_tr_matmul, par_tr_matmul_diag,
+    },
+    means::{MeanFunction, Zero},
+    parameterized::Parameterized,
+};
+
+use super::{
+    base::outer,
+    likelihood::{Likelihood, LikelihoodGradient},
+};
+
+/// The number of points evaluated at once by `elbo`, to bound its memory use
+const ELBO_CHUNK: usize = 4096;
+
+/// Settings for SVGP training
+#[derive(Debug, Clone)]
+pub struct SVGPOptions {
+    /// The number of training points sampled for each step, without replacement
+    pub batch_size: usize,
+    /// The number of Adam steps
+    pub iterations: usize,
+    /// Settings for the Adam minimizer
+    pub adam: AdamOptions,
+    /// Whether to train the kernel parameters, or keep them fixed
+    pub optimize_kernel: bool,
+    /// Whether to train the likelihood parameters, such as the gaussian noise, or keep them fixed
+    pub optimize_likelihood: bool,
+    /// Whether to move the inducing inputs, or keep them fixed
+    pub optimize_inducing: bool,
+}
+
+impl Default for SVGPOptions {
+    fn default() -> Self {
+        SVGPOptions {
+            batch_size: 256,
+            iterations: 1000,
+            adam: AdamOptions::default(),
+            optimize_kernel: true,
+            optimize_likelihood: true,
+            optimize_inducing: true,
+        }
+    }
+}
+
+/// Stochastic variational Gaussian Process (Hensman et al., 2013)
+///
+/// The inducing values are whitened, `u = Luu v`, and approximated by `q(v) = N(m, S S')`,
+/// where `S` is lower triangular. The marginals of the latent function at `x` are
+///
+/// `f = m* + A' m`
+///
+/// `var = k** - A' A + A' S S' A`
+///
+/// where `A = Luu^-1 Ku*`. Training maximizes the evidence lower bound,
+/// `ELBO = sum_i E[log p(y_i | f_i)] - KL(q(v) || N(0, I))`, from minibatches of the data,
+/// so each step costs `O(b m^2)` for a batch of `b` points, independent of the size of the data.
+///
+/// `train` also fits the kernel parameters, likelihood parameters and inducing inputs, unless they are
+/// fixed by `SVGPOptions`. The mean function is kept fixed.
+///
+/// # Examples
+/// ```rust
+/// use gprs::{
+///     kernels::RBF,
+///     sparse::{Bernoulli, SVGPOptions, SVGP},
+/// };
+/// use nalgebra::{DMatrix, DVector};
+/// use rand::{rngs::StdRng, SeedableRng};
+///
+/// // classify points by their sign
+/// let x = DMatrix::from_fn(1, 400, |_, j| j as f64 * 0.015 - 3.0);
+/// let y = DVector::from_iterator(400, x.iter().map(|v| if *v > 0.0 { 1.0 } else { 0.0 }));
+///
+/// let inducing = DMatrix::from_fn(1, 10, |_, j| j as f64 * 0.6 - 2.7);
+/// let mut svgp = SVGP::new(RBF::new(vec![1.0], 2.0), Bernoulli, inducing).unwrap();
+///
+/// let options = SVGPOptions { batch_size: 32, iterations: 500, ..Default::default() };
+/// let mut rng = StdRng::seed_from_u64(0);
+/// let trace = svgp.train(&x, &y, &options, &mut rng).unwrap();
+/// assert_eq!(trace.len(), 500);
+///
+/// let x_test = DMatrix::from_vec(1, 2, vec![-2.0, 2.0]);
+/// let probability = svgp.predict_observations(&x_test).unwrap().mean;
+/// assert!(probability[0] < 0.1 && probability[1] > 0.9);
+/// ```
+#[derive(Debug)]
+pub struct SVGP<K: Kernel, L: Likelihood, M: MeanFunction = Zero> {
+    kernel: K,
+    likelihood: L,
+    mean: M,
+    inducing: DMatrix<f64>,
+    /// The cholesky decomposition of (Kuu + jitter * I)
+    luu: Cholesky<f64, Dynamic>,
+    /// The jitter added to the diagonal of Kuu to make it positive definite
+    jitter: f64,
+    /// The jitter policy used to factorize `Kuu`
+    policy: Option<JitterPolicy>,
+    /// The variational mean of the whitened inducing values
+    q_mean: DVector<f64>,
+    /// The lower triangular square root of the variational covariance of the whitened inducing values
+    q_sqrt: DMatrix<f64>,
+}
+
+impl<K: Kernel, L: Likelihood> SVGP<K, L> {
+    /// Create an SVGP with inducing inputs in the columns of `inducing`, and `q(v)` equal to the prior
+    ///
+    /// `Kuu` is factorized with jitter from the default `JitterPolicy`, unless set with `with_jitter`.
+    pub fn new(
+        kernel: K,
+        likelihood: L,
+        inducing: DMatrix<f64>,
+    ) -> Result<Self, GPCompilationError> {
+        let policy = Some(JitterPolicy::default());
+        let (luu, jitter) = factorize(&kernel, &inducing, policy.as_ref())?;
+
+        let m = inducing.ncols();
+        Ok(SVGP {
+            kernel,
+            likelihood,
+            mean: Zero,
+            inducing,
+            luu,
+            jitter,
+            policy,
+            q_mean: DVector::zeros(m),
+            q_sqrt: DMatrix::identity(m, m),
+        })
+    }
+}
+
+impl<K: Kernel, L: Likelihood, M: MeanFunction> SVGP<K, L, M> {
+    /// Set the prior mean function
+    pub fn with_mean<N: MeanFunction>(self, mean: N) -> SVGP<K, L, N> {
+        SVGP {
+            kernel: self.kernel,
+            likelihood: self.likelihood,
+            mean,
+            inducing: self.inducing,
+            luu: self.luu,
+            jitter: self.jitter,
+            policy: self.policy,
+            q_mean: self.q_mean,
+            q_sqrt: self.q_sqrt,
+        }
+    }
+
+    /// Set the jitter policy used to factorize `Kuu`, and refactorize it
+    pub fn with_jitter(self, policy: JitterPolicy) -> Result<Self, GPCompilationError> {
+        let policy = Some(policy);
+        let (luu, jitter) = factorize(&self.kernel, &self.inducing, policy.as_ref())?;
+
+        Ok(SVGP {
+            luu,
+            jitter,
+            policy,
+            ..self
+        })
+    }
+
+    /// The covariance kernel
+    pub fn kernel(&self) -> &K {
+        &self.kernel
+    }
+
+    /// The observation likelihood
+    pub fn likelihood(&self) -> &L {
+        &self.likelihood
+    }
+
+    /// The prior mean function
+    pub fn mean_function(&self) -> &M {
+        &self.mean
+    }
+
+    /// The inducing inputs, one per column
+    pub fn inducing(&self) -> &DMatrix<f64> {
+        &self.inducing
+    }
+
+    /// The jitter that was added to the diagonal of `Kuu`
+    pub fn jitter(&self) -> f64 {
+        self.jitter
+    }
+
+    /// The jitter policy used to factorize `Kuu`
+    pub fn jitter_policy(&self) -> Option<&JitterPolicy> {
+        self.policy.as_ref()
+    }
+
+    /// The variational mean of the whitened inducing values
+    pub fn q_mean(&self) -> &DVector<f64> {
+        &self.q_mean
+    }
+
+    /// The lower triangular square root of the variational covariance of the whitened inducing values
+    pub fn q_sqrt(&self) -> &DMatrix<f64> {
+        &self.q_sqrt
+    }
+
+    /// Compute the ELBO on the full data set
+    pub fn elbo(&self, x: &DMatrix<f64>, y: &DVector<f64>) -> GPResult<f64> {
+        check_data(x, y)?;
+
+        let mut expectation = 0.0;
+        for start in (0..y.len()).step_by(ELBO_CHUNK) {
+            let len = ELBO_CHUNK.min(y.len() - start);
+            let (mean, var) = self.call(&x.columns(start, len).into_owned())?;
+            let expectations = self.expectations(&y.rows(start, len).into_owned(), &mean, &var);
+            expectation += expectations.iter().map(|(e, _, _)| e).sum::<f64>();
+        }
+
+        Ok(expectation - self.kl())
+    }
+
+    /// `KL(q(v) || N(0, I)) = 0.5 (tr(S S') + m'm - M - log|S S'|)`
+    fn kl(&self) -> f64 {
+        let log_det = self
+            .q_sqrt
+            .diagonal()
+            .iter()
+            .map(|v| v.abs().ln())
+            .sum::<f64>();
+
+        0.5 * (self.q_sqrt.norm_squared() + self.q_mean.norm_squared() - self.q_mean.len() as f64)
+            - log_det
+    }
+
+    /// Estimate the ELBO from a batch whose expectations are scaled by `scale`
+    fn batch_elbo(&self, x: &DMatrix<f64>, y: &DVector<f64>, scale: f64) -> GPResult<BatchElbo> {
+        let (a, p) = self.project(x)?;
+        let (mean, var) = self.marginals(x, &a, &p)?;
+        let expectations = self.expectations(y, &mean, &var);
+
+        Ok(BatchElbo {
+            elbo: scale * expectations.iter().map(|(e, _, _)| e).sum::<f64>() - self.kl(),
+            dmean: DVector::from_iterator(y.len(), expectations.iter().map(|(_, d, _)| scale * d)),
+            dvar: DVector::from_iterator(y.len(), expectations.iter().map(|(_, _, d)| scale * d)),
+            scale,
+            a,
+            p,
+            mean,
+            var,
+        })
+    }
+
+    /// Compute the gradient of a batch ELBO w.r.t. the variational parameters
+    ///
+    /// `dELBO/dm = A dE/dmean - m`
+    ///
+    /// `dELBO/dS = lower(2 A diag(dE/dvar) A' S - S + diag(S)^-1)`
+    fn variational_gradient(&self, batch: &BatchElbo) -> GPResult<Vec<f64>> {
+        let grad_mean = DVector::from_vec(par_matmul(&batch.a, &batch.dmean)?) - &self.q_mean;
+
+        let mut scaled = batch.a.clone();
+        scaled
+            .column_iter_mut()
+            .zip(batch.dvar.iter())
+            .for_each(|(mut col, dvar)| col *= 2.0 * dvar);
+        let mut grad_sqrt = outer(&scaled, &batch.p)? - &self.q_sqrt;
+        for i in 0..grad_sqrt.nrows() {
+            grad_sqrt[(i, i)] += 1.0 / self.q_sqrt[(i, i)];
+        }
+
+        let mut grad: Vec<f64> = grad_mean.iter().copied().collect();
+        grad.extend(lower_triangle(&grad_sqrt));
+        Ok(grad)
+    }
+
+    /// Compute the gradient of a batch ELBO w.r.t. `W = Kuf` and `U = Kuu`
+    ///
+    /// With `G = m dE/dmean' + 2 (S P - A) diag(dE/dvar)` the gradient w.r.t. `A`,
+    /// and `C = -G A'`, chained through `A = Luu^-1 W` and the cholesky decomposition of `U`,
+    ///
+    /// `dELBO/dW = Luu^-T G`
+    ///
+    /// `dELBO/dU = 0.5 Luu^-T (C_L + C_L' - diag(C)) Luu^-1`
+    ///
+    /// where `C_L` is the lower triangle of `C`. The KL term does not depend on either.
+    fn covariance_gradients(&self, batch: &BatchElbo) -> (DMatrix<f64>, DMatrix<f64>) {
+        let mut g = &self.q_sqrt * &batch.p - &batch.a;
+        g.column_iter_mut()
+            .zip(batch.dvar.iter())
+            .for_each(|(mut col, dvar)| col *= 2.0 * dvar);
+        g.ger(1.0, &self.q_mean, &batch.dmean, 1.0);
+
+        let c = -&g * batch.a.transpose();
+        let mut sym = c.lower_triangle();
+        sym += sym.transpose() - DMatrix::from_diagonal(&c.diagonal());
+        sym *= 0.5;
+
+        let l = self.luu.l_dirty();
+        let dw = l.tr_solve_lower_triangular_unchecked(&g);
+        let du = l.tr_solve_lower_triangular_unchecked(&sym);
+        let du = l
+            .tr_solve_lower_triangular_unchecked(&du.transpose())
+            .transpose();
+
+        (dw, du)
+    }
+
+    /// Compute the expected log likelihood of each point, with its derivatives w.r.t. the marginal mean and variance
+    fn expectations(
+        &self,
+        y: &DVector<f64>,
+        mean: &DVector<f64>,
+        var: &DVector<f64>,
+    ) -> Vec<(f64, f64, f64)> {
+        y.iter()
+            .zip(mean.iter().zip(var.iter()))
+            .map(|(y, (mean, var))| self.likelihood.variational_expectation(*y, *mean, *var))
+            .collect()
+    }
+
+    /// The variational parameters, `m` then the lower triangle of `S` in column-major order
+    fn variational_params(&self) -> Vec<f64> {
+        let mut params: Vec<f64> = self.q_mean.iter().copied().collect();
+        params.extend(lower_triangle(&self.q_sqrt));
+        params
+    }
+
+    /// Set the variational parameters, in the order of `variational_params`
+    fn set_variational_params(&mut self, params: &[f64]) {
+        let m = self.q_mean.len();
+        self.q_mean.copy_from_slice(&params[..m]);
+
+        let mut values = params[m..].iter();
+        for j in 0..m {
+            for i in j..m {
+                self.q_sqrt[(i, j)] = *values.next().unwrap();
+            }
+        }
+    }
+
+    /// Compute the mean and variance of the latent function from input data
+    pub fn call(&self, x: &DMatrix<f64>) -> GPResult<(DVector<f64>, DVector<f64>)> {
+        let (a, p) = self.project(x)?;
+        self.marginals(x, &a, &p)
+    }
+
+    /// Predict the latent function at `x`
+    pub fn predict(&self, x: &DMatrix<f64>) -> GPResult<Prediction> {
+        let (mean, var) = self.call(x)?;
+        Ok(Prediction { mean, var })
+    }
+
+    /// Predict observations at `x`, through the likelihood
+    ///
+    /// For a `Bernoulli` likelihood, the mean is the probability of the positive class.
+    pub fn predict_observations(&self, x: &DMatrix<f64>) -> GPResult<Prediction> {
+        let (mean, var) = self.call(x)?;
+        let (mean, var) = mean
+            .iter()
+            .zip(var.iter())
+            .map(|(mean, var)| self.likelihood.predict(*mean, *var))
+            .unzip::<_, _, Vec<f64>, Vec<f64>>();
+
+        Ok(Prediction {
+            mean: DVector::from_vec(mean),
+            var: DVector::from_vec(var),
+        })
+    }
+
+    /// Compute the mean of the latent function from input data
+    ///
+    /// `f = m* + A' m`
+    pub fn mean(&self, x: &DMatrix<f64>) -> GPResult<DVector<f64>> {
+        let k_u_xp = self.kernel.call(&self.inducing, x)?;
+        let a = par_solve_lower_triangular_unchecked(self.luu.l_dirty(), &k_u_xp);
+        Ok(self.mean.call(x)? + DVector::from_vec(par_tr_matmul(&a, &self.q_mean)?))
+    }
+
+    /// Compute just the diagonal variance of the latent function
+    pub fn var(&self, x: &DMatrix<f64>) -> GPResult<DVector<f64>> {
+        Ok(self.call(x)?.1)
+    }
+
+    /// Compute the full covariance matrix of the latent function from input data
+    ///
+    /// `V = K** - A' A + P' P`, where `P = S' A`
+    pub fn cov(&self, x: &DMatrix<f64>) -> GPResult<DMatrix<f64>> {
+        let (a, p) = self.project(x)?;
+
+        let mut k_xp_xp = self.kernel.call(x, x)?;
+        let explained = par_tr_matmul(&a, &a)?;
+        let covered = par_tr_matmul(&p, &p)?;
+
+        k_xp_xp
+            .iter_mut()
+            .zip(explained)
+            .zip(covered)
+            .for_each(|((k, e), c)| *k += c - e);
+
+        Ok(k_xp_xp)
+    }
+
+    /// Compute `A = Luu^-1 Ku*` and `P = S' A`
+    fn project(&self, x: &DMatrix<f64>) -> GPResult<(DMatrix<f64>, DMatrix<f64>)> {
+        let k_u_xp = self.kernel.call(&self.inducing, x)?;
+        let a = par_solve_lower_triangular_unchecked(self.luu.l_dirty(), &k_u_xp);
+        let p = DMatrix::from_vec(a.nrows(), a.ncols(), par_tr_matmul(&self.q_sqrt, &a)?);
+        Ok((a, p))
+    }
+
+    /// Find the marginal means and variances given precomputed A and P
+    fn marginals(
+        &self,
+        x: &DMatrix<f64>,
+        a: &DMatrix<f64>,
+        p: &DMatrix<f64>,
+    ) -> GPResult<(DVector<f64>, DVector<f64>)> {
+        let mean = self.mean.call(x)? + DVector::from_vec(par_tr_matmul(a, &self.q_mean)?);
+
+        let k_xp_xp = self.kernel.call_diagonal(x)?;
+        let explained = par_tr_matmul_diag(a, a)?;
+        let covered = par_tr_matmul_diag(p, p)?;
+        let var = DVector::from_iterator(
+            k_xp_xp.len(),
+            k_xp_xp
+                .into_iter()
+                .zip(explained)
+                .zip(covered)
+                // rounding can leave a tiny negative variance where Kuu explains the kernel exactly
+                .map(|((k, e), c)| (k - e + c).max(0.0)),
+        );
+
+        Ok((mean, var))
+    }
+}
+
+impl<K, L, M> SVGP<K, L, M>
+where
+    K: KernelGradient + KernelJacobian + for<'a> Parameterized<'a>,
+    L: LikelihoodGradient,
+    M: MeanFunction,
+{
+    /// Maximize the ELBO with Adam, from minibatches of `x` and `y` drawn with `rng`
+    ///
+    /// `q(v)` is always trained, along with the groups enabled in `options`. Kernel and likelihood
+    /// parameters are trained in log space, so they keep their sign, and a parameter of exactly zero
+    /// stays zero. Training continues from the current parameters. Returns the minibatch estimate of the ELBO
+    /// before each step, which is noisy but should trend upwards.
+    pub fn train<R: Rng + ?Sized>(
+        &mut self,
+        x: &DMatrix<f64>,
+        y: &DVector<f64>,
+        options: &SVGPOptions,
+        rng: &mut R,
+    ) -> Result<Vec<f64>, GPCompilationError> {
+        check_data(x, y).map_err(GPCompilationError::IncompatibleShapeError)?;
+
+        let n = y.len();
+        let batch = options.batch_size.clamp(1, n);
+        let scale = n as f64 / batch as f64;
+
+        let space = TrainingSpace::new(self, options)?;
+        let mut params = space.initial(self);
+        let mut adam = Adam::new(params.len(), options.adam.clone());
+        let mut trace = Vec::with_capacity(options.iterations);
+
+        for _ in 0..options.iterations {
+            let indices = index::sample(rng, n, batch).into_vec();
+            let (x_batch, y_batch) = (x.select_columns(&indices), y.select_rows(&indices));
+            let (elbo, grad) = space
+                .gradient(self, &x_batch, &y_batch, scale, &params)
+                .map_err(GPCompilationError::IncompatibleShapeError)?;
+            trace.push(elbo);
+
+            // minimize the negative ELBO
+            let grad: Vec<f64> = grad.iter().map(|g| -g).collect();
+            adam.step(&mut params, &grad);
+            space.apply(self, &params)?;
+        }
+
+        Ok(trace)
+    }
+
+    /// Compute the gradient of a batch ELBO w.r.t. the kernel parameters, in the order of `get_params()`
+    ///
+    /// The gradients w.r.t. `Kuf`, `Kuu` and the variance of each point are chained through the kernel jacobians.
+    fn kernel_gradient(
+        &self,
+        x: &DMatrix<f64>,
+        batch: &BatchElbo,
+        dw: &DMatrix<f64>,
+        du: &DMatrix<f64>,
+    ) -> GPResult<Vec<f64>> {
+        let jac_uf = self.kernel.cross_jacobian(&self.inducing, x)?;
+        let jac_uu = self.kernel.jacobian(&self.inducing);
+        let jac_ff = self.kernel.diagonal_jacobian(x)?;
+
+        let grad = jac_uf.tr_mul(&DVector::from_column_slice(dw.as_slice()))
+            + jac_uu.tr_mul(&DVector::from_column_slice(du.as_slice()))
+            + jac_ff.tr_mul(&batch.dvar);
+
+        Ok(grad.iter().copied().collect())
+    }
+
+    /// Compute the gradient of a batch ELBO w.r.t. the likelihood parameters, in the order of `get_params()`
+    fn likelihood_gradient(&self, y: &DVector<f64>, batch: &BatchElbo) -> Vec<f64> {
+        let mut grad = vec![0.0; self.likelihood.get_params().len()];
+
+        for (y, (mean, var)) in y.iter().zip(batch.mean.iter().zip(batch.var.iter())) {
+            let point = self
+                .likelihood
+                .variational_expectation_gradient(*y, *mean, *var);
+            grad.iter_mut()
+                .zip(point)
+                .for_each(|(g, d)| *g += batch.scale * d);
+        }
+
+        grad
+    }
+
+    /// Compute the gradient of a batch ELBO w.r.t. the inducing inputs, in the same shape as `inducing`
+    fn inducing_gradient(
+        &self,
+        x: &DMatrix<f64>,
+        dw: &DMatrix<f64>,
+        du: &DMatrix<f64>,
+    ) -> GPResult<DMatrix<f64>> {
+        // dk(z_k, x_j)/dz_kd is entry (j, k) of the kernel gradient of (X, Z),
+        // and Kuu depends on z_k through both row and column k
+        let grad_xz = self.kernel.gradient(x, &self.inducing)?;
+        let grad_zz = self.kernel.gradient(&self.inducing, &self.inducing)?;
+
+        let mut grad = DMatrix::zeros(self.inducing.nrows(), self.inducing.ncols());
+        for (d, (gx, gz)) in grad_xz.iter().zip(&grad_zz).enumerate() {
+            let row = dw.transpose().component_mul(gx).row_sum()
+                + 2.0 * du.transpose().component_mul(gz).row_sum();
+            grad.row_mut(d).copy_from(&row);
+        }
+
+        Ok(grad)
+    }
+}
+
+/// A minibatch estimate of the ELBO, with the intermediate values its gradients are computed from
+struct BatchElbo {
+    elbo: f64,
+    /// The factor that scales the batch expectations up to the size of the data
+    scale: f64,
+    /// `A = Luu^-1 Kuf` for the batch
+    a: DMatrix<f64>,
+    /// `P = S' A`
+    p: DMatrix<f64>,
+    /// The marginal mean of each point
+    mean: DVector<f64>,
+    /// The marginal variance of each point
+    var: DVector<f64>,
+    /// The scaled derivative of each expectation w.r.t. the marginal mean
+    dmean: DVector<f64>,
+    /// The scaled derivative of each expectation w.r.t. the marginal variance
+    dvar: DVector<f64>,
+}
+
+/// Mapping between SVGP parameters and the variables trained by Adam
+///
+/// The variables are the variational parameters, then the log kernel parameters, the log likelihood
+/// parameters, and the inducing inputs in column-major order. Each group after the variational parameters
+/// is only present if it is trained.
+struct TrainingSpace {
+    /// The number of variational parameters
+    nvariational: usize,
+    /// The kernel parameters if they are trained, followed by the likelihood parameters if they are trained
+    magnitudes: LogMagnitudes,
+    /// The number of kernel parameters, or zero if the kernel is fixed
+    nkernel: usize,
+    /// Whether the likelihood parameters follow the kernel parameters
+    optimize_likelihood: bool,
+    /// Whether the inducing inputs are at the end
+    optimize_inducing: bool,
+}
+
+impl TrainingSpace {
+    fn new<K, L, M>(svgp: &SVGP<K, L, M>, options: &SVGPOptions) -> Result<Self, GPCompilationError>
+    where
+        K: KernelGradient + KernelJacobian + for<'a> Parameterized<'a>,
+        L: LikelihoodGradient,
+        M: MeanFunction,
+    {
+        let params =
+            Self::hyperparameters(svgp, options.optimize_kernel, options.optimize_likelihood);
+
+        Ok(TrainingSpace {
+            nvariational: svgp.variational_params().len(),
+            // Adam is unbounded
+            magnitudes: LogMagnitudes::unbounded(&params),
+            nkernel: if options.optimize_kernel {
+                svgp.kernel.get_params().len()
+            } else {
+                0
+            },
+            optimize_likelihood: options.optimize_likelihood,
+            optimize_inducing: options.optimize_inducing,
+        })
+    }
+
+    /// The kernel parameters followed by the likelihood parameters, for the groups that are trained
+    fn hyperparameters<K, L, M>(svgp: &SVGP<K, L, M>, kernel: bool, likelihood: bool) -> Vec<f64>
+    where
+        K: KernelGradient + KernelJacobian + for<'a> Parameterized<'a>,
+        L: LikelihoodGradient,
+        M: MeanFunction,
+    {
+        let mut params = if kernel {
+            svgp.kernel.get_params()
+        } else {
+            Vec::new()
+        };
+        if likelihood {
+            params.extend(svgp.likelihood.get_params());
+        }
+        params
+    }
+
+    /// The variables for the SVGP's current parameters
+    fn initial<K, L, M>(&self, svgp: &SVGP<K, L, M>) -> Vec<f64>
+    where
+        K: KernelGradient + KernelJacobian + for<'a> Parameterized<'a>,
+        L: LikelihoodGradient,
+        M: MeanFunction,
+    {
+        let mut u = svgp.variational_params();
+        let params = Self::hyperparameters(svgp, self.nkernel > 0, self.optimize_likelihood);
+        u.extend(self.magnitudes.to_search(&params));
+
+        if self.optimize_inducing {
+            u.extend(svgp.inducing.iter());
+        }
+
+        u
+    }
+
+    /// The index of the first inducing input coordinate in the variables
+    fn inducing_offset(&self) -> usize {
+        self.nvariational + self.magnitudes.len()
+    }
+
+    /// Set the SVGP's parameters from the variables, and refactorize `Kuu` if it changed
+    ///
+    /// If `Kuu` cannot be factorized, the SVGP is left unchanged.
+    fn apply<K, L, M>(&self, svgp: &mut SVGP<K, L, M>, u: &[f64]) -> Result<(), GPCompilationError>
+    where
+        K: KernelGradient + KernelJacobian + for<'a> Parameterized<'a>,
+        L: LikelihoodGradient,
+        M: MeanFunction,
+    {
+        if self.nkernel == 0 && !self.optimize_inducing {
+            self.set(svgp, u);
+            return Ok(());
+        }
+
+        let previous = (
+            svgp.variational_params(),
+            svgp.kernel.get_params(),
+            svgp.likelihood.get_params(),
+            svgp.inducing.clone(),
+        );
+        self.set(svgp, u);
+
+        match factorize(&svgp.kernel, &svgp.inducing, svgp.policy.as_ref()) {
+            Ok((luu, jitter)) => {
+                (svgp.luu, svgp.jitter) = (luu, jitter);
+                Ok(())
+            }
+            Err(err) => {
+                let (variational, kernel, likelihood, inducing) = previous;
+                svgp.set_variational_params(&variational);
+                svgp.kernel.set_params(&kernel);
+                svgp.likelihood.set_params(&likelihood);
+                svgp.inducing = inducing;
+                Err(err)
+            }
+        }
+    }
+
+    /// Set the SVGP's parameters from the variables, without refactorizing `Kuu`
+    fn set<K, L, M>(&self, svgp: &mut SVGP<K, L, M>, u: &[f64])
+    where
+        K: KernelGradient + KernelJacobian + for<'a> Parameterized<'a>,
+        L: LikelihoodGradient,
+        M: MeanFunction,
+    {
+        svgp.set_variational_params(&u[..self.nvariational]);
+
+        let params = self.magnitudes.to_params(&u[self.nvariational..]);
+        if self.nkernel > 0 {
+            svgp.kernel.set_params(&params[..self.nkernel]);
+        }
+        if self.optimize_likelihood {
+            svgp.likelihood.set_params(&params[self.nkernel..]);
+        }
+        if self.optimize_inducing {
+            svgp.inducing
+                .as_mut_slice()
+                .copy_from_slice(&u[self.inducing_offset()..]);
+        }
+    }
+
+    /// Estimate the ELBO from a batch whose expectations are scaled by `scale`,
+    /// and its gradient w.r.t. the variables `u`
+    fn gradient<K, L, M>(
+        &self,
+        svgp: &SVGP<K, L, M>,
+        x: &DMatrix<f64>,
+        y: &DVector<f64>,
+        scale: f64,
+        u: &[f64],
+    ) -> GPResult<(f64, Vec<f64>)>
+    where
+        K: KernelGradient + KernelJacobian + for<'a> Parameterized<'a>,
+        L: LikelihoodGradient,
+        M: MeanFunction,
+    {
+        let batch = svgp.batch_elbo(x, y, scale)?;
+        let mut grad = svgp.variational_gradient(&batch)?;
+        let (dw, du) = svgp.covariance_gradients(&batch);
+
+        let mut params_grad = if self.nkernel > 0 {
+            svgp.kernel_gradient(x, &batch, &dw, &du)?
+        } else {
+            Vec::new()
+        };
+        if self.optimize_likelihood {
+            params_grad.extend(svgp.likelihood_gradient(y, &batch));
+        }
+        grad.extend(
+            self.magnitudes
+                .to_search_gradient(&u[self.nvariational..], &params_grad),
+        );
+
+        if self.optimize_inducing {
+            grad.extend(svgp.inducing_gradient(x, &dw, &du)?.iter());
+        }
+
+        Ok((batch.elbo, grad))
+    }
+}
+
+/// Factorize `Kuu` with jitter from `policy`
+fn factorize<K: Kernel>(
+    kernel: &K,
+    inducing: &DMatrix<f64>,
+    policy: Option<&JitterPolicy>,
+) -> Result<(Cholesky<f64, Dynamic>, f64), GPCompilationError> {
+    let kuu = kernel
+        .call_triangular(inducing, TriangleSide::LOWER)
+        .map_err(GPCompilationError::IncompatibleShapeError)?;
+    cholesky_with_jitter(kuu, policy)
+}
+
+/// Check that there is one output for each input, and at least one of each
+fn check_data(x: &DMatrix<f64>, y: &DVector<f64>) -> GPResult<()> {
+    if x.ncols() != y.len() || y.is_empty() {
+        return Err(IncompatibleShapeError {
+            shapes: vec![x.shape(), y.shape()],
+        });
+    }
+    Ok(())
+}
+
+/// The lower triangle of a square matrix, in column-major order
+fn lower_triangle(mat: &DMatrix<f64>) -> impl Iterator<Item = f64> + '_ {
+    let n = mat.nrows();
+    (0..n).flat_map(move |j| (j..n).map(move |i| mat[(i, j)]))
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DMatrix, DVector};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{
+        gp::{optimize::adam::AdamOptions, JitterPolicy, GP},
+        kernels::RBF,
+        parameterized::Parameterized,
+        sparse::{Bernoulli, Gaussian, LikelihoodGradient, Poisson},
+    };
+
+    use super::{SVGPOptions, TrainingSpace, SVGP};
+
+    fn data() -> (DMatrix<f64>, DVector<f64>) {
+        let x = DMatrix::from_fn(1, 10, |_, j| j as f64 * 0.6);
+        let y = DVector::from_fn(10, |i, _| (i as f64 * 0.6).sin());
+        (x, y)
+    }
+
+    /// With the training inputs as inducing inputs, the optimal q(v) is the exact posterior
+    #[test]
+    fn test_gaussian_matches_exact() {
+        let (x, y) = data();
+        let kernel = RBF::new(vec![1.0], 1.0);
+
+        let exact = GP::new(kernel.clone(), 0.1).compile(x.clone(), &y).unwrap();
+        let mut svgp = SVGP::new(kernel, Gaussian::new(0.1), x.clone()).unwrap();
+
+        let options = SVGPOptions {
+            batch_size: 10,
+            iterations: 3000,
+            adam: AdamOptions {
+                learning_rate: 0.02,
+                ..Default::default()
+            },
+            optimize_kernel: false,
+            optimize_likelihood: false,
+            optimize_inducing: false,
+        };
+        let trace = svgp
+            .train(&x, &y, &options, &mut StdRng::seed_from_u64(0))
+            .unwrap();
+
+        let elbo = svgp.elbo(&x, &y).unwrap();
+        // the trace is recorded before each step, and the steps still oscillate slightly
+        assert!((trace[2999] - elbo).abs() < 1e-2);
+        assert!(elbo <= exact.log_marginal_likelihood() + 1e-9);
+        assert!(elbo > exact.log_marginal_likelihood() - 1e-3);
+
+        let x_test = DMatrix::from_vec(1, 3, vec![0.3, 2.0, 4.1]);
+        let (mean, var) = svgp.call(&x_test).unwrap();
+        let (exact_mean, exact_var) = exact.call(&x_test).unwrap();
+        assert!((mean - exact_mean).amax() < 1e-2);
+        assert!((var - exact_var).amax() < 1e-2);
+        assert!((svgp.cov(&x_test).unwrap() - exact.cov(&x_test).unwrap()).amax() < 1e-2);
+    }
+
+    /// Check the gradient w.r.t. every trained variable against central finite differences
+    fn check_gradient<L: LikelihoodGradient>(likelihood: L, y: &DVector<f64>) {
+        let (x, _) = data();
+        let inducing = DMatrix::from_vec(1, 3, vec![0.5, 2.5, 4.5]);
+
+        let mut svgp = SVGP::new(RBF::new(vec![1.0], 1.5), likelihood, inducing).unwrap();
+        let space = TrainingSpace::new(&svgp, &SVGPOptions::default()).unwrap();
+        let mut u = space.initial(&svgp);
+        u.iter_mut()
+            .take(9)
+            .enumerate()
+            .for_each(|(i, v)| *v = 0.3 + 0.1 * (i as f64).sin());
+        space.apply(&mut svgp, &u).unwrap();
+
+        let (_, grad) = space.gradient(&svgp, &x, y, 2.0, &u).unwrap();
+        assert_eq!(grad.len(), u.len());
+
+        let step = 1e-6;
+        for i in 0..u.len() {
+            let mut elbo = |delta: f64| {
+                let mut shifted = u.clone();
+                shifted[i] += delta;
+                space.apply(&mut svgp, &shifted).unwrap();
+                svgp.batch_elbo(&x, y, 2.0).unwrap().elbo
+            };
+            let fd = (elbo(step) - elbo(-step)) / (2.0 * step);
+            assert!((grad[i] - fd).abs() < 1e-5, "{}: {} != {}", i, grad[i], fd);
+        }
+    }
+
+    /// The ELBO gradient matches central finite differences
+    #[test]
+    fn test_gradient_finite_difference() {
+        let (_, y) = data();
+        check_gradient(Gaussian::new(0.3), &y);
+        check_gradient(Bernoulli, &y.map(|v| if v > 0.0 { 1.0 } else { 0.0 }));
+    }
+
+    /// Training the kernel, noise and inducing inputs improves on training q(v) alone
+    #[test]
+    fn test_hyperparameters() {
+        let x = DMatrix::from_fn(1, 200, |_, j| j as f64 * 0.05);
+        let y = DVector::from_iterator(200, x.iter().map(|v| v.sin()));
+        let inducing = DMatrix::from_fn(1, 6, |_, j| 4.0 + j as f64 * 0.1);
+
+        let train = |optimize: bool| {
+            let mut svgp = SVGP::new(
+                RBF::new(vec![3.0], 0.5),
+                Gaussian::new(1.0),
+                inducing.clone(),
+            )
+            .unwrap();
+            let options = SVGPOptions {
+                batch_size: 50,
+                iterations: 1000,
+                adam: AdamOptions {
+                    learning_rate: 0.05,
+                    ..Default::default()
+                },
+                optimize_kernel: optimize,
+                optimize_likelihood: optimize,
+                optimize_inducing: optimize,
+            };
+            svgp.train(&x, &y, &options, &mut StdRng::seed_from_u64(3))
+                .unwrap();
+            svgp
+        };
+
+        let fixed = train(false);
+        let trained = train(true);
+        let (elbo_fixed, elbo_trained) =
+            (fixed.elbo(&x, &y).unwrap(), trained.elbo(&x, &y).unwrap());
+        assert!(
+            elbo_trained > elbo_fixed + 100.0,
+            "{} {}",
+            elbo_trained,
+            elbo_fixed
+        );
+        assert!(
+            trained.likelihood().noise < 0.1,
+            "{}",
+            trained.likelihood().noise
+        );
+        assert!(trained.inducing().max() - trained.inducing().min() > 2.0);
+        assert_eq!(fixed.inducing(), &inducing);
+    }
+
+    /// The jitter policy is used to factorize Kuu, and a failed factorization leaves the SVGP unchanged
+    #[test]
+    fn test_jitter() {
+        // identical inducing inputs make Kuu singular
+        let duplicate = DMatrix::from_vec(1, 2, vec![1.0, 1.0]);
+        let no_jitter = JitterPolicy {
+            initial: 1.0,
+            factor: 10.0,
+            max: 0.0,
+        };
+
+        let svgp = SVGP::new(RBF::new(vec![1.0], 1.0), Gaussian::new(0.1), duplicate).unwrap();
+        assert!(svgp.jitter() > 0.0);
+        assert!(svgp.with_jitter(no_jitter).is_err());
+
+        let inducing = DMatrix::from_vec(1, 2, vec![0.0, 2.0]);
+        let mut svgp = SVGP::new(RBF::new(vec![1.0], 1.0), Gaussian::new(0.1), inducing.clone())
+            .unwrap()
+            .with_jitter(no_jitter)
+            .unwrap();
+        assert_eq!(svgp.jitter_policy(), Some(&no_jitter));
+
+        let space = TrainingSpace::new(&svgp, &SVGPOptions::default()).unwrap();
+        let x_test = DMatrix::from_vec(1, 3, vec![-1.0, 1.0, 3.0]);
+        let before = svgp.call(&x_test).unwrap();
+        let (kernel, likelihood) = (svgp.kernel().get_params(), svgp.likelihood().get_params());
+
+        let mut u = space.initial(&svgp);
+        u.iter_mut().for_each(|v| *v += 0.1);
+        let len = u.len();
+        u[len - 2..].copy_from_slice(&[1.0, 1.0]);
+
+        assert!(space.apply(&mut svgp, &u).is_err());
+        assert_eq!(svgp.inducing(), &inducing);
+        assert_eq!(svgp.kernel().get_params(), kernel);
+        assert_eq!(svgp.likelihood().get_params(), likelihood);
+        assert_eq!(svgp.call(&x_test).unwrap(), before);
+    }
+
+    /// Minibatch training follows the rate of count data, and is repeatable with the same seed
+    #[test]
+    fn test_poisson() {
+        let x = DMatrix::from_fn(1, 300, |_, j| j as f64 * 0.02);
+        let rate = |x: f64| (1.0 + x.sin()).exp();
+        // deterministic counts close to the rate
+        let y = DVector::from_iterator(300, x.iter().map(|v| rate(*v).round()));
+        let inducing = DMatrix::from_fn(1, 8, |_, j| j as f64 * 0.85);
+
+        let options = SVGPOptions {
+            batch_size: 50,
+            iterations: 1500,
+            adam: AdamOptions {
+                learning_rate: 0.05,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let train = || {
+            let mut svgp = SVGP::new(RBF::new(vec![1.0], 1.0), Poisson, inducing.clone()).unwrap();
+            let trace = svgp
+                .train(&x, &y, &options, &mut StdRng::seed_from_u64(7))
+                .unwrap();
+            (svgp, trace)
+        };
+
+        let (svgp, trace) = train();
+        assert_eq!(trace, train().1);
+
+        let x_test = DMatrix::from_vec(1, 3, vec![1.0, 3.0, 5.0]);
+        let prediction = svgp.predict_observations(&x_test).unwrap();
+        for (x, mean) in x_test.iter().zip(prediction.mean.iter()) {
+            assert!((mean / rate(*x) - 1.0).abs() < 0.2, "{}: {}", x, mean);
+        }
+    }
+
+    #[test]
+    fn test_invalid_shapes() {
+        let (x, y) = data();
+        let mut svgp = SVGP::new(RBF::new(vec![1.0], 1.0), Gaussian::new(0.1), x.clone()).unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let short = DVector::from_vec(vec![1.0]);
+        assert!(svgp
+            .train(&x, &short, &SVGPOptions::default(), &mut rng)
+            .is_err());
+        assert!(svgp.elbo(&x, &short).is_err());
+
+        let empty = DMatrix::zeros(1, 0);
+        assert!(svgp
+            .train(
+                &empty,
+                &DVector::zeros(0),
+                &SVGPOptions::default(),
+                &mut rng
+            )
+            .is_err());
+
+        let wrong_dims = DMatrix::zeros(2, 10);
+        assert!(svgp.call(&wrong_dims).is_err());
+        assert!(svgp.elbo(&wrong_dims, &y).is_err());
+    }
+}